@@ -81,6 +81,19 @@ impl Bounds {
     }
 
     pub fn intersects(&self, ray: &Ray) -> bool {
+        self.intersect_ts(ray).is_some()
+    }
+
+    // does the ray pass through the box somewhere in t = 0 -> max_t
+    pub fn intersects_within(&self, ray: &Ray, max_t: math::F3D) -> bool {
+        matches!(self.intersect_ts(ray), Some((tmin, _)) if tmin <= max_t)
+    }
+
+    /**
+     * Entry and exit t of the ray through the box, None on a miss or when
+     * the box is entirely behind the ray. Entry is negative from inside.
+     */
+    pub fn intersect_ts(&self, ray: &Ray) -> Option<(math::F3D, math::F3D)> {
         let (xtmin, xtmax) =
             Cube::check_axis(ray.origin.x, ray.direction.x, self.min.x, self.max.x);
         let (ytmin, ytmax) =
@@ -88,15 +101,47 @@ impl Bounds {
         let (ztmin, ztmax) =
             Cube::check_axis(ray.origin.z, ray.direction.z, self.min.z, self.max.z);
 
+        let tmin = glm::max3_scalar(xtmin, ytmin, ztmin);
         let tmax = glm::min3_scalar(xtmax, ytmax, ztmax);
-        if tmax < 0.0 {
-            false
+        if tmax < 0.0 || tmin > tmax {
+            None
         } else {
-            let tmin = glm::max3_scalar(xtmin, ytmin, ztmin);
-            tmin <= tmax
+            Some((tmin, tmax))
         }
     }
 
+    // sphere through the corners of the box -> (center, radius)
+    pub fn bounding_sphere(&self) -> (Point, math::F3D) {
+        let half = (self.max - self.min) / 2.0;
+        (self.min + half, half.magnitude())
+    }
+
+    // box edge lengths, None for empty (default) bounds
+    fn extents(&self) -> Option<Vector> {
+        let d = self.max - self.min;
+        if d.x < 0.0 || d.y < 0.0 || d.z < 0.0 {
+            None
+        } else {
+            Some(d)
+        }
+    }
+
+    pub fn volume(&self) -> math::F3D {
+        self.extents().map_or(0.0, |d| d.x * d.y * d.z)
+    }
+
+    // for surface area heuristic splits, a ray's chance of hitting a box grows with its area
+    // unbounded boxes (planes) are infinite rather than NaN from 0 * inf
+    pub fn surface_area(&self) -> math::F3D {
+        self.extents().map_or(0.0, |d| {
+            if d.iter().any(|e| e.is_infinite()) {
+                math::INFINITY
+            } else {
+                2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+            }
+        })
+    }
+
     pub fn split(&self) -> (Bounds, Bounds) {
         let d = self.max - self.min;
         let greatest = d.max();
@@ -256,6 +301,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn entry_and_exit_of_bounding_box() {
+        let b = Bounds::new(point(-1.0, -1.0, -1.0), point(1.0, 1.0, 1.0));
+        let ray = Ray::new(point(0.0, 0.0, -5.0), vector_z());
+        assert_eq!(b.intersect_ts(&ray), Some((4.0, 6.0)));
+        assert_eq!(
+            b.intersect_ts(&Ray::new(point_zero(), vector_z())),
+            Some((-1.0, 1.0))
+        );
+        assert_eq!(
+            b.intersect_ts(&Ray::new(point(2.0, 0.0, -5.0), vector_z())),
+            None
+        );
+        assert_eq!(
+            b.intersect_ts(&Ray::new(point(0.0, 0.0, 5.0), vector_z())),
+            None
+        );
+    }
+
+    #[test]
+    fn intersecting_bounding_box_within_distance() {
+        let b = Bounds::new(point(-1.0, -1.0, -1.0), point(1.0, 1.0, 1.0));
+        let ray = Ray::new(point(0.0, 0.0, -5.0), vector_z());
+        assert!(b.intersects_within(&ray, 4.0));
+        assert!(!b.intersects_within(&ray, 3.9));
+        // starting inside always counts
+        assert!(b.intersects_within(&Ray::new(point_zero(), vector_z()), 0.1));
+    }
+
     #[test]
     fn splitting_a_perfect_cube() {
         let b = Bounds::new(point(-1.0, -4.0, -5.0), point(9.0, 6.0, 5.0));
@@ -295,4 +369,29 @@ mod tests {
         assert_eq!(right.min, point(-1.0, -2.0, 2.0));
         assert_eq!(right.max, point(5.0, 3.0, 7.0));
     }
+
+    #[test]
+    fn bounding_sphere_of_box() {
+        let b = Bounds::new(point(-1.0, 0.0, 2.0), point(3.0, 3.0, 2.0));
+        let (center, radius) = b.bounding_sphere();
+        assert_eq!(center, point(1.0, 1.5, 2.0));
+        assert_eq!(radius, 2.5);
+    }
+
+    #[test]
+    fn volume_and_surface_area() {
+        let b = Bounds::new(point(-1.0, -1.0, -1.0), point(1.0, 1.0, 1.0));
+        assert_eq!(b.volume(), 8.0);
+        assert_eq!(b.surface_area(), 24.0);
+        let flat = Bounds::new(point(0.0, 0.0, 0.0), point(2.0, 0.0, 3.0));
+        assert_eq!(flat.volume(), 0.0);
+        assert_eq!(flat.surface_area(), 12.0);
+        assert_eq!(Bounds::default().volume(), 0.0);
+        assert_eq!(Bounds::default().surface_area(), 0.0);
+        let plane = Bounds::new(
+            point(-math::INFINITY, 0.0, -math::INFINITY),
+            point(math::INFINITY, 0.0, math::INFINITY),
+        );
+        assert_eq!(plane.surface_area(), math::INFINITY);
+    }
 }
@@ -1,16 +1,45 @@
 use crate::canvas::Canvas;
 use crate::color::*;
+use crate::intersection::Intersections;
 use crate::math::*;
 use crate::matrix::Matrix4;
 use crate::ray::Ray;
+use crate::transformation::{view_transform, Transformable};
 use crate::tuple::*;
 use crate::world::World;
 use glm;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use rayon::prelude::*;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 const OPT_PARALLEL: bool = false;
+// fewest pixels of a row rendered by one parallel task
+const PIXELS_PER_TASK: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Projection {
+    Perspective,
+    // parallel rays, no vanishing point
+    Orthographic,
+}
+
+// auxiliary buffers for compositing and debugging, see Camera::render_aov
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aov {
+    // distance to the first hit in every channel, infinity where nothing is hit
+    Depth,
+    // world normal remapped from -1 -> 1 to 0 -> 1, black background
+    Normal,
+    // a color hashed from the hit object's id, black background
+    ObjectId,
+}
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Camera {
     hsize: usize,
     vsize: usize,
@@ -18,24 +47,40 @@ pub struct Camera {
     half_height: F3D,
     fov: F3D,
     pixel_size: F3D,
+    pub projection: Projection,
+    // rays per pixel, jittered when > 1
+    pub samples: usize,
+    // None jitters with thread_rng, Some(seed) makes renders reproducible
+    pub seed: Option<u64>,
+    // times (0 -> 1) the shutter is open between, sampled per ray when samples > 1.
+    // Equal times freeze moving objects.
+    pub shutter_open: F3D,
+    pub shutter_close: F3D,
+    #[cfg_attr(feature = "serde", serde(with = "crate::scene::matrix_serde"))]
     pub transform: Matrix4,
 }
 
+// fit half_view to the longer image side -> (half_width, half_height, pixel_size)
+fn view_extent(hsize: usize, vsize: usize, half_view: F3D) -> (F3D, F3D, F3D) {
+    let aspect = hsize as F3D / vsize as F3D;
+    let half_width = if aspect >= 1.0 {
+        half_view
+    } else {
+        half_view * aspect
+    };
+    let half_height = if aspect >= 1.0 {
+        half_view / aspect
+    } else {
+        half_view
+    };
+    let pixel_size = (half_width * 2.0) / hsize as F3D;
+    (half_width, half_height, pixel_size)
+}
+
 impl Camera {
     pub fn new(hsize: usize, vsize: usize, field_of_view: F3D) -> Camera {
         let half_view = (field_of_view / 2.0).tan();
-        let aspect = hsize as F3D / vsize as F3D;
-        let half_width = if aspect >= 1.0 {
-            half_view
-        } else {
-            half_view * aspect
-        };
-        let half_height = if aspect >= 1.0 {
-            half_view / aspect
-        } else {
-            half_view
-        };
-        let pixel_size = (half_width * 2.0) / hsize as F3D;
+        let (half_width, half_height, pixel_size) = view_extent(hsize, vsize, half_view);
 
         Camera {
             hsize,
@@ -44,50 +89,305 @@ impl Camera {
             half_height,
             fov: field_of_view,
             pixel_size,
+            projection: Projection::Perspective,
+            samples: 1,
+            seed: None,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            transform: glm::identity(),
+        }
+    }
+
+    /**
+     * Parallel projection camera. world_width is the width of the
+     * view plane in world units (the longer image side).
+     */
+    pub fn orthographic(hsize: usize, vsize: usize, world_width: F3D) -> Camera {
+        let (half_width, half_height, pixel_size) = view_extent(hsize, vsize, world_width / 2.0);
+
+        Camera {
+            hsize,
+            vsize,
+            half_width,
+            half_height,
+            fov: 0.0,
+            pixel_size,
+            projection: Projection::Orthographic,
+            samples: 1,
+            seed: None,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
             transform: glm::identity(),
         }
     }
 
+    pub fn hsize(&self) -> usize {
+        self.hsize
+    }
+
+    pub fn vsize(&self) -> usize {
+        self.vsize
+    }
+
+    pub fn fov(&self) -> F3D {
+        self.fov
+    }
+
+    pub fn pixel_size(&self) -> F3D {
+        self.pixel_size
+    }
+
+    // change the image size, keeping the same view
+    pub fn resize(&mut self, hsize: usize, vsize: usize) {
+        let half_view = match self.projection {
+            Projection::Perspective => (self.fov / 2.0).tan(),
+            Projection::Orthographic => self.half_width.max(self.half_height),
+        };
+        let (half_width, half_height, pixel_size) = view_extent(hsize, vsize, half_view);
+        self.hsize = hsize;
+        self.vsize = vsize;
+        self.half_width = half_width;
+        self.half_height = half_height;
+        self.pixel_size = pixel_size;
+    }
+
+    pub fn with_samples(mut self, samples: usize) -> Camera {
+        self.samples = samples;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Camera {
+        self.seed = Some(seed);
+        self
+    }
+
+    // motion blur, needs samples > 1 to spread rays over the interval
+    pub fn with_shutter(mut self, open: F3D, close: F3D) -> Camera {
+        self.shutter_open = open;
+        self.shutter_close = close;
+        self
+    }
+
+    // point the camera from `from` at `to`, see view_transform
+    pub fn look_at(&mut self, from: Point, to: Point, up: Vector) {
+        self.transform = view_transform(&from, &to, &up);
+    }
+
+    pub fn looking_at(mut self, from: Point, to: Point, up: Vector) -> Camera {
+        self.look_at(from, to, up);
+        self
+    }
+
     pub fn ray_for_pixel(&self, x: usize, y: usize) -> Ray {
-        let xoffset = (x as F3D + 0.5) * self.pixel_size;
-        let yoffset = (y as F3D + 0.5) * self.pixel_size;
+        self.ray_for_pixel_offset(x, y, 0.5, 0.5)
+    }
+
+    // (dx, dy) is the position inside the pixel, each in [0, 1)
+    fn ray_for_pixel_offset(&self, x: usize, y: usize, dx: F3D, dy: F3D) -> Ray {
+        let xoffset = (x as F3D + dx) * self.pixel_size;
+        let yoffset = (y as F3D + dy) * self.pixel_size;
 
         let world_x = self.half_width - xoffset;
         let world_y = self.half_height - yoffset;
 
-        let pixel = glm::inverse(&self.transform) * point(world_x, world_y, -1.0);
-        let origin = glm::inverse(&self.transform) * point_zero();
-        let direction = (pixel - origin).normalize();
+        let inv = glm::inverse(&self.transform);
+        match self.projection {
+            Projection::Perspective => {
+                let pixel = inv * point(world_x, world_y, -1.0);
+                let origin = inv * point_zero();
+                let direction = (pixel - origin).normalize();
+                Ray::new(origin, direction)
+            }
+            Projection::Orthographic => {
+                let origin = inv * point(world_x, world_y, 0.0);
+                let direction = (inv * vector(0.0, 0.0, -1.0)).normalize();
+                Ray::new(origin, direction)
+            }
+        }
+    }
 
-        Ray { origin, direction }
+    // camera origin in world space
+    pub fn position(&self) -> Point {
+        glm::inverse(&self.transform) * point_zero()
+    }
+
+    fn sample_pixel<'w, R: Rng>(
+        &self,
+        world: &'w World,
+        x: usize,
+        y: usize,
+        rng: &mut R,
+        buf: &mut Intersections<'w>,
+    ) -> Color {
+        if self.samples <= 1 {
+            let r = self.ray_for_pixel(x, y).with_time(self.shutter_open);
+            return world.color_at_with(&r, buf);
+        }
+        let shutter = self.shutter_close - self.shutter_open;
+        let total = (0..self.samples).fold(Color::black(), |acc, _| {
+            let mut r = self.ray_for_pixel_offset(x, y, rng.gen(), rng.gen());
+            if shutter > 0.0 {
+                r.time = self.shutter_open + rng.gen::<F3D>() * shutter;
+            }
+            acc + world.color_at_with(&r, buf)
+        });
+        total * (1.0 / self.samples as F3D)
+    }
+
+    // buf is scratch space for primary ray intersections, one per thread
+    fn color_for_pixel<'w>(
+        &self,
+        world: &'w World,
+        x: usize,
+        y: usize,
+        buf: &mut Intersections<'w>,
+    ) -> Color {
+        match self.seed {
+            Some(seed) => {
+                // seed each pixel on its own so render order doesn't matter
+                let pixel = (y * self.hsize + x) as u64;
+                let mut rng =
+                    StdRng::seed_from_u64(seed ^ pixel.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+                self.sample_pixel(world, x, y, &mut rng, buf)
+            }
+            None => self.sample_pixel(world, x, y, &mut rand::thread_rng(), buf),
+        }
     }
 
     pub fn render(&self, world: &World) -> Canvas {
+        self.render_region(world, 0, 0, self.hsize, self.vsize)
+    }
+
+    /**
+     * Render the pixels in [x0, x1) x [y0, y1) of the full image into a
+     * canvas of just that size
+     */
+    pub fn render_region(
+        &self,
+        world: &World,
+        x0: usize,
+        y0: usize,
+        x1: usize,
+        y1: usize,
+    ) -> Canvas {
+        let (x1, y1) = (x1.min(self.hsize), y1.min(self.vsize));
+        let mut image = Canvas::new(x1.saturating_sub(x0), y1.saturating_sub(y0), None);
+
+        let mut buf = Intersections::new();
+        for y in y0..y1 {
+            for (x, color) in self
+                .render_row(world, x0, x1, y, &mut buf)
+                .into_iter()
+                .enumerate()
+            {
+                image.write_pixel(x, y - y0, color);
+            }
+        }
+        image
+    }
+
+    /**
+     * Like render, but stops before the next scanline once cancel is set.
+     * Err carries the partly rendered canvas, rows not reached stay white.
+     */
+    pub fn render_cancellable(&self, world: &World, cancel: &AtomicBool) -> Result<Canvas, Canvas> {
+        let mut image = Canvas::new(self.hsize, self.vsize, None);
+        let mut buf = Intersections::new();
+
+        for y in 0..self.vsize {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(image);
+            }
+            for (x, color) in self
+                .render_row(world, 0, self.hsize, y, &mut buf)
+                .into_iter()
+                .enumerate()
+            {
+                image.write_pixel(x, y, color);
+            }
+        }
+        Ok(image)
+    }
+
+    /**
+     * Render an auxiliary buffer instead of shaded color, from the first hit
+     * of each pixel's center ray (World::cast)
+     */
+    pub fn render_aov(&self, world: &World, aov: Aov) -> Canvas {
         let mut image = Canvas::new(self.hsize, self.vsize, None);
 
         for y in 0..self.vsize {
             let x_colors: Vec<Color> = if OPT_PARALLEL {
                 (0..self.hsize)
                     .into_par_iter()
-                    .map(|x| {
-                        let r = self.ray_for_pixel(x, y);
-                        world.color_at(&r, crate::world::MAX_RAY_DEPTH)
-                    })
+                    .map(|x| aov_color(world, &self.ray_for_pixel(x, y), aov))
                     .collect()
             } else {
                 (0..self.hsize)
-                    .map(|x| {
-                        let r = self.ray_for_pixel(x, y);
-                        world.color_at(&r, crate::world::MAX_RAY_DEPTH)
-                    })
+                    .map(|x| aov_color(world, &self.ray_for_pixel(x, y), aov))
                     .collect()
             };
-            for (x, &color) in x_colors.iter().enumerate() {
+            for (x, color) in x_colors.into_iter().enumerate() {
                 image.write_pixel(x, y, color);
             }
         }
         image
     }
+
+    // colors of pixels x0..x1 of scanline y, buf is used when not rendering in parallel
+    fn render_row<'w>(
+        &self,
+        world: &'w World,
+        x0: usize,
+        x1: usize,
+        y: usize,
+        buf: &mut Intersections<'w>,
+    ) -> Vec<Color> {
+        if OPT_PARALLEL {
+            // map_init makes a buffer per rayon task rather than per thread,
+            // so keep tasks from being split down to single pixels
+            (x0..x1)
+                .into_par_iter()
+                .with_min_len(PIXELS_PER_TASK)
+                .map_init(Intersections::new, |buf, x| {
+                    self.color_for_pixel(world, x, y, buf)
+                })
+                .collect()
+        } else {
+            (x0..x1)
+                .map(|x| self.color_for_pixel(world, x, y, buf))
+                .collect()
+        }
+    }
+}
+
+impl Transformable for Camera {
+    fn get_transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    fn set_transform(&mut self, m: &Matrix4) {
+        self.transform = *m;
+    }
+}
+
+fn aov_color(world: &World, ray: &Ray, aov: Aov) -> Color {
+    match (world.cast(ray), aov) {
+        (Some((_, point, _)), Aov::Depth) => {
+            let d = (point - ray.origin).magnitude() / ray.direction.magnitude();
+            Color::new(d, d, d)
+        }
+        (None, Aov::Depth) => Color::new(F3D::INFINITY, F3D::INFINITY, F3D::INFINITY),
+        (Some((_, _, n)), Aov::Normal) => Color::new(n.x + 1.0, n.y + 1.0, n.z + 1.0) * 0.5,
+        (Some((id, _, _)), Aov::ObjectId) => {
+            let mut hasher = DefaultHasher::new();
+            id.hash(&mut hasher);
+            let h = hasher.finish();
+            let channel = |shift: u64| ((h >> shift) & 0xff) as F3D / 255.0;
+            Color::new(channel(0), channel(8), channel(16))
+        }
+        (None, _) => Color::black(),
+    }
 }
 
 #[cfg(test)]
@@ -102,7 +402,7 @@ mod tests {
         let c = Camera::new(160, 200, glm::half_pi());
         assert_eq!(c.hsize, 160);
         assert_eq!(c.vsize, 200);
-        assert_eq!(c.fov, glm::half_pi());
+        assert_eq!(c.fov, glm::half_pi::<F3D>());
         assert_eq!(c.transform, glm::identity::<F3D, 4>());
     }
 
@@ -112,6 +412,19 @@ mod tests {
         assert!(f_equals(c.pixel_size, 0.01));
     }
 
+    #[test]
+    fn resizing_camera_keeps_view() {
+        let mut c = Camera::new(200, 125, glm::half_pi());
+        c.resize(400, 250);
+        assert_eq!((c.hsize(), c.vsize()), (400, 250));
+        assert_eq!(c.fov(), glm::half_pi::<F3D>());
+        assert!(f_equals(c.pixel_size(), 0.005));
+
+        let mut o = Camera::orthographic(200, 100, 4.0);
+        o.resize(100, 50);
+        assert!(f_equals(o.pixel_size(), 0.04));
+    }
+
     #[test]
     fn construct_ray_through_center_of_canvas() {
         let c = Camera::new(201, 101, glm::half_pi());
@@ -154,4 +467,184 @@ mod tests {
             Color::new(0.38066, 0.47583, 0.2855).tuple()
         );
     }
+
+    #[test]
+    fn look_at_sets_view_transform() {
+        let from = point(1.0, 3.0, 2.0);
+        let to = point(4.0, -2.0, 8.0);
+        let up = vector(1.0, 1.0, 0.0);
+        let mut c = Camera::new(11, 11, glm::half_pi());
+        c.look_at(from, to, up);
+        assert_eq!(c.transform, view_transform(&from, &to, &up));
+        let built = Camera::new(11, 11, glm::half_pi()).looking_at(from, to, up);
+        assert_eq!(built.transform, c.transform);
+    }
+
+    #[test]
+    fn applying_transform_to_camera() {
+        let a = make_translation(0.0, -2.0, 5.0);
+        let b = make_rotation_y(glm::quarter_pi());
+        let mut c = Camera::new(201, 101, glm::half_pi()).with_transform(a);
+        c.apply(&b);
+        assert_eq!(*c.get_transform(), b * a);
+        let r = c.ray_for_pixel(100, 50);
+        assert_eq_eps!(r.origin, point(0.0, 2.0, -5.0));
+        assert_eq_eps!(
+            r.direction,
+            vector(2.0_f64.sqrt() / 2.0, 0.0, -(2.0_f64.sqrt()) / 2.0)
+        );
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let c = Camera::orthographic(201, 101, 4.0);
+        let r1 = c.ray_for_pixel(0, 0);
+        let r2 = c.ray_for_pixel(200, 100);
+        assert_eq_eps!(r1.direction, r2.direction);
+        assert_eq_eps!(r1.direction, vector(0.0, 0.0, -1.0));
+        assert_ne!(r1.origin, r2.origin);
+        assert_eq_eps!(r1.origin, point(1.99005, 0.99005, 0.0));
+
+        let p = Camera::new(201, 101, glm::half_pi());
+        assert_ne!(
+            p.ray_for_pixel(0, 0).direction,
+            p.ray_for_pixel(200, 100).direction
+        );
+        assert_eq!(
+            p.ray_for_pixel(0, 0).origin,
+            p.ray_for_pixel(200, 100).origin
+        );
+    }
+
+    #[test]
+    fn orthographic_ray_when_camera_transformed() {
+        let mut c = Camera::orthographic(11, 11, 2.0);
+        c.transform = make_translation(0.0, -2.0, 5.0);
+        let r = c.ray_for_pixel(5, 5);
+        assert_eq_eps!(r.origin, point(0.0, 2.0, -5.0));
+        assert_eq_eps!(r.direction, vector(0.0, 0.0, -1.0));
+    }
+
+    fn render_sampled(seed: u64) -> Canvas {
+        let mut c = Camera::new(11, 11, glm::half_pi())
+            .with_samples(4)
+            .with_seed(seed);
+        c.transform = view_transform(&point(0.0, 0.0, -5.0), &point_zero(), &vector_y());
+        c.render(&World::default())
+    }
+
+    #[test]
+    fn shutter_blurs_moving_objects() {
+        let mut ball = crate::shapes::sphere::sphere()
+            .with_transformation(make_translation(-3.0, 0.0, -5.0))
+            .with_motion(make_translation(3.0, 0.0, -5.0));
+        ball.material.ambient = 1.0;
+        ball.material.diffuse = 0.0;
+        ball.material.specular = 0.0;
+        let light = crate::lights::point_light(point(0.0, 0.0, 10.0), Color::white());
+        let mut world = World::new(vec![light]);
+        world.add_shape(ball);
+
+        let still = Camera::orthographic(9, 1, 9.0)
+            .with_samples(16)
+            .with_seed(7);
+        let blurred = still.clone().with_shutter(0.0, 1.0);
+        let (a, b) = (still.render(&world), blurred.render(&world));
+        let lit = |c: &Canvas| c.pixels.iter().filter(|p| p.red() > 0.0).count();
+
+        // the middle is only crossed while the shutter is open
+        assert_eq!(a.pixel_at(4, 0).red(), 0.0);
+        assert!(b.pixel_at(4, 0).red() > 0.0 && b.pixel_at(4, 0).red() < 1.0);
+        assert!(lit(&b) > lit(&a));
+    }
+
+    #[test]
+    fn same_seed_renders_identical_images() {
+        let a = render_sampled(42);
+        let b = render_sampled(42);
+        for y in 0..11 {
+            for x in 0..11 {
+                assert_eq!(a.pixel_at(x, y), b.pixel_at(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn different_seeds_jitter_edge_pixels() {
+        let a = render_sampled(1);
+        let b = render_sampled(2);
+        let differs = (0..11)
+            .flat_map(|y| (0..11).map(move |x| (x, y)))
+            .any(|(x, y)| a.pixel_at(x, y) != b.pixel_at(x, y));
+        assert!(differs);
+    }
+
+    #[test]
+    fn rendering_quadrant_tiles_matches_full_render() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, glm::half_pi());
+        c.transform = view_transform(&point(0.0, 0.0, -5.0), &point_zero(), &vector_y());
+        let full = c.render(&w);
+
+        let mut assembled = Canvas::new(11, 11, None);
+        for (x0, y0, x1, y1) in [(0, 0, 6, 6), (6, 0, 11, 6), (0, 6, 6, 11), (6, 6, 11, 11)] {
+            let tile = c.render_region(&w, x0, y0, x1, y1);
+            assert_eq!(tile.dimensions(), (x1 - x0, y1 - y0));
+            assembled.paste(&tile, x0, y0);
+        }
+        assert_eq!(assembled.pixels, full.pixels);
+    }
+
+    #[test]
+    fn cancelling_a_render_returns_the_rows_done_so_far() {
+        let mut w = World::default().with_stats();
+        w.background = Color::new(0.1, 0.2, 0.3);
+        // tall enough that even an optimized build is still rendering when cancelled
+        let mut c = Camera::new(11, 20_000, glm::half_pi());
+        c.transform = view_transform(&point(0.0, 0.0, -5.0), &point_zero(), &vector_y());
+
+        let cancel = AtomicBool::new(true);
+        let nothing = c.render_cancellable(&w, &cancel).err().unwrap();
+        assert!(nothing.pixels.iter().all(|p| *p == Color::white()));
+
+        let cancel = AtomicBool::new(false);
+        let partial = std::thread::scope(|s| {
+            let render = s.spawn(|| c.render_cancellable(&w, &cancel));
+            // cancel as soon as the first row has started
+            while w.stats().rays_cast == 0 {
+                std::thread::yield_now();
+            }
+            cancel.store(true, Ordering::Relaxed);
+            render.join().unwrap()
+        })
+        .err()
+        .unwrap();
+
+        let row = |y: usize| (0..11).map(|x| *partial.pixel_at(x, y)).collect::<Vec<_>>();
+        assert!(row(0).iter().all(|p| *p == w.background));
+        assert!(row(19_999).iter().all(|p| *p == Color::white()));
+    }
+
+    #[test]
+    fn depth_aov_encodes_hit_distance() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, glm::half_pi());
+        c.transform = view_transform(&point(0.0, 0.0, -5.0), &point_zero(), &vector_y());
+
+        let depth = c.render_aov(&w, Aov::Depth);
+        // the center ray hits the outer sphere at t = 4
+        let center = depth.pixel_at(5, 5);
+        assert!((center.red() - 4.0).abs() < 1e-9, "{}", center);
+        assert_eq!(center.red(), center.green());
+        assert_eq!(center.red(), center.blue());
+        // a corner ray misses everything
+        assert_eq!(depth.pixel_at(0, 0).red(), F3D::INFINITY);
+
+        let normals = c.render_aov(&w, Aov::Normal);
+        assert_eq!(*normals.pixel_at(5, 5), Color::new(0.5, 0.5, 0.0));
+        let ids = c.render_aov(&w, Aov::ObjectId);
+        assert_ne!(*ids.pixel_at(5, 5), Color::black());
+        assert_eq!(*ids.pixel_at(5, 5), *ids.pixel_at(5, 6));
+        assert_eq!(*ids.pixel_at(0, 0), Color::black());
+    }
 }
@@ -1,6 +1,15 @@
 use crate::color::Color;
+use crate::math::F3D;
 use crate::ppm;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlendMode {
+    // top layer wins, black pixels are treated as empty
+    Over,
+    Add,
+    Multiply,
+}
+
 pub struct Canvas {
     width: usize,
     height: usize,
@@ -43,10 +52,123 @@ impl Canvas {
         &self.pixels[i]
     }
 
+    // (x, y, color) for every pixel, row by row
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize, Color)> + '_ {
+        (0..self.height)
+            .flat_map(move |y| (0..self.width).map(move |x| (x, y, *self.pixel_at(x, y))))
+    }
+
+    // same order as pixels(), for editing in place
+    pub fn pixels_mut(&mut self) -> impl Iterator<Item = (usize, usize, &mut Color)> {
+        let width = self.width;
+        self.pixels
+            .iter_mut()
+            .enumerate()
+            .map(move |(i, c)| (i % width, i / width, c))
+    }
+
+    /**
+     * Composite another canvas of the same size on top of this one
+     */
+    pub fn blend(&mut self, other: &Canvas, mode: BlendMode) {
+        assert_eq!(
+            self.dimensions(),
+            other.dimensions(),
+            "blend: canvas dimensions differ"
+        );
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let below = *self.pixel_at(x, y);
+                let above = *other.pixel_at(x, y);
+                let c = match mode {
+                    BlendMode::Over => {
+                        if above == Color::black() {
+                            below
+                        } else {
+                            above
+                        }
+                    }
+                    BlendMode::Add => below + above,
+                    BlendMode::Multiply => below * above,
+                };
+                self.write_pixel(x, y, c);
+            }
+        }
+    }
+
+    // copy tile into this canvas with its top left corner at (x0, y0), clipping at the edges
+    pub fn paste(&mut self, tile: &Canvas, x0: usize, y0: usize) {
+        for y in 0..tile.height {
+            for x in 0..tile.width {
+                self.safe_write_pixel(x0 + x, y0 + y, *tile.pixel_at(x, y));
+            }
+        }
+    }
+
+    /**
+     * Resized copy. Each output pixel averages the block of source pixels it
+     * covers (box filter) when shrinking, and repeats the nearest source pixel
+     * when growing.
+     */
+    pub fn scaled(&self, new_w: usize, new_h: usize) -> Canvas {
+        assert!(
+            (self.width > 0 && self.height > 0) || new_w * new_h == 0,
+            "scaled: can't resize an empty canvas"
+        );
+        // source span [start, end) covering output index i, at least one pixel
+        let span = |i: usize, from: usize, to: usize| {
+            let start = i * from / to;
+            (start, ((i + 1) * from / to).max(start + 1))
+        };
+        let mut out = Canvas::new(new_w, new_h, None);
+        for y in 0..new_h {
+            let (y0, y1) = span(y, self.height, new_h);
+            for x in 0..new_w {
+                let (x0, x1) = span(x, self.width, new_w);
+                let mut sum = Color::black();
+                for sy in y0..y1 {
+                    for sx in x0..x1 {
+                        sum = sum + *self.pixel_at(sx, sy);
+                    }
+                }
+                let n = ((x1 - x0) * (y1 - y0)) as F3D;
+                out.write_pixel(x, y, sum * (1.0 / n));
+            }
+        }
+        out
+    }
+
+    /**
+     * Reinhard tone mapping, c * e / (1 + c * e) per channel. Brings
+     * highlights above 1 back into range. exposure 1.0 is the plain operator.
+     */
+    pub fn tone_map_reinhard(&mut self, exposure: F3D) {
+        let map = |c: F3D| {
+            let c = c * exposure;
+            c / (1.0 + c)
+        };
+        for p in self.pixels.iter_mut() {
+            *p = Color::new(map(p.red()), map(p.green()), map(p.blue()));
+        }
+    }
+
+    pub fn from_ppm(data: &str) -> Result<Canvas, ppm::PpmError> {
+        ppm::canvas_from_string(data)
+    }
+
     pub fn to_ppm(&self) -> String {
         ppm::canvas_to_string(self)
     }
 
+    // ordered dithering to hide 8-bit banding in smooth gradients
+    pub fn to_ppm_dithered(&self) -> String {
+        ppm::canvas_to_string_dithered(self)
+    }
+
+    pub fn write_ppm<W: std::io::Write>(&self, w: &mut W) -> std::io::Result<()> {
+        ppm::write_canvas(self, w)
+    }
+
     pub fn to_file(&self, filename: &str) {
         match ppm::create_file_from_data(filename, &self.to_ppm()) {
             Ok(_) => {
@@ -81,4 +203,135 @@ mod tests {
         let white = c.pixel_at(1, 3);
         assert_ne!(*white, Color::new(1.0, 0.0, 0.0));
     }
+
+    #[test]
+    fn blending_red_canvas_over_green_canvas_with_add() {
+        let mut c = Canvas::new(3, 2, Some(Color::new(0.0, 1.0, 0.0)));
+        let red = Canvas::new(3, 2, Some(Color::new(1.0, 0.0, 0.0)));
+        c.blend(&red, BlendMode::Add);
+        assert!(c.pixels.iter().all(|p| *p == Color::new(1.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn blending_with_multiply() {
+        let mut c = Canvas::new(2, 2, Some(Color::new(0.5, 1.0, 0.2)));
+        let mut other = Canvas::new(2, 2, Some(Color::white()));
+        other.write_pixel(1, 1, Color::new(0.5, 0.5, 0.5));
+        c.blend(&other, BlendMode::Multiply);
+        assert_eq!(*c.pixel_at(0, 0), Color::new(0.5, 1.0, 0.2));
+        assert_eq!(*c.pixel_at(1, 1), Color::new(0.25, 0.5, 0.1));
+    }
+
+    #[test]
+    fn blending_over_skips_black_pixels() {
+        let mut c = Canvas::new(2, 1, Some(Color::new(0.0, 1.0, 0.0)));
+        let mut overlay = Canvas::new(2, 1, Some(Color::black()));
+        overlay.write_pixel(1, 0, Color::new(1.0, 0.0, 0.0));
+        c.blend(&overlay, BlendMode::Over);
+        assert_eq!(*c.pixel_at(0, 0), Color::new(0.0, 1.0, 0.0));
+        assert_eq!(*c.pixel_at(1, 0), Color::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn blending_canvases_of_different_sizes_panics() {
+        let mut c = Canvas::new(2, 2, None);
+        c.blend(&Canvas::new(3, 2, None), BlendMode::Add);
+    }
+
+    #[test]
+    fn writing_ppm_to_a_byte_sink() {
+        let mut c = Canvas::new(10, 2, Some(Color::new(1.0, 0.8, 0.6)));
+        c.write_pixel(0, 0, Color::new(0.0, 0.5, 1.0));
+        let mut buf: Vec<u8> = vec![];
+        c.write_ppm(&mut buf).unwrap();
+        let written = String::from_utf8(buf).unwrap();
+        let lines = written.split('\n').collect::<Vec<&str>>();
+        assert_eq!(lines[0..3], ["P3", "10 2", "255"]);
+        assert_eq!(
+            lines[3],
+            "0 128 255 255 204 153 255 204 153 255 204 153 255 204 153 255 204"
+        );
+        assert_eq!(written, c.to_ppm());
+    }
+
+    #[test]
+    fn pasting_tile_clips_at_edges() {
+        let mut c = Canvas::new(4, 4, Some(Color::black()));
+        let tile = Canvas::new(3, 3, Some(Color::new(1.0, 0.0, 0.0)));
+        c.paste(&tile, 2, 2);
+        assert_eq!(*c.pixel_at(1, 1), Color::black());
+        assert_eq!(*c.pixel_at(2, 2), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(*c.pixel_at(3, 3), Color::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn reinhard_tone_mapping() {
+        let mut c = Canvas::new(3, 1, None);
+        c.write_pixel(0, 0, Color::new(4.0, 4.0, 4.0));
+        c.write_pixel(1, 0, Color::new(0.2, 0.5, 1.0));
+        c.tone_map_reinhard(1.0);
+        assert_eq!(*c.pixel_at(0, 0), Color::new(0.8, 0.8, 0.8));
+        let p = c.pixel_at(1, 0);
+        assert!(p.red() < 0.2 && p.green() < 0.5 && p.blue() < 1.0);
+        assert!(p.red() < p.green() && p.green() < p.blue());
+    }
+
+    #[test]
+    fn downscaling_averages_blocks() {
+        let mut c = Canvas::new(4, 4, None);
+        for y in 0..4 {
+            for x in 0..4 {
+                if (x + y) % 2 == 1 {
+                    c.write_pixel(x, y, Color::new(x as F3D * 0.2, y as F3D * 0.2, 0.0));
+                }
+            }
+        }
+        let small = c.scaled(2, 2);
+        assert_eq!(small.dimensions(), (2, 2));
+        for y in 0..2 {
+            for x in 0..2 {
+                let block = [(0, 0), (1, 0), (0, 1), (1, 1)]
+                    .iter()
+                    .fold(Color::black(), |acc, (dx, dy)| {
+                        acc + *c.pixel_at(2 * x + dx, 2 * y + dy)
+                    });
+                let expected = block * 0.25;
+                let got = small.pixel_at(x, y);
+                assert!((*got - expected).tuple().iter().all(|d| d.abs() < 1e-12));
+            }
+        }
+    }
+
+    #[test]
+    fn upscaling_repeats_nearest_pixel() {
+        let mut c = Canvas::new(2, 1, Some(Color::black()));
+        c.write_pixel(1, 0, Color::white());
+        let big = c.scaled(4, 2);
+        for y in 0..2 {
+            assert_eq!(*big.pixel_at(0, y), Color::black());
+            assert_eq!(*big.pixel_at(1, y), Color::black());
+            assert_eq!(*big.pixel_at(2, y), Color::white());
+            assert_eq!(*big.pixel_at(3, y), Color::white());
+        }
+    }
+
+    #[test]
+    fn iterating_over_pixels() {
+        let mut c = Canvas::new(2, 3, Some(Color::black()));
+        c.write_pixel(1, 2, Color::white());
+        let all: Vec<_> = c.pixels().collect();
+        assert_eq!(all.len(), 6);
+        let coords: Vec<_> = all.iter().map(|&(x, y, _)| (x, y)).collect();
+        assert_eq!(coords, vec![(0, 0), (1, 0), (0, 1), (1, 1), (0, 2), (1, 2)]);
+        assert_eq!(all[5].2, Color::white());
+
+        for (x, y, p) in c.pixels_mut() {
+            *p = Color::new(x as F3D, y as F3D, 0.0);
+        }
+        assert_eq!(*c.pixel_at(1, 2), Color::new(1.0, 2.0, 0.0));
+        assert!(c
+            .pixels()
+            .all(|(x, y, p)| p == Color::new(x as F3D, y as F3D, 0.0)));
+    }
 }
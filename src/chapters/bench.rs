@@ -0,0 +1,106 @@
+/**
+ * Render timing on fixed scenes, nothing is written to disk
+ */
+use crate::camera::Camera;
+use crate::color::*;
+use crate::lights::*;
+use crate::math::F3D;
+use crate::object::*;
+use crate::shapes::plane::*;
+use crate::shapes::sphere::*;
+use crate::transformation::*;
+use crate::tuple::*;
+use crate::world::World;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::Instant;
+
+const SPHERE_GRID: usize = 10;
+
+/**
+ * 10x10x10 grid of small spheres with seeded random colors over a floor,
+ * the spheres in one divided group
+ */
+pub fn spheres_world() -> World {
+    let mut world = World::new(vec![point_light(point(-10.0, 20.0, -10.0), Color::white())]);
+    let mut rng = StdRng::seed_from_u64(1000);
+    let mut spheres = vec![];
+
+    for i in 0..SPHERE_GRID * SPHERE_GRID * SPHERE_GRID {
+        let (x, y, z) = (
+            (i % SPHERE_GRID) as F3D,
+            (i / SPHERE_GRID % SPHERE_GRID) as F3D,
+            (i / (SPHERE_GRID * SPHERE_GRID)) as F3D,
+        );
+        let mut s = sphere();
+        s.material.color = color(rng.gen(), rng.gen(), rng.gen());
+        s.set_transform(
+            &(make_translation(x - 4.5, y + 0.5, z - 4.5) * make_scaling(0.3, 0.3, 0.3)),
+        );
+        spheres.push(s);
+    }
+
+    world.add_shape(plane().with_transformation(make_translation(0.0, -0.5, 0.0)));
+    world.add_shape(Object::new_group(spheres).divide(8));
+    world
+}
+
+fn spheres_camera(hsize: usize, vsize: usize) -> Camera {
+    Camera::new(hsize, vsize, glm::pi::<F3D>() / 3.0).looking_at(
+        point(0.0, 8.0, -16.0),
+        point(0.0, 4.0, 0.0),
+        vector_y(),
+    )
+}
+
+fn default_camera(hsize: usize, vsize: usize) -> Camera {
+    Camera::new(hsize, vsize, glm::half_pi()).looking_at(
+        point(0.0, 0.0, -5.0),
+        point_zero(),
+        vector_y(),
+    )
+}
+
+// render once, print total time and rays/sec
+fn time_render(name: &str, world: &World, camera: &Camera) {
+    let start = Instant::now();
+    camera.render(world);
+    let elapsed = start.elapsed().as_secs_f64();
+    let rays = world.stats().rays_cast;
+    println!(
+        "{}: {}x{} in {:.3}s, {} rays, {:.0} rays/sec",
+        name,
+        camera.hsize(),
+        camera.vsize(),
+        elapsed,
+        rays,
+        rays as F3D / elapsed
+    );
+}
+
+pub fn run(hsize: usize, vsize: usize) {
+    time_render(
+        "default world",
+        &World::default().with_stats(),
+        &default_camera(hsize, vsize),
+    );
+    time_render(
+        "1000 spheres",
+        &spheres_world().with_stats(),
+        &spheres_camera(hsize, vsize),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bench_scene_renders() {
+        let world = spheres_world().with_stats();
+        assert_eq!(world.summary().primitives, 1001);
+        let image = spheres_camera(10, 10).render(&world);
+        assert_eq!(image.dimensions(), (10, 10));
+        assert!(world.stats().rays_cast >= 100);
+    }
+}
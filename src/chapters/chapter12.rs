@@ -86,11 +86,7 @@ pub fn run() {
     cone1.set_transform(&st);
 
     let mut world = World::new(vec![point_light(point(-10.0, 10.0, -10.0), Color::white())]);
-    world.add_shape(floor);
-    world.add_shape(glass_ball);
-    world.add_shape(checker_ball);
-    world.add_shape(lcube);
-    world.add_shape(cone1);
+    world.add_objects([floor, glass_ball, checker_ball, lcube, cone1]);
 
     let mut camera = Camera::new(500, 250, glm::pi::<F3D>() / 3.0);
     //let mut camera = Camera::new(100, 50, glm::pi::<F3D>() / 3.0);
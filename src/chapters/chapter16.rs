@@ -26,7 +26,7 @@ pub fn run(hsize: usize, vsize: usize) {
     floor.material.specular = 0.0;
 
     let mut checkers = checkers::checkers_pattern(Color::white(), color(0.4, 0.4, 0.4));
-    checkers.set_transform(make_scaling(0.1, 0.1, 0.2));
+    checkers.set_transform(&make_scaling(0.1, 0.1, 0.2));
     floor.material.pattern = Some(TPattern::Checkers(checkers));
 
     let mut back_wall = plane();
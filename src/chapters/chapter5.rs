@@ -46,7 +46,7 @@ pub fn run() {
                     let color = is
                         .object
                         .get_material()
-                        .lighting(&is.object, &light, &p, &eye, &normal, 0.0);
+                        .lighting(is.object, &light, &p, &eye, &normal, 0.0);
                     canvas.write_pixel(x, y, color);
                 }
                 _ => canvas.write_pixel(x, y, Color::black()),
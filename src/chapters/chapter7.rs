@@ -55,11 +55,7 @@ pub fn run() {
     //println!("lwall material: {}", lwall.transform);
     //println!("rwall material: {}", rwall.transform);
     let mut world = World::new(vec![point_light(point(-10.0, 10.0, -10.0), Color::white())]);
-    world.add_shape(floor);
-    world.add_shape(lwall);
-    world.add_shape(rwall);
-    world.add_shape(msphere);
-    world.add_shape(rsphere);
+    world.add_objects([floor, lwall, rwall, msphere, rsphere]);
 
     let mut camera = Camera::new(500, 250, glm::pi::<F3D>() / 3.0);
     camera.transform = view_transform(&point(0.0, 1.5, -5.0), &point_y(), &vector_y());
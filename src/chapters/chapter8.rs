@@ -58,14 +58,16 @@ pub fn run(hsize: usize, vsize: usize) {
     lsphere.material.specular = 0.3;
 
     let mut world = World::new(vec![
-        area_light(point(-10.0, 10.0, -10.0), Color::white(), 1.5), //point_light(point(-10.0, 10.0, -10.0), Color::white())]);
+        area_light(
+            point(-10.0, 10.0, -10.0),
+            vector(1.5, 0.0, 0.0),
+            3,
+            vector(0.0, 1.5, 0.0),
+            3,
+            Color::white(),
+        ), //point_light(point(-10.0, 10.0, -10.0), Color::white())]);
     ]);
-    world.add_shape(floor);
-    world.add_shape(lwall);
-    world.add_shape(rwall);
-    world.add_shape(msphere);
-    world.add_shape(rsphere);
-    world.add_shape(lsphere);
+    world.add_objects([floor, lwall, rwall, msphere, rsphere, lsphere]);
 
     let mut camera = Camera::new(hsize, vsize, glm::pi::<F3D>() / 3.0);
     camera.transform = view_transform(&point(0.0, 1.5, -5.0), &point_y(), &vector_y());
@@ -22,6 +22,8 @@ pub fn run(fixture: &String, hsize: usize, vsize: usize) {
         //point_light(point(100.0, 10.0, -25.0), color(0.2, 0.2, 0.2)),
         //point_light(point(-100.0, 10.0, -25.0), color(0.2, 0.2, 0.2)),
     ]);
+    // rays pass in and out of a glass box and the dragon inside it
+    world.max_refraction_depth = 8;
 
     // dragon obj
     let obj = parse_obj_file(fixture).unwrap();
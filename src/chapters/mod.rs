@@ -1,4 +1,5 @@
 pub mod appendix1;
+pub mod bench;
 pub mod chapter1;
 pub mod chapter11;
 pub mod chapter12;
@@ -23,13 +23,13 @@ pub fn run(hsize: usize, vsize: usize) {
     floor.material.specular = 0.0;
 
     let mut checkers = checkers::checkers_pattern(Color::white(), color(0.4, 0.4, 0.4));
-    checkers.set_transform(make_scaling(0.1, 0.1, 0.2));
+    checkers.set_transform(&make_scaling(0.1, 0.1, 0.2));
     floor.material.pattern = Some(TPattern::Checkers(checkers));
 
     let mut backWall = plane();
     backWall.set_transform(&(make_translation(0.0, 0.0, 10.0) * make_rotation_x(glm::half_pi())));
     let mut rings = ring::ring_pattern(color(1.0, 0.1, 0.1), color(0.8, 0.7, 0.8));
-    rings.set_transform(make_scaling(0.4, 0.4, 0.4));
+    rings.set_transform(&make_scaling(0.4, 0.4, 0.4));
     backWall.material.specular = 0.0;
     // doesn't work !?
     backWall.material.pattern = Some(TPattern::Ring(rings));
@@ -54,7 +54,7 @@ pub fn run(hsize: usize, vsize: usize) {
     right.material.specular = 0.3;
 
     let mut stripes = stripe::stripe_pattern(color(1.0, 0.0, 0.2), color(0.0, 0.0, 1.0));
-    stripes.set_transform(make_rotation_z(0.5) * make_scaling(0.1, 0.1, 0.2));
+    stripes.set_transform(&(make_rotation_z(0.5) * make_scaling(0.1, 0.1, 0.2)));
     left.material.pattern = Some(TPattern::Stripe(stripes));
 
     let uv_checkers = texture_map::UVCheckers::new(16.0, 8.0, color(0.1, 1.0, 0.1), Color::white());
@@ -1,9 +1,14 @@
 use crate::math::F3D;
 use crate::tuple::{tuple, Tuple};
 use std::fmt;
-use std::ops::{Add, Mul};
+use std::ops::{Add, Mul, Sub};
 
 #[derive(Copy, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "[F3D; 3]", into = "[F3D; 3]")
+)]
 pub struct Color {
     rgb: Tuple,
 }
@@ -42,6 +47,87 @@ impl Color {
     pub fn tuple(&self) -> &Tuple {
         &self.rgb // immutable ref, readonly
     }
+
+    // each channel clamped to [0, 1]
+    pub fn clamped(&self) -> Color {
+        Color::new(
+            self.red().clamp(0.0, 1.0),
+            self.green().clamp(0.0, 1.0),
+            self.blue().clamp(0.0, 1.0),
+        )
+    }
+
+    // perceived brightness (Rec. 709 weights)
+    pub fn luminance(&self) -> F3D {
+        0.2126 * self.red() + 0.7152 * self.green() + 0.0722 * self.blue()
+    }
+
+    // linear blend, t = 0 -> a, t = 1 -> b
+    pub fn lerp(a: Color, b: Color, t: F3D) -> Color {
+        a * (1.0 - t) + b * t
+    }
+
+    /**
+     * Approximate color of a black body at the given temperature, good for
+     * roughly 1000K - 40000K (Tanner Helland's curve fit)
+     */
+    pub fn from_kelvin(k: F3D) -> Color {
+        let t = k / 100.0;
+        let red = if t <= 66.0 {
+            255.0
+        } else {
+            329.698727446 * (t - 60.0).powf(-0.1332047592)
+        };
+        let green = if t <= 66.0 {
+            99.4708025861 * t.ln() - 161.1195681661
+        } else {
+            288.1221695283 * (t - 60.0).powf(-0.0755148492)
+        };
+        let blue = if t >= 66.0 {
+            255.0
+        } else if t <= 19.0 {
+            0.0
+        } else {
+            138.5177312231 * (t - 10.0).ln() - 305.0447927307
+        };
+        Color::new(red / 255.0, green / 255.0, blue / 255.0).clamped()
+    }
+
+    // (hue in degrees 0 -> 360, saturation, value)
+    pub fn to_hsv(&self) -> (F3D, F3D, F3D) {
+        let (r, g, b) = (self.red(), self.green(), self.blue());
+        let max = r.max(g).max(b);
+        let delta = max - r.min(g).min(b);
+
+        let hue = if delta == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+        let saturation = if max == 0.0 { 0.0 } else { delta / max };
+        (hue, saturation, max)
+    }
+
+    // hue wraps, so any angle in degrees works
+    pub fn from_hsv(h: F3D, s: F3D, v: F3D) -> Color {
+        let h = h.rem_euclid(360.0) / 60.0;
+        let c = v * s;
+        let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as u8 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+        let m = v - c;
+        Color::new(r + m, g + m, b + m)
+    }
 }
 
 impl PartialEq for Color {
@@ -60,6 +146,14 @@ impl Add for Color {
     }
 }
 
+impl Sub for Color {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::from_tuple(&(self.tuple() - other.tuple()))
+    }
+}
+
 impl Mul<F3D> for Color {
     type Output = Self;
 
@@ -93,6 +187,18 @@ impl fmt::Display for Color {
     }
 }
 
+impl From<[F3D; 3]> for Color {
+    fn from(rgb: [F3D; 3]) -> Self {
+        Color::new(rgb[0], rgb[1], rgb[2])
+    }
+}
+
+impl From<Color> for [F3D; 3] {
+    fn from(c: Color) -> Self {
+        [c.red(), c.green(), c.blue()]
+    }
+}
+
 // constructor utility
 pub fn color(r: F3D, g: F3D, b: F3D) -> Color {
     Color::new(r, g, b)
@@ -118,4 +224,68 @@ mod tests {
         assert_eq!(d.y, -2.1);
         assert_eq!(d.z, 0.0);
     }
+
+    #[test]
+    fn clamping_a_color() {
+        let c = color(1.5, -0.2, 0.5).clamped();
+        assert_eq!(c, color(1.0, 0.0, 0.5));
+    }
+
+    #[test]
+    fn luminance_of_white() {
+        assert!((Color::white().luminance() - 1.0).abs() < 1e-9);
+        assert_eq!(Color::black().luminance(), 0.0);
+    }
+
+    #[test]
+    fn lerp_between_colors() {
+        let c = Color::lerp(Color::black(), Color::white(), 0.5);
+        assert_eq!(c, color(0.5, 0.5, 0.5));
+        assert_eq!(
+            Color::lerp(Color::black(), Color::white(), 0.0),
+            Color::black()
+        );
+    }
+
+    #[test]
+    fn subtracting_colors() {
+        let c = color(0.9, 0.6, 0.75) - color(0.7, 0.1, 0.25);
+        assert_eq!(c, color(0.9 - 0.7, 0.5, 0.5));
+    }
+
+    #[test]
+    fn red_round_trips_through_hsv() {
+        let red = color(1.0, 0.0, 0.0);
+        assert_eq!(red.to_hsv(), (0.0, 1.0, 1.0));
+        assert_eq!(Color::from_hsv(0.0, 1.0, 1.0), red);
+
+        let c = color(0.2, 0.4, 0.6);
+        let (h, s, v) = c.to_hsv();
+        let back = Color::from_hsv(h, s, v);
+        assert!(
+            (back - c).tuple().iter().all(|d| d.abs() < 1e-9),
+            "{}",
+            back
+        );
+    }
+
+    #[test]
+    fn rotating_hue_turns_red_green() {
+        let (h, s, v) = color(1.0, 0.0, 0.0).to_hsv();
+        assert_eq!(Color::from_hsv(h + 120.0, s, v), color(0.0, 1.0, 0.0));
+        assert_eq!(Color::from_hsv(h - 120.0, s, v), color(0.0, 0.0, 1.0));
+        assert_eq!(Color::white().to_hsv(), (0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn color_from_kelvin() {
+        let daylight = Color::from_kelvin(6500.0);
+        for c in [daylight.red(), daylight.green(), daylight.blue()] {
+            assert!((c - 1.0).abs() < 0.03, "{}", daylight);
+        }
+        let candle = Color::from_kelvin(2000.0);
+        assert!(candle.red() > candle.blue());
+        let sky = Color::from_kelvin(10000.0);
+        assert!(sky.blue() > sky.red());
+    }
 }
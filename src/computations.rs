@@ -1,14 +1,15 @@
+use crate::color::Color;
 use crate::intersection::*;
 use crate::math::*;
 use crate::object::Object;
 use crate::ray::Ray;
+use crate::shapes::shape::Shape;
 use crate::tuple::*;
-use std::sync::{Arc, RwLock};
 
 #[derive(Debug)]
-pub struct Computations {
+pub struct Computations<'a> {
     pub t: F3D,
-    pub object: Arc<Object>,
+    pub object: &'a Object,
     pub point: Point,
     pub over_point: Point,
     pub under_point: Point,
@@ -18,6 +19,42 @@ pub struct Computations {
     pub inside: bool,
     pub n1: F3D,
     pub n2: F3D,
+    // interpolated color of a vertex colored triangle
+    pub vertex_color: Option<Color>,
+    // shutter time of the ray that hit, carried on to secondary rays
+    pub time: F3D,
+    // Fresnel (schlick) fraction of light reflected rather than refracted,
+    // only worked out for transparent materials and 0 otherwise
+    pub reflectance: F3D,
+}
+
+// entering an object pushes it, leaving removes it (keeping the order of the rest)
+fn update_containers<'a>(containers: &mut Vec<&'a Object>, object: &'a Object) {
+    let id = object.get_id();
+    if let Some(pos) = containers.iter().position(|o| o.get_id() == id) {
+        containers.remove(pos);
+    } else {
+        containers.push(object);
+    }
+}
+
+/**
+ * Debugging aid for n1/n2: the ids of the objects the ray is inside of
+ * after each intersection in xs, up to and including i
+ */
+pub fn refractive_trace(i: &Intersection, xs: &Intersections) -> Vec<Vec<String>> {
+    let mut containers: Vec<&Object> = Vec::new();
+    let mut trace = vec![];
+
+    for is in xs.iter() {
+        update_containers(&mut containers, is.object);
+        trace.push(containers.iter().map(|o| o.get_id()).collect());
+
+        if i.t == is.t && i.object.get_id() == is.object.get_id() {
+            break;
+        }
+    }
+    trace
 }
 
 fn calc_refractive_indices(i: &Intersection, xs: &Intersections) -> (F3D, F3D) {
@@ -36,15 +73,7 @@ fn calc_refractive_indices(i: &Intersection, xs: &Intersections) -> (F3D, F3D) {
                 n1 = containers.last().unwrap().get_material().refractive_index;
             }
         }
-        // if container holds the current hit object
-        if let Some(pos) = containers
-            .iter()
-            .position(|i| is.object.get_id() == i.get_id())
-        {
-            containers.swap_remove(pos);
-        } else {
-            containers.push(&is.object);
-        }
+        update_containers(&mut containers, is.object);
 
         if is_hit {
             if containers.is_empty() {
@@ -58,28 +87,69 @@ fn calc_refractive_indices(i: &Intersection, xs: &Intersections) -> (F3D, F3D) {
     (n1, n2)
 }
 
-pub fn prepare_computations(i: &Intersection, ray: &Ray, xs: &Intersections) -> Computations {
+// bump the normal by the material's perturbation pattern, if any
+fn perturb_normal(object: &Object, p: &Point, normal: Vector) -> Vector {
+    match &object.get_material().normal_perturb {
+        Some(pattern) => {
+            let offset = pattern.pattern_at_shape(object, p);
+            (normal + vector(offset.red(), offset.green(), offset.blue())).normalize()
+        }
+        None => normal,
+    }
+}
+
+pub fn prepare_computations<'a>(
+    i: &Intersection<'a>,
+    ray: &Ray,
+    xs: &Intersections<'a>,
+) -> Computations<'a> {
+    prepare_computations_eps(i, ray, xs, EPSILON)
+}
+
+/**
+ * prepare_computations with over_point / under_point pushed eps along the
+ * normal. Too small and rounding puts secondary ray origins back inside the
+ * surface (shadow and reflection acne, worse far from the origin); too large
+ * and they skip past thin objects and contact shadows come loose.
+ */
+pub fn prepare_computations_eps<'a>(
+    i: &Intersection<'a>,
+    ray: &Ray,
+    xs: &Intersections<'a>,
+    eps: F3D,
+) -> Computations<'a> {
     let p = ray.position(i.t);
-    let normal = i.object.normal_at(p, Some(i));
+    let normal = perturb_normal(i.object, &p, i.object.normal_at_time(p, Some(i), ray.time));
     let eyev = -ray.direction;
     let inside = normal.dot(&eyev) < 0.0;
     let normalv = if inside { -normal } else { normal };
     let reflectv = reflect(ray.direction, normalv);
     let (n1, n2) = calc_refractive_indices(i, xs);
+    let vertex_color = match i.object.shape() {
+        Shape::SmoothTriangle(t) => t.vertex_color(i.u, i.v),
+        _ => None,
+    };
 
-    Computations {
+    let mut comps = Computations {
         t: i.t,
-        object: Arc::clone(&i.object),
+        object: i.object,
         point: p,
-        over_point: p + normalv * EPSILON,
-        under_point: p - normalv * EPSILON,
+        over_point: p + normalv * eps,
+        under_point: p - normalv * eps,
         eyev,
         normalv,
         reflectv,
         inside,
         n1,
         n2,
+        vertex_color,
+        time: ray.time,
+        reflectance: 0.0,
+    };
+    if i.object.get_material().transparency > 0.0 {
+        comps.reflectance = schlick(&comps);
     }
+    comps
 }
 
 #[cfg(test)]
@@ -88,6 +158,7 @@ mod tests {
 
     use super::*;
     use crate::computations::prepare_computations;
+    use crate::pattern::{stripe::stripe_pattern, TPattern};
     use crate::ray::Ray;
     use crate::shapes::plane::plane;
     use crate::shapes::sphere::*;
@@ -178,6 +249,40 @@ mod tests {
         }
     }
 
+    #[test]
+    fn tracing_containers_of_overlapping_glass() {
+        let mut a = glass_sphere();
+        a.id = String::from("A");
+        a.set_transform(&make_scaling(2.0, 2.0, 2.0));
+        let mut b = glass_sphere();
+        b.id = String::from("B");
+        b.set_transform(&make_translation(0.0, 0.0, -0.25));
+        let mut c = glass_sphere();
+        c.id = String::from("C");
+        c.set_transform(&make_translation(0.0, 0.0, 0.25));
+        let xs = Intersections::from_intersections(vec![
+            Intersection::new(&a, 2.0),
+            Intersection::new(&b, 2.75),
+            Intersection::new(&c, 3.25),
+            Intersection::new(&b, 4.75),
+            Intersection::new(&c, 5.25),
+            Intersection::new(&a, 6.0),
+        ]);
+        let expected: Vec<Vec<&str>> = vec![
+            vec!["sphere_A"],
+            vec!["sphere_A", "sphere_B"],
+            vec!["sphere_A", "sphere_B", "sphere_C"],
+            vec!["sphere_A", "sphere_C"],
+            vec!["sphere_A"],
+            vec![],
+        ];
+        for k in 0..xs.len() {
+            let trace = refractive_trace(&xs[k], &xs);
+            assert_eq!(trace.len(), k + 1);
+            assert_eq!(trace, expected[..=k]);
+        }
+    }
+
     #[test]
     fn underpoint_is_offset_below_surface() {
         let ray = Ray::new(point(0.0, 0.0, -5.0), vector_z());
@@ -189,4 +294,31 @@ mod tests {
         assert!(comps.under_point.z > EPSILON / 2.0);
         assert!(comps.point.z < comps.under_point.z);
     }
+
+    #[test]
+    fn zero_normal_perturbation_leaves_normal_unchanged() {
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector_z());
+        let mut shape = sphere();
+        shape.material.normal_perturb = Some(TPattern::Stripe(stripe_pattern(
+            Color::black(),
+            Color::black(),
+        )));
+        let i = Intersection::new(&shape, 4.0);
+        let comps =
+            prepare_computations(&i, &r, &Intersections::from_intersections(vec![i.clone()]));
+        assert_eq!(comps.normalv, vector(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn normal_perturbation_tilts_normal() {
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector_z());
+        let mut shape = sphere();
+        let offset = Color::new(0.5, 0.0, 0.0);
+        shape.material.normal_perturb = Some(TPattern::Stripe(stripe_pattern(offset, offset)));
+        let i = Intersection::new(&shape, 4.0);
+        let comps =
+            prepare_computations(&i, &r, &Intersections::from_intersections(vec![i.clone()]));
+        assert!(comps.normalv.x > 0.1);
+        assert!(f_equals(comps.normalv.magnitude(), 1.0));
+    }
 }
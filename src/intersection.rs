@@ -1,34 +1,43 @@
 use crate::computations::Computations;
 use crate::math::*;
 use crate::object::*;
+use crate::ray::Ray;
+use crate::tuple::Point;
 use std::clone::Clone;
 use std::fmt;
-use std::sync::Arc;
 
 #[derive(Clone, Debug, PartialEq)]
-pub struct Intersection {
+pub struct Intersection<'a> {
     pub t: F3D,
-    pub object: Arc<Object>,
+    pub object: &'a Object,
     pub u: F3D,
     pub v: F3D,
+    // which triangle of an indexed mesh was hit, 0 for everything else
+    pub face: usize,
 }
 
-impl Intersection {
-    pub fn new(object: &Object, t: F3D) -> Self {
+impl<'a> Intersection<'a> {
+    pub fn new(object: &'a Object, t: F3D) -> Self {
         Self::with_uv(object, t, 0.0, 0.0)
     }
 
-    pub fn with_uv(object: &Object, t: F3D, u: F3D, v: F3D) -> Self {
+    pub fn with_uv(object: &'a Object, t: F3D, u: F3D, v: F3D) -> Self {
         Self {
-            object: Arc::new(object.clone()), // sorry lifetimes, you're too annoying
+            object,
             t,
             u,
             v,
+            face: 0,
         }
     }
+
+    pub fn with_face(mut self, face: usize) -> Self {
+        self.face = face;
+        self
+    }
 }
 
-impl fmt::Display for Intersection {
+impl fmt::Display for Intersection<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
@@ -40,12 +49,18 @@ impl fmt::Display for Intersection {
 }
 
 #[derive(Clone, Debug)]
-pub struct Intersections {
-    intersections: Vec<Intersection>,
+pub struct Intersections<'a> {
+    intersections: Vec<Intersection<'a>>,
+}
+
+impl Default for Intersections<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl Intersections {
-    pub fn from_intersections(intersections: Vec<Intersection>) -> Self {
+impl<'a> Intersections<'a> {
+    pub fn from_intersections(intersections: Vec<Intersection<'a>>) -> Self {
         let mut is = Self::new();
         is.intersections = intersections;
         is.sort_intersections()
@@ -57,7 +72,7 @@ impl Intersections {
         }
     }
 
-    pub fn vec(&self) -> &Vec<Intersection> {
+    pub fn vec(&self) -> &Vec<Intersection<'a>> {
         &self.intersections
     }
 
@@ -69,46 +84,97 @@ impl Intersections {
         self.intersections.is_empty()
     }
 
-    pub fn push(&mut self, is: Intersection) {
+    pub fn push(&mut self, is: Intersection<'a>) {
         self.intersections.push(is);
     }
 
-    pub fn iter(&self) -> std::slice::Iter<Intersection> {
+    pub fn iter(&self) -> std::slice::Iter<'_, Intersection<'a>> {
         self.intersections.iter()
     }
 
-    pub fn extend(&mut self, is: &Intersections) {
+    pub fn extend(&mut self, is: &Intersections<'a>) {
         for is in is.intersections.iter() {
             self.intersections.push(is.clone());
         }
     }
 
+    // keeps the list sorted by t, equal t go after the ones already there
+    pub fn insert_sorted(&mut self, is: Intersection<'a>) {
+        let at = self.intersections.partition_point(|i| i.t <= is.t);
+        self.intersections.insert(at, is);
+    }
+
+    /**
+     * Merge two lists that are already sorted by t into one sorted list, in a
+     * single pass instead of extending and sorting again
+     */
+    pub fn merge(a: Intersections<'a>, b: Intersections<'a>) -> Self {
+        if a.is_empty() {
+            return b;
+        }
+        if b.is_empty() {
+            return a;
+        }
+        let mut merged = Vec::with_capacity(a.len() + b.len());
+        let mut a = a.intersections.into_iter().peekable();
+        let mut b = b.intersections.into_iter().peekable();
+        while let (Some(x), Some(y)) = (a.peek(), b.peek()) {
+            if y.t < x.t {
+                merged.extend(b.next());
+            } else {
+                merged.extend(a.next());
+            }
+        }
+        merged.extend(a);
+        merged.extend(b);
+        Self {
+            intersections: merged,
+        }
+    }
+
     pub fn sort_intersections(mut self) -> Self {
+        self.sort_in_place();
+        self
+    }
+
+    pub fn sort_in_place(&mut self) {
         self.intersections
             .sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
-        self
+    }
+
+    // empties the list but keeps its allocation for reuse
+    pub fn clear(&mut self) {
+        self.intersections.clear();
     }
 
     /**
      * "Closest" intersection in a collection
      */
-    pub fn hit(&self) -> Option<&Intersection> {
+    pub fn hit(&self) -> Option<&Intersection<'a>> {
         // filter out negative t values here
         self.intersections.iter().find(|i| i.t >= 0.0)
     }
+
+    // world space hit points along the ray, in intersection order
+    pub fn world_points(&self, ray: &Ray) -> Vec<Point> {
+        self.intersections
+            .iter()
+            .map(|i| ray.position(i.t))
+            .collect()
+    }
 }
 
 // intersections[i]
-impl std::ops::Index<usize> for Intersections {
-    type Output = Intersection;
+impl<'a> std::ops::Index<usize> for Intersections<'a> {
+    type Output = Intersection<'a>;
 
-    fn index(&self, i: usize) -> &Intersection {
+    fn index(&self, i: usize) -> &Intersection<'a> {
         &self.intersections[i]
     }
 }
 
-impl FromIterator<Intersection> for Intersections {
-    fn from_iter<I: IntoIterator<Item = Intersection>>(iter: I) -> Self {
+impl<'a> FromIterator<Intersection<'a>> for Intersections<'a> {
+    fn from_iter<I: IntoIterator<Item = Intersection<'a>>>(iter: I) -> Self {
         let mut c = Intersections::new();
 
         for i in iter {
@@ -142,7 +208,6 @@ mod tests {
     use super::*;
     use crate::assert_eq_feps;
     use crate::computations::prepare_computations;
-    use crate::ray::Ray;
     use crate::shapes::sphere::*;
     use crate::shapes::triangle::*;
     use crate::transformation::*;
@@ -201,6 +266,43 @@ mod tests {
         assert_eq!(i, Some(&i4));
     }
 
+    #[test]
+    fn merging_sorted_lists_matches_extend_then_sort() {
+        let s = sphere();
+        let ts = |xs: &Intersections| xs.iter().map(|i| i.t).collect::<Vec<_>>();
+        let a: Intersections = [-3.0, 1.0, 1.5, 6.0, 9.0]
+            .iter()
+            .map(|t| Intersection::new(&s, *t))
+            .collect();
+        let b: Intersections = [-4.0, 1.5, 2.0, 10.0]
+            .iter()
+            .map(|t| Intersection::new(&s, *t))
+            .collect();
+
+        let mut expected = a.clone();
+        expected.extend(&b);
+        let expected = expected.sort_intersections();
+        let merged = Intersections::merge(a.clone(), b);
+        assert_eq!(ts(&merged), ts(&expected));
+        assert!(merged.vec().windows(2).all(|w| w[0].t <= w[1].t));
+        assert_eq!(
+            ts(&Intersections::merge(a.clone(), Intersections::new())),
+            ts(&a)
+        );
+    }
+
+    #[test]
+    fn inserting_keeps_intersections_sorted() {
+        let s = sphere();
+        let mut xs = Intersections::new();
+        for t in [5.0, -1.0, 7.0, 2.0, 5.0] {
+            xs.insert_sorted(Intersection::new(&s, t));
+        }
+        let ts: Vec<F3D> = xs.iter().map(|i| i.t).collect();
+        assert_eq!(ts, vec![-1.0, 2.0, 5.0, 5.0, 7.0]);
+        assert_eq!(xs.hit().unwrap().t, 2.0);
+    }
+
     #[test]
     fn hit_should_offset_point() {
         let r = Ray::new(point(0.0, 0.0, -5.0), vector_z());
@@ -238,6 +340,16 @@ mod tests {
         let comps = prepare_computations(&xs[1], &ray, &xs);
         let reflectance = schlick(&comps);
         assert_eq_feps!(reflectance, 0.04);
+        // prepare_computations caches the same value
+        assert_eq!(comps.reflectance, reflectance);
+
+        // but doesn't bother for opaque objects
+        let opaque = crate::shapes::sphere::sphere();
+        let xs = Intersections::from_intersections(vec![
+            Intersection::new(&opaque, -1.0),
+            Intersection::new(&opaque, 1.0),
+        ]);
+        assert_eq!(prepare_computations(&xs[1], &ray, &xs).reflectance, 0.0);
     }
 
     #[test]
@@ -257,4 +369,13 @@ mod tests {
         assert_eq!(i.u, 0.2);
         assert_eq!(i.v, 0.4);
     }
+
+    #[test]
+    fn world_points_along_ray() {
+        let s = sphere();
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector_z());
+        let xs = s.intersect(&r);
+        let points = xs.world_points(&r);
+        assert_eq!(points, vec![point(0.0, 0.0, -1.0), point(0.0, 0.0, 1.0)]);
+    }
 }
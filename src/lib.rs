@@ -12,7 +12,10 @@ pub mod matrix;
 pub mod object;
 pub mod ppm;
 pub mod ray;
+#[cfg(feature = "serde")]
+pub mod scene;
 pub mod shapes;
+pub mod stats;
 #[macro_use]
 pub mod transformation;
 #[macro_use]
@@ -25,4 +28,5 @@ pub mod bounds;
 pub mod chapters;
 pub mod obj_file;
 pub mod pattern;
+pub mod ply_file;
 pub mod world;
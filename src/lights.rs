@@ -1,3 +1,4 @@
+use crate::camera::Camera;
 use crate::color::Color;
 use crate::math;
 use crate::tuple::*;
@@ -5,44 +6,141 @@ use crate::world::World;
 use rand::rngs::ThreadRng;
 use rand::Rng;
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Light {
     Point(PointLight),
     Area(AreaLight),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PointLight {
+    #[cfg_attr(feature = "serde", serde(with = "crate::scene::tuple_serde"))]
     pub position: Point,
     pub intensity: Color,
+    // radius of the sphere sampled for soft shadows, 0 gives hard shadows
+    pub softness: math::F3D,
+    pub soft_samples: usize,
+    // dim diffuse and specular light by 1 / distance^2, off to match the book
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub attenuation: bool,
 }
 
-const NUM_AREA_SAMPLES: u32 = 5;
-
-#[derive(Debug, PartialEq)]
+/**
+ * Rectangular light made of usteps x vsteps cells spanning corner -> corner + full_uvec + full_vvec.
+ * uvec/vvec are the size of a single cell.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AreaLight {
     pub light: PointLight,
-    pub radius: math::F3D,
+    #[cfg_attr(feature = "serde", serde(with = "crate::scene::tuple_serde"))]
+    pub corner: Point,
+    #[cfg_attr(feature = "serde", serde(with = "crate::scene::tuple_serde"))]
+    pub uvec: Vector,
+    pub usteps: usize,
+    #[cfg_attr(feature = "serde", serde(with = "crate::scene::tuple_serde"))]
+    pub vvec: Vector,
+    pub vsteps: usize,
+    // shadow rays per intensity_at, spread over the cells in turn
+    pub samples: usize,
 }
 
 impl Light {
     pub fn point(position: Point, intensity: Color) -> Self {
+        Light::point_soft(position, intensity, 0.0, 0)
+    }
+
+    // point light treated as a small sphere of the given radius when shadowing
+    pub fn point_soft(
+        position: Point,
+        intensity: Color,
+        softness: math::F3D,
+        soft_samples: usize,
+    ) -> Self {
         Light::Point(PointLight {
             position,
             intensity,
+            softness,
+            soft_samples,
+            attenuation: false,
         })
     }
 
-    pub fn area(position: Point, intensity: Color, radius: math::F3D) -> Self {
+    pub fn area(
+        corner: Point,
+        full_uvec: Vector,
+        usteps: usize,
+        full_vvec: Vector,
+        vsteps: usize,
+        intensity: Color,
+    ) -> Self {
+        assert!(
+            usteps > 0 && vsteps > 0,
+            "area light needs at least one cell along each edge"
+        );
         Light::Area(AreaLight {
             light: PointLight {
-                position,
+                // center of the light
+                position: corner + full_uvec / 2.0 + full_vvec / 2.0,
                 intensity,
+                softness: 0.0,
+                soft_samples: 0,
+                attenuation: false,
             },
-            radius,
+            corner,
+            uvec: full_uvec / usteps as math::F3D,
+            usteps,
+            vvec: full_vvec / vsteps as math::F3D,
+            vsteps,
+            samples: usteps * vsteps,
         })
     }
 
+    /**
+     * Square light in the xz plane centered on position, 2 * radius wide,
+     * casting `samples` shadow rays per shaded point. More samples trade
+     * render time for less noisy soft shadows.
+     */
+    pub fn area_with_samples(
+        position: Point,
+        intensity: Color,
+        radius: math::F3D,
+        samples: usize,
+    ) -> Self {
+        let steps = ((samples as math::F3D).sqrt() as usize).max(1);
+        let corner = position - vector(radius, 0.0, radius);
+        match Light::area(
+            corner,
+            vector(radius * 2.0, 0.0, 0.0),
+            steps,
+            vector(0.0, 0.0, radius * 2.0),
+            steps,
+            intensity,
+        ) {
+            Light::Area(a) => Light::Area(AreaLight {
+                samples: samples.max(1),
+                ..a
+            }),
+            _ => unreachable!(),
+        }
+    }
+
+    // turns on inverse-square falloff from the light's position
+    pub fn with_attenuation(mut self) -> Self {
+        match &mut self {
+            Light::Point(p) => p.attenuation = true,
+            Light::Area(a) => a.light.attenuation = true,
+        }
+        self
+    }
+
+    // point light sitting at the camera, handy for quick previews
+    pub fn headlight(camera: &Camera) -> Self {
+        Light::point(camera.position(), Color::white())
+    }
+
     pub fn position(&self) -> Point {
         match self {
             Light::Point(p) => p.position,
@@ -57,25 +155,33 @@ impl Light {
         }
     }
 
-    pub fn radius(&self) -> math::F3D {
-        if let Light::Area(a) = self {
-            a.radius
+    // 1 / distance^2 from the light to point when attenuated, otherwise 1
+    pub fn falloff_at(&self, point: &Point) -> math::F3D {
+        let attenuation = match self {
+            Light::Point(p) => p.attenuation,
+            Light::Area(a) => a.light.attenuation,
+        };
+        if attenuation {
+            1.0 / (self.position() - point).magnitude_squared()
         } else {
-            0.0
+            1.0
         }
     }
 
-    pub fn intensity_at(&self, world: &World, point: &Point) -> math::F3D {
+    // fraction of the light reaching point, with shadows cast at shutter time
+    pub fn intensity_at(&self, world: &World, point: &Point, time: math::F3D) -> math::F3D {
         match self {
-            Light::Point(p) => p.intensity_at(world, point),
-            Light::Area(a) => a.intensity_at(world, point),
+            Light::Point(p) => p.intensity_at(world, point, time),
+            Light::Area(a) => a.intensity_at(world, point, time),
         }
     }
 }
 
 impl PointLight {
-    fn intensity_at(&self, world: &World, point: &Point) -> math::F3D {
-        if world.is_shadowed(&self.position, point) {
+    fn intensity_at(&self, world: &World, point: &Point, time: math::F3D) -> math::F3D {
+        if self.softness > 0.0 && self.soft_samples > 0 {
+            1.0 - world.is_shadowed_soft(self, point, self.soft_samples, time)
+        } else if world.is_shadowed(&self.position, point, time) {
             0.0
         } else {
             1.0
@@ -84,25 +190,41 @@ impl PointLight {
 }
 
 impl AreaLight {
-    fn intensity_at(&self, world: &World, point: &Point) -> math::F3D {
-        // For # samples, calculate random point within the area
-        // and call is_shadowed to that point.
-        // Return average of non-shadowed rays
+    fn intensity_at(&self, world: &World, point: &Point, time: math::F3D) -> math::F3D {
+        // Sample a jittered point in each cell of the light in turn
+        // and return the fraction that aren't shadowed. Samples left over
+        // after the last full pass go to random cells, so the first cells
+        // don't weigh more than the others.
         let mut rng = rand::thread_rng();
         let mut tot = 0.0;
+        let cells = self.usteps * self.vsteps;
+        let full_passes = self.samples - self.samples % cells;
 
-        for _ in 0..NUM_AREA_SAMPLES {
-            if !world.is_shadowed(&self.rnd_point(&mut rng), point) {
+        for k in 0..self.samples {
+            let cell = if k < full_passes {
+                k % cells
+            } else {
+                rng.gen_range(0..cells)
+            };
+            let (u, v) = (cell % self.usteps, cell / self.usteps);
+            let sample = self.jittered_point_on_light(u, v, &mut rng);
+            if !world.is_shadowed(&sample, point, time) {
                 tot += 1.0;
             }
         }
-        tot / NUM_AREA_SAMPLES as math::F3D
+        tot / self.samples as math::F3D
+    }
+
+    // center of cell (u, v)
+    pub fn point_on_light(&self, u: usize, v: usize) -> Point {
+        self.corner + self.uvec * (u as math::F3D + 0.5) + self.vvec * (v as math::F3D + 0.5)
     }
 
-    fn rnd_point(&self, rng: &mut ThreadRng) -> Point {
-        let x = rng.gen::<f64>() * self.radius;
-        let y = rng.gen::<f64>() * self.radius;
-        self.light.position + vector(x, y, 0.0)
+    // random point within cell (u, v)
+    fn jittered_point_on_light(&self, u: usize, v: usize, rng: &mut ThreadRng) -> Point {
+        self.corner
+            + self.uvec * (u as math::F3D + rng.gen::<math::F3D>())
+            + self.vvec * (v as math::F3D + rng.gen::<math::F3D>())
     }
 }
 
@@ -111,13 +233,27 @@ pub fn point_light(position: Point, intensity: Color) -> Light {
     Light::point(position, intensity)
 }
 
-pub fn area_light(position: Point, intensity: Color, radius: math::F3D) -> Light {
-    Light::area(position, intensity, radius)
+// white-ish point light colored by temperature, e.g. 2700.0 for a warm bulb
+pub fn point_light_kelvin(position: Point, kelvin: math::F3D) -> Light {
+    Light::point(position, Color::from_kelvin(kelvin))
+}
+
+pub fn area_light(
+    corner: Point,
+    full_uvec: Vector,
+    usteps: usize,
+    full_vvec: Vector,
+    vsteps: usize,
+    intensity: Color,
+) -> Light {
+    Light::area(corner, full_uvec, usteps, full_vvec, vsteps, intensity)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::assert_eq_eps;
+    use crate::transformation::*;
 
     #[test]
     fn point_light_has_position_and_intensity() {
@@ -127,10 +263,155 @@ mod tests {
     }
 
     #[test]
-    fn area_light_contains_point_props_and_radius() {
-        let al = Light::area(point_x(), Color::white(), 2.0);
-        assert_eq!(al.position(), point_x());
+    fn point_light_from_kelvin() {
+        let l = point_light_kelvin(point_y(), 2700.0);
+        assert_eq!(l.position(), point_y());
+        assert_eq!(l.intensity(), Color::from_kelvin(2700.0));
+    }
+
+    #[test]
+    fn creating_an_area_light() {
+        let al = Light::area(
+            point_zero(),
+            vector(2.0, 0.0, 0.0),
+            4,
+            vector(0.0, 0.0, 1.0),
+            2,
+            Color::white(),
+        );
+        assert_eq!(al.position(), point(1.0, 0.0, 0.5));
         assert_eq!(al.intensity(), Color::white());
-        assert_eq!(al.radius(), 2.0);
+        match al {
+            Light::Area(a) => {
+                assert_eq!(a.corner, point_zero());
+                assert_eq!(a.uvec, vector(0.5, 0.0, 0.0));
+                assert_eq!(a.usteps, 4);
+                assert_eq!(a.vvec, vector(0.0, 0.0, 0.5));
+                assert_eq!(a.vsteps, 2);
+                assert_eq!(a.samples, 8);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one cell")]
+    fn area_light_without_cells_panics() {
+        Light::area(
+            point_zero(),
+            vector(2.0, 0.0, 0.0),
+            0,
+            vector(0.0, 0.0, 1.0),
+            2,
+            Color::white(),
+        );
+    }
+
+    #[test]
+    fn finding_single_point_on_area_light() {
+        let al = Light::area(
+            point_zero(),
+            vector(2.0, 0.0, 0.0),
+            4,
+            vector(0.0, 0.0, 1.0),
+            2,
+            Color::white(),
+        );
+        if let Light::Area(a) = al {
+            for c in [
+                (0, 0, point(0.25, 0.0, 0.25)),
+                (1, 0, point(0.75, 0.0, 0.25)),
+                (0, 1, point(0.25, 0.0, 0.75)),
+                (2, 0, point(1.25, 0.0, 0.25)),
+                (3, 1, point(1.75, 0.0, 0.75)),
+            ] {
+                assert_eq!(a.point_on_light(c.0, c.1), c.2);
+            }
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn area_light_intensity_at_unshadowed_point_is_full() {
+        let world = World::new(vec![]);
+        let al = Light::area(
+            point(-0.5, 5.0, -0.5),
+            vector_x(),
+            2,
+            vector_z(),
+            2,
+            Color::white(),
+        );
+        assert_eq!(al.intensity_at(&world, &point_zero(), 0.0), 1.0);
+    }
+
+    #[test]
+    fn leftover_area_light_samples_are_unbiased() {
+        // the left cell is hidden behind a cube, the right one is clear
+        let mut world = World::new(vec![]);
+        world.add_shape(
+            crate::shapes::cube::cube().with_transformation(
+                make_translation(-0.3, 2.5, 0.0) * make_scaling(0.3, 0.1, 0.3),
+            ),
+        );
+        let mut light = Light::area(
+            point(-1.0, 5.0, 0.0),
+            vector(2.0, 0.0, 0.0),
+            2,
+            vector(0.0, 0.0, 0.01),
+            1,
+            Color::white(),
+        );
+        if let Light::Area(a) = &mut light {
+            a.samples = 3;
+        }
+        let runs = 2000;
+        let mean = (0..runs)
+            .map(|_| light.intensity_at(&world, &point_zero(), 0.0))
+            .sum::<math::F3D>()
+            / runs as math::F3D;
+        assert!((mean - 0.5).abs() < 0.05, "mean {}", mean);
+    }
+
+    #[test]
+    fn more_area_light_samples_reduce_noise() {
+        let mut world = World::new(vec![]);
+        world
+            .add_shape(crate::shapes::sphere::sphere().with_transformation(
+                make_translation(0.0, 2.5, 0.0) * make_scaling(0.5, 0.5, 0.5),
+            ));
+        let stddev = |samples| {
+            let light =
+                Light::area_with_samples(point(0.0, 5.0, 0.0), Color::white(), 1.0, samples);
+            let runs: Vec<math::F3D> = (0..200)
+                .map(|_| light.intensity_at(&world, &point_zero(), 0.0))
+                .collect();
+            let mean = runs.iter().sum::<math::F3D>() / runs.len() as math::F3D;
+            assert!(mean > 0.0 && mean < 1.0, "not in the penumbra");
+            let var = runs.iter().map(|r| (r - mean).powi(2)).sum::<math::F3D>()
+                / runs.len() as math::F3D;
+            var.sqrt()
+        };
+        assert!(stddev(64) < stddev(4));
+
+        match Light::area_with_samples(point_y(), Color::white(), 1.0, 10) {
+            Light::Area(a) => {
+                assert_eq!(a.samples, 10);
+                assert_eq!((a.usteps, a.vsteps), (3, 3));
+                assert_eq!(a.light.position, point_y());
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn headlight_sits_at_camera_position() {
+        let mut camera = Camera::new(10, 10, glm::half_pi());
+        assert_eq!(Light::headlight(&camera).position(), point_zero());
+
+        camera.transform = view_transform(&point(1.0, 2.0, -5.0), &point_zero(), &vector_y());
+        assert_eq_eps!(Light::headlight(&camera).position(), point(1.0, 2.0, -5.0));
+        assert_eq!(Light::headlight(&camera).intensity(), Color::white());
     }
 }
@@ -2,6 +2,7 @@ extern crate raytracer;
 use clap::Parser;
 
 use raytracer::chapters::appendix1;
+use raytracer::chapters::bench;
 use raytracer::chapters::chapter1;
 use raytracer::chapters::chapter11;
 use raytracer::chapters::chapter12;
@@ -55,6 +56,8 @@ fn main() {
         "patterns" => patterns::run(args.hres, args.vres),
         "appendix1" => appendix1::run(args.hres, args.vres),
         "dragons" => dragons::run(&args.fixture, args.hres, args.vres),
+        // timing only, no output file
+        "bench" => bench::run(args.hres, args.vres),
         _ => println!("No such program: {}", args.name),
     }
 }
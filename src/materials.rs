@@ -7,8 +7,21 @@ use crate::pattern::*;
 use crate::tuple;
 use crate::tuple::*;
 use glm::*;
+use std::borrow::Cow;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, OnceLock};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SpecularModel {
+    // (reflected light . eye)^shininess
+    Phong,
+    // (normal . halfway(light, eye))^shininess, cheaper with a broader highlight
+    BlinnPhong,
+}
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Material {
     pub color: Color,
     pub ambient: F3D,
@@ -16,9 +29,22 @@ pub struct Material {
     pub specular: F3D,
     pub shininess: F3D,
     pub reflective: F3D,
+    // 0 for a sharp mirror, larger values spread reflections into a blur
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub reflection_roughness: F3D,
     pub transparency: F3D,
     pub refractive_index: F3D,
+    // None shows color, see surface_pattern. Patterns aren't saved in
+    // scene files yet
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub pattern: Option<TPattern>,
+    // pattern color sampled as an (r, g, b) -> (x, y, z) offset that tilts the surface normal
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub normal_perturb: Option<TPattern>,
+    // light given off by the surface itself, added once per hit by
+    // World::shade_hit regardless of lighting
+    pub emissive: Color,
+    pub specular_model: SpecularModel,
 }
 
 impl Material {
@@ -30,14 +56,116 @@ impl Material {
             specular,
             shininess,
             reflective: 0.0,
+            reflection_roughness: 0.0,
             transparency: 0.0,
             refractive_index: 1.0,
             pattern: None,
+            normal_perturb: None,
+            emissive: Color::black(),
+            specular_model: SpecularModel::Phong,
+        }
+    }
+
+    // clear glass, most of what you see is reflection and refraction
+    pub fn glass() -> Material {
+        Material {
+            ambient: 0.0,
+            diffuse: 0.1,
+            specular: 1.0,
+            shininess: 300.0,
+            reflective: 0.9,
+            transparency: 1.0,
+            refractive_index: 1.5,
+            ..Material::default()
         }
     }
 
+    // tinted reflections with a tight highlight
+    pub fn metal(color: Color) -> Material {
+        Material {
+            color,
+            diffuse: 0.3,
+            specular: 0.9,
+            shininess: 250.0,
+            reflective: 0.6,
+            ..Material::default()
+        }
+    }
+
+    // diffuse only, no highlight
+    pub fn matte(color: Color) -> Material {
+        Material {
+            color,
+            specular: 0.0,
+            shininess: 10.0,
+            ..Material::default()
+        }
+    }
+
+    // black surface that shows only what it reflects
+    pub fn mirror() -> Material {
+        Material {
+            color: Color::black(),
+            ambient: 0.0,
+            diffuse: 0.0,
+            specular: 1.0,
+            shininess: 300.0,
+            reflective: 1.0,
+            ..Material::default()
+        }
+    }
+
+    pub fn with_emissive(mut self, color: Color) -> Material {
+        self.emissive = color;
+        self
+    }
+
+    pub fn with_reflection_roughness(mut self, roughness: F3D) -> Material {
+        self.reflection_roughness = roughness.max(0.0);
+        self
+    }
+
+    pub fn with_specular_model(mut self, model: SpecularModel) -> Material {
+        self.specular_model = model;
+        self
+    }
+
+    // a solid pattern just becomes the material color
     pub fn set_pattern(&mut self, pattern: Option<TPattern>) {
-        self.pattern = pattern;
+        match pattern {
+            Some(TPattern::Solid(sp)) => self.set_color(sp.color),
+            _ => self.pattern = pattern,
+        }
+    }
+
+    // sets color, dropping a solid pattern that would hide it
+    pub fn set_color(&mut self, color: Color) {
+        self.color = color;
+        if let Some(TPattern::Solid(_)) = self.pattern {
+            self.pattern = None;
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Material {
+        self.set_color(color);
+        self
+    }
+
+    // The pattern lighting samples: the material pattern, or a solid
+    // pattern of color when none is set
+    pub fn surface_pattern(&self) -> Cow<'_, TPattern> {
+        match &self.pattern {
+            Some(p) => Cow::Borrowed(p),
+            None => Cow::Owned(TPattern::Solid(solid::solid_pattern(self.color))),
+        }
+    }
+
+    // surface_pattern sampled at point, without building a solid pattern
+    fn surface_color_at(&self, object: &Object, point: &Point) -> Color {
+        match &self.pattern {
+            Some(p) => p.pattern_at_shape(object, point),
+            None => self.color,
+        }
     }
 
     // Phong lighting
@@ -50,12 +178,22 @@ impl Material {
         normalv: &Vector,
         light_intensity: F3D,
     ) -> Color {
-        // use material pattern for color if it exists
-        let color = if let Some(p) = &self.pattern {
-            p.pattern_at_shape(object, &point)
-        } else {
-            self.color
-        };
+        self.lighting_with_occlusion(object, light, point, eyev, normalv, light_intensity, 1.0)
+    }
+
+    // Phong lighting with the ambient term scaled by an occlusion factor (0 -> 1)
+    #[allow(clippy::too_many_arguments)]
+    pub fn lighting_with_occlusion(
+        &self,
+        object: &Object,
+        light: &Light,
+        point: &Point,
+        eyev: &Vector,
+        normalv: &Vector,
+        light_intensity: F3D,
+        occlusion: F3D,
+    ) -> Color {
+        let color = self.surface_color_at(object, point);
         // combine surface color with lights color/intensity
         let effective_color: Color = color * light.intensity();
 
@@ -63,7 +201,7 @@ impl Material {
         let lightv: Vector = normalize(&(light.position() - point));
 
         // compute ambient contribution
-        let ambient: Color = effective_color * self.ambient;
+        let ambient: Color = effective_color * self.ambient * occlusion * object.ao_factor;
 
         // light_dot_normal represents the cosine of the angle between the light vector and the normal vector. A negative number means the light is on the other side of the surface.
         let light_dot_normal: F3D = lightv.dot(&normalv);
@@ -72,16 +210,20 @@ impl Material {
             ambient
         } else {
             // compute the diffuse contribution
-            let diffuse: Color = effective_color * self.diffuse * light_dot_normal;
+            let falloff = light.falloff_at(point);
+            let diffuse: Color = effective_color * self.diffuse * light_dot_normal * falloff;
             // reflect_dot_eye represents the cosine of the angle between the reflection vector and the eye vector. A negative number means the light reflects away from the eye.
-            let reflectv: Vector = tuple::reflect(-lightv, *normalv);
-            let reflect_dot_eye: F3D = reflectv.dot(&eyev);
+            // Blinn-Phong uses the angle between the normal and the halfway vector instead.
+            let reflect_dot_eye: F3D = match self.specular_model {
+                SpecularModel::Phong => tuple::reflect(-lightv, *normalv).dot(eyev),
+                SpecularModel::BlinnPhong => normalize(&(lightv + eyev)).dot(normalv),
+            };
             let mut specular = Color::black();
 
             if reflect_dot_eye >= 0.0 {
                 // compute the specular contribution
                 let factor: F3D = reflect_dot_eye.powf(self.shininess);
-                specular = light.intensity() * self.specular * factor;
+                specular = light.intensity() * self.specular * factor * falloff;
             }
             (ambient + diffuse + specular) * light_intensity
         }
@@ -94,10 +236,61 @@ impl Default for Material {
     }
 }
 
+/**
+ * A Material behind an Arc, what objects hold. Objects given the same
+ * SharedMaterial (like every triangle of a mesh after set_group_material)
+ * store it once, and every object starts out sharing one default material.
+ * Changing a field through one copies it first if it's shared, so the
+ * other objects keep the original.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct SharedMaterial(Arc<Material>);
+
+impl Default for SharedMaterial {
+    fn default() -> Self {
+        static DEFAULT: OnceLock<SharedMaterial> = OnceLock::new();
+        DEFAULT
+            .get_or_init(|| SharedMaterial::new(Material::default()))
+            .clone()
+    }
+}
+
+impl SharedMaterial {
+    pub fn new(material: Material) -> Self {
+        SharedMaterial(Arc::new(material))
+    }
+
+    // whether both refer to the same stored material
+    pub fn ptr_eq(a: &SharedMaterial, b: &SharedMaterial) -> bool {
+        Arc::ptr_eq(&a.0, &b.0)
+    }
+}
+
+impl From<Material> for SharedMaterial {
+    fn from(material: Material) -> Self {
+        SharedMaterial::new(material)
+    }
+}
+
+impl Deref for SharedMaterial {
+    type Target = Material;
+
+    fn deref(&self) -> &Material {
+        &self.0
+    }
+}
+
+impl DerefMut for SharedMaterial {
+    fn deref_mut(&mut self) -> &mut Material {
+        Arc::make_mut(&mut self.0)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::assert_eq_eps;
+    use crate::assert_eq_feps;
     use crate::pattern::stripe::stripe_pattern;
     use crate::shapes::sphere::*;
 
@@ -114,6 +307,91 @@ mod tests {
         assert_eq!(m.shininess, 200.0);
     }
 
+    #[test]
+    fn material_presets() {
+        let glass = Material::glass();
+        assert_eq!(glass.transparency, 1.0);
+        assert_eq_feps!(glass.refractive_index, 1.5);
+        assert_eq!(Material::mirror().reflective, 1.0);
+
+        let red = Color::new(1.0, 0.0, 0.0);
+        assert_eq!(Material::metal(red).color, red);
+        assert!(Material::metal(red).reflective > 0.0);
+        assert_eq!(Material::matte(red).specular, 0.0);
+        assert_eq!(Material::matte(red).reflective, 0.0);
+    }
+
+    #[test]
+    fn blinn_phong_matches_phong_head_on() {
+        let (m, position, object) = setup();
+        let blinn = m.clone().with_specular_model(SpecularModel::BlinnPhong);
+        let eyev = vector(0.0, 0.0, -1.0);
+        let normalv = vector(0.0, 0.0, -1.0);
+        let light = point_light(point(0.0, 0.0, -10.0), Color::white());
+        assert_eq_eps!(
+            m.lighting(&object, &light, &position, &eyev, &normalv, 1.0)
+                .tuple(),
+            blinn
+                .lighting(&object, &light, &position, &eyev, &normalv, 1.0)
+                .tuple()
+        );
+    }
+
+    #[test]
+    fn blinn_phong_highlight_is_broader() {
+        let (mut m, position, object) = setup();
+        m.shininess = 10.0;
+        let blinn = m.clone().with_specular_model(SpecularModel::BlinnPhong);
+        // eye 45 degrees off the reflected light
+        let eyev = vector(0.0, math::SQRT_2_DIV_2, -math::SQRT_2_DIV_2);
+        let normalv = vector(0.0, 0.0, -1.0);
+        let light = point_light(point(0.0, 0.0, -10.0), Color::white());
+        let phong_c = m.lighting(&object, &light, &position, &eyev, &normalv, 1.0);
+        let blinn_c = blinn.lighting(&object, &light, &position, &eyev, &normalv, 1.0);
+        // 0.9 * (cos(22.5)^10 - cos(45)^10)
+        assert_eq_feps!(blinn_c.red() - phong_c.red(), 0.37963);
+    }
+
+    #[test]
+    fn default_material_samples_its_color_everywhere() {
+        let (mut m, _, object) = setup();
+        m.color = Color::new(0.3, 0.6, 0.9);
+        for p in [point_zero(), point(0.5, -2.0, 9.0), point(-7.0, 0.1, 0.0)] {
+            assert_eq!(m.surface_pattern().pattern_at_shape(&object, &p), m.color);
+        }
+    }
+
+    #[test]
+    fn solid_patterns_become_the_material_color() {
+        let mut m = Material::default();
+        m.set_pattern(Some(TPattern::Solid(solid::solid_pattern(Color::black()))));
+        assert_eq!(m.color, Color::black());
+        assert_eq!(m.pattern, None);
+        // so it matches a material given the color directly
+        assert_eq!(
+            Material::matte(Color::black()),
+            Material {
+                specular: 0.0,
+                shininess: 10.0,
+                ..m
+            }
+        );
+    }
+
+    #[test]
+    fn set_color_replaces_a_solid_pattern() {
+        let mut m = Material {
+            pattern: Some(TPattern::Solid(solid::solid_pattern(Color::white()))),
+            ..Material::default()
+        };
+        m.set_color(Color::new(1.0, 0.0, 0.0));
+        assert_eq!(
+            m.surface_pattern()
+                .pattern_at_shape(&sphere(), &point_zero()),
+            Color::new(1.0, 0.0, 0.0)
+        );
+    }
+
     #[test]
     fn lighting_with_eye_between_light_and_surface() {
         let (m, position, object) = setup();
@@ -124,6 +402,28 @@ mod tests {
         assert_eq!(result, Color::new(1.9, 1.9, 1.9));
     }
 
+    #[test]
+    fn attenuated_light_falls_off_with_distance_squared() {
+        let m = Material {
+            ambient: 0.0,
+            specular: 0.0,
+            ..Material::default()
+        };
+        let (_, position, object) = setup();
+        let eyev = vector(0.0, 0.0, -1.0);
+        let normalv = vector(0.0, 0.0, -1.0);
+        let diffuse_at = |distance: F3D| {
+            let light = point_light(point(0.0, 0.0, -distance), Color::white()).with_attenuation();
+            m.lighting(&object, &light, &position, &eyev, &normalv, 1.0)
+                .red()
+        };
+        assert_eq_feps!(diffuse_at(2.0), 0.9 / 4.0);
+        assert_eq_feps!(diffuse_at(4.0), diffuse_at(2.0) / 4.0);
+        // off by default
+        let light = point_light(point(0.0, 0.0, -4.0), Color::white());
+        assert_eq!(light.falloff_at(&position), 1.0);
+    }
+
     #[test]
     fn lighting_with_eye_between_light_and_surface_eye_offset_45() {
         let (m, position, object) = setup();
@@ -164,6 +464,22 @@ mod tests {
         assert_eq_eps!(result.tuple(), Color::new(0.1, 0.1, 0.1).tuple());
     }
 
+    #[test]
+    fn lighting_leaves_out_emission() {
+        let (_, position, object) = setup();
+        let m = Material {
+            ambient: 0.0,
+            diffuse: 0.0,
+            ..Material::default()
+        }
+        .with_emissive(Color::white());
+        let eyev = vector(0.0, 0.0, -1.0);
+        let normalv = vector(0.0, 0.0, -1.0);
+        let light = point_light(point(0.0, 0.0, 10.0), Color::white());
+        let result = m.lighting(&object, &light, &position, &eyev, &normalv, 1.0);
+        assert_eq_eps!(result.tuple(), Color::black().tuple());
+    }
+
     #[test]
     fn lighting_with_surface_in_shadow() {
         let (m, position, object) = setup();
@@ -40,6 +40,55 @@ pub fn spherical_map(p: &Point) -> (F3D, F3D) {
     (u, v)
 }
 
+// map point on the xz plane to 2d, tiling every unit
+pub fn planar_map(p: &Point) -> (F3D, F3D) {
+    (p.x.rem_euclid(1.0), p.z.rem_euclid(1.0))
+}
+
+// u is the angle around y, v the height (repeating every unit), for wrapping cylinders
+pub fn cylindrical_map(p: &Point) -> (F3D, F3D) {
+    let theta = p.x.atan2(p.z);
+    let raw_u = theta / (glm::pi::<F3D>() * 2.0);
+    let u = 1.0 - (raw_u + 0.5);
+    let v = p.y.rem_euclid(1.0);
+
+    (u, v)
+}
+
+/**
+ * Map a point on the unit cube to one texture laid out as the cross unfold,
+ * 4 faces wide and 3 high:
+ *
+ * ```text
+ *       [up]
+ * [left][front][right][back]
+ *       [down]
+ * ```
+ *
+ * Neighbouring faces in the cross share their edges, so the middle band
+ * wraps around the cube (u = 1 meets u = 0 at the back/left edge) and the
+ * top and bottom meet the front without a seam.
+ */
+pub fn cube_map(p: &Point) -> (F3D, F3D) {
+    let (ax, ay, az) = (p.x.abs(), p.y.abs(), p.z.abs());
+    let max = ax.max(ay).max(az);
+    // (column, row, u and v within the face)
+    let (col, row, u, v) = if max == p.x {
+        (2.0, 1.0, (1.0 - p.z) / 2.0, (p.y + 1.0) / 2.0)
+    } else if max == -p.x {
+        (0.0, 1.0, (p.z + 1.0) / 2.0, (p.y + 1.0) / 2.0)
+    } else if max == p.y {
+        (1.0, 2.0, (p.x + 1.0) / 2.0, (1.0 - p.z) / 2.0)
+    } else if max == -p.y {
+        (1.0, 0.0, (p.x + 1.0) / 2.0, (p.z + 1.0) / 2.0)
+    } else if max == p.z {
+        (1.0, 1.0, (p.x + 1.0) / 2.0, (p.y + 1.0) / 2.0)
+    } else {
+        (3.0, 1.0, (1.0 - p.x) / 2.0, (p.y + 1.0) / 2.0)
+    };
+    ((col + u) / 4.0, (row + v) / 3.0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -76,4 +125,87 @@ mod tests {
             assert_eq!(v, c.2);
         }
     }
+
+    #[test]
+    fn planar_mapping_on_3d_point() {
+        for c in [
+            (point(0.25, 0.0, 0.5), 0.25, 0.5),
+            (point(0.25, 0.0, -0.25), 0.25, 0.75),
+            (point(0.25, 0.5, -0.25), 0.25, 0.75),
+            (point(1.25, 0.0, 0.5), 0.25, 0.5),
+            (point(0.25, 0.0, -1.75), 0.25, 0.25),
+            (point(1.0, 0.0, -1.0), 0.0, 0.0),
+            (point_zero(), 0.0, 0.0),
+        ] {
+            let (u, v) = planar_map(&c.0);
+            assert_eq_feps!(u, c.1);
+            assert_eq_feps!(v, c.2);
+        }
+    }
+
+    #[test]
+    fn cylindrical_mapping_on_3d_point() {
+        for c in [
+            (point(0.0, 0.0, -1.0), 0.0, 0.0),
+            (point(0.0, 0.5, -1.0), 0.0, 0.5),
+            (point(0.0, 1.0, -1.0), 0.0, 0.0),
+            (point(SQRT_2_DIV_2, 0.5, -SQRT_2_DIV_2), 0.125, 0.5),
+            (point(1.0, 0.5, 0.0), 0.25, 0.5),
+            (point(SQRT_2_DIV_2, 0.5, SQRT_2_DIV_2), 0.375, 0.5),
+            (point(0.0, -0.25, 1.0), 0.5, 0.75),
+            (point(-SQRT_2_DIV_2, 0.5, SQRT_2_DIV_2), 0.625, 0.5),
+            (point(-1.0, 1.25, 0.0), 0.75, 0.25),
+            (point(-SQRT_2_DIV_2, 0.5, -SQRT_2_DIV_2), 0.875, 0.5),
+        ] {
+            let (u, v) = cylindrical_map(&c.0);
+            assert_eq_feps!(u, c.1);
+            assert_eq_feps!(v, c.2);
+        }
+    }
+
+    #[test]
+    fn cube_mapping_is_continuous_across_cross_edges() {
+        let d = 1e-6;
+        let close = |a: (F3D, F3D), b: (F3D, F3D)| {
+            assert!(
+                (a.0 - b.0).abs() < 1e-5 && (a.1 - b.1).abs() < 1e-5,
+                "{:?} {:?}",
+                a,
+                b
+            );
+        };
+        // front / right
+        close(
+            cube_map(&point(1.0 - d, 0.3, 1.0)),
+            cube_map(&point(1.0, 0.3, 1.0 - d)),
+        );
+        // left / front
+        close(
+            cube_map(&point(-1.0, -0.4, 1.0 - d)),
+            cube_map(&point(-1.0 + d, -0.4, 1.0)),
+        );
+        // right / back
+        close(
+            cube_map(&point(1.0, 0.1, -1.0 + d)),
+            cube_map(&point(1.0 - d, 0.1, -1.0)),
+        );
+        // front / up and front / down
+        close(
+            cube_map(&point(0.2, 1.0 - d, 1.0)),
+            cube_map(&point(0.2, 1.0, 1.0 - d)),
+        );
+        close(
+            cube_map(&point(0.2, -1.0 + d, 1.0)),
+            cube_map(&point(0.2, -1.0, 1.0 - d)),
+        );
+        // back / left wraps around u
+        let (a, b) = (
+            cube_map(&point(-1.0 + d, 0.5, -1.0)),
+            cube_map(&point(-1.0, 0.5, -1.0 + d)),
+        );
+        close((a.0 - 1.0, a.1), b);
+        // face centers land in the middle of their cells
+        close(cube_map(&point(0.0, 0.0, 1.0)), (0.375, 0.5));
+        close(cube_map(&point(0.0, 1.0, 0.0)), (0.375, 5.0 / 6.0));
+    }
 }
@@ -1,9 +1,48 @@
 use glm::*;
+use std::fmt;
 
 use crate::math::F3D;
 
 pub type Matrix4 = TMat4<F3D>;
 
+// a matrix is treated as singular when its determinant is below this
+// fraction of the cube of its largest axis length, so uniformly tiny or
+// huge scalings still invert but squashing one axis flat doesn't
+const MIN_RELATIVE_DETERMINANT: F3D = 1e-12;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransformError {
+    // matrix has no usable inverse, holds the determinant
+    Singular(F3D),
+}
+
+impl fmt::Display for TransformError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransformError::Singular(det) => {
+                write!(f, "transform is not invertible (determinant {})", det)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TransformError {}
+
+/**
+ * Inverse of m, or an error instead of NaNs when m is (nearly) singular
+ */
+pub fn checked_inverse(m: &Matrix4) -> Result<Matrix4, TransformError> {
+    let det = determinant(m);
+    let linear = mat4_to_mat3(m);
+    let scale = (0..3).map(|c| linear.column(c).norm()).fold(0.0, F3D::max);
+
+    if !det.is_finite() || det == 0.0 || det.abs() < MIN_RELATIVE_DETERMINANT * scale.powi(3) {
+        Err(TransformError::Singular(det))
+    } else {
+        Ok(inverse(m))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -28,4 +67,29 @@ mod tests {
         let t1 = m * t;
         assert_eq!(t1, Vec4::new(18.0, 24.0, 33.0, 1.0));
     }
+
+    #[test]
+    fn checked_inverse_of_invertible_matrix() {
+        let m = scaling(&vec3(2.0, 4.0, 8.0));
+        assert_eq!(checked_inverse(&m), Ok(scaling(&vec3(0.5, 0.25, 0.125))));
+    }
+
+    #[test]
+    fn checked_inverse_of_singular_matrix() {
+        let m = scaling(&vec3(0.0, 1.0, 1.0));
+        assert_eq!(checked_inverse(&m), Err(TransformError::Singular(0.0)));
+    }
+
+    #[test]
+    fn checked_inverse_of_tiny_scaling() {
+        let m = scaling(&vec3(1e-4, 1e-4, 1e-4));
+        let inv = checked_inverse(&m).unwrap();
+        assert!((inv * m - Matrix4::identity()).norm() < 1e-12);
+    }
+
+    #[test]
+    fn checked_inverse_of_nearly_flat_matrix() {
+        let m = scaling(&vec3(1e-14, 1.0, 1.0));
+        assert!(checked_inverse(&m).is_err());
+    }
 }
@@ -4,14 +4,17 @@ use crate::bounds::*;
  */
 use crate::math::*;
 use crate::object::*;
+use crate::shapes::mesh::*;
 use crate::shapes::shape::*;
 use crate::shapes::smooth_triangle::*;
 use crate::shapes::triangle::*;
+use crate::transformation::*;
 use crate::tuple::*;
+use rayon::prelude::*;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::{BufReader, Error};
+use std::io::{BufReader, Error, ErrorKind};
 use tobj::*;
 
 type GroupMap = HashMap<String, Object>;
@@ -101,17 +104,13 @@ impl ObjData {
 
         for (_, m) in models.iter().enumerate() {
             let mesh = &m.mesh;
-            let mut triangles = vec![];
-
-            for j in 0..(mesh.indices.len() / 3) {
-                let idx = j * 3;
-                triangles.push(ObjData::make_triangle(
-                    &mesh.positions,
-                    &mesh.indices,
-                    &mesh.normals,
-                    idx,
-                ));
-            }
+            // indexed parallel collect keeps the triangles in file order
+            let triangles: Vec<Object> = (0..(mesh.indices.len() / 3))
+                .into_par_iter()
+                .map(|j| {
+                    ObjData::make_triangle(&mesh.positions, &mesh.indices, &mesh.normals, j * 3)
+                })
+                .collect();
             let hash_key = if m.name != "unnamed_object" {
                 m.name.as_str()
             } else {
@@ -135,6 +134,11 @@ impl ObjData {
         self.groups.get(&DEFAULT_GROUP_KEY.to_string())
     }
 
+    // one top level group holding each file's to_group, for composing scenes
+    pub fn merge(parts: &[ObjData]) -> Object {
+        Object::new_group(parts.iter().map(ObjData::to_group).collect())
+    }
+
     pub fn to_group(&self) -> Object {
         let mut gs = vec![];
 
@@ -150,6 +154,60 @@ impl ObjData {
             panic!("no groups!");
         }
     }
+
+    /**
+     * Alternative to to_group: one IndexedMesh per model, sharing the file's
+     * vertices instead of copying them into a Triangle object per face.
+     * Several models are wrapped in a group.
+     */
+    pub fn to_indexed_mesh(&self) -> Object {
+        let mut meshes: Vec<Object> = self
+            .raw
+            .iter()
+            .map(|m| {
+                let mesh = &m.mesh;
+                let vertices = (0..mesh.positions.len() / 3)
+                    .map(|i| ObjData::make_vertex(&mesh.positions, i * 3))
+                    .collect();
+                let normals = (0..mesh.normals.len() / 3)
+                    .map(|i| ObjData::make_normal(&mesh.normals, i * 3))
+                    .collect();
+                indexed_mesh(vertices, normals, &mesh.indices)
+            })
+            .collect();
+        match meshes.len() {
+            0 => panic!("no groups!"),
+            1 => meshes.remove(0),
+            _ => Object::new_group(meshes),
+        }
+    }
+
+    // Combined bounds of all groups' triangles
+    pub fn bounds(&self) -> Bounds {
+        let mut b = Bounds::default();
+
+        for g in self.groups.values() {
+            b.add_bounds(&g.bounds());
+        }
+        b
+    }
+
+    /**
+     * Group centered at the origin and scaled uniformly to fit in a
+     * (-1, -1, -1) -> (1, 1, 1) cube
+     */
+    pub fn normalized_group(&self) -> Object {
+        let b = self.bounds();
+        let size = b.max - b.min;
+        let largest = glm::max3_scalar(size.x, size.y, size.z);
+        let scale = if largest > 0.0 { 2.0 / largest } else { 1.0 };
+        let center = b.min + size / 2.0;
+
+        self.to_group().transform(
+            &(make_scaling(scale, scale, scale)
+                * make_translation(-center.x, -center.y, -center.z)),
+        )
+    }
 }
 
 // We get free fan triangulation with this
@@ -161,6 +219,17 @@ pub fn parse_obj_file(filename: &str) -> Result<ObjData, Error> {
     Ok(ObjData::new(models))
 }
 
+// same as parse_obj_file with the OBJ text in memory
+pub fn parse_obj_str(data: &str) -> Result<ObjData, Error> {
+    let (models, _) =
+        tobj::load_obj_buf(&mut BufReader::new(data.as_bytes()), &LOAD_OPTIONS, |_| {
+            Ok(Default::default())
+        })
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+    Ok(ObjData::new(models))
+}
+
 fn debug_model(models: &Vec<Model>) {
     for (i, m) in models.iter().enumerate() {
         let mesh = &m.mesh;
@@ -224,11 +293,15 @@ fn debug_model(models: &Vec<Model>) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::materials::SharedMaterial;
 
     const TEST_FILE: &str = "obj_file";
 
     fn test_filename(id: &str) -> String {
-        format!("tests/{}-{}.obj", TEST_FILE, id)
+        std::env::temp_dir()
+            .join(format!("{}-{}.obj", TEST_FILE, id))
+            .to_string_lossy()
+            .into_owned()
     }
 
     fn write_obj_file(filename: &str, contents: &str) -> std::io::Result<()> {
@@ -425,4 +498,123 @@ f 1/0/3 2/102/1 3/14/2
             _ => panic!(),
         }
     }
+
+    #[test]
+    fn normalized_group_is_centered_and_unit_sized() {
+        let filedata = "
+v 0 0 0
+v 10 0 0
+v 10 10 0
+v 0 10 10
+
+f 1 2 3
+f 1 3 4
+";
+        let fname = test_filename("normalized");
+        write_obj_file(fname.as_str(), filedata).unwrap();
+
+        let data = parse_obj_file(fname.as_str()).unwrap();
+        let b = data.normalized_group().bounds();
+        assert_eq_eps!(b.min, point(-1.0, -1.0, -1.0));
+        assert_eq_eps!(b.max, point(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn indexed_mesh_shares_vertices() {
+        let filedata = "
+v -1 1 0
+v -1 0 0
+v 1 0 0
+v 1 1 0
+
+f 1 2 3 4
+";
+        let fname = test_filename("indexed-mesh");
+        write_obj_file(fname.as_str(), filedata).unwrap();
+
+        let data = parse_obj_file(fname.as_str()).unwrap();
+        let mesh = data.to_indexed_mesh();
+        match mesh.shape() {
+            Shape::IndexedMesh(m) => {
+                assert_eq!(m.triangle_count(), 2);
+                assert_eq!(
+                    m.face(1),
+                    (
+                        point(-1.0, 1.0, 0.0),
+                        point(1.0, 0.0, 0.0),
+                        point(1.0, 1.0, 0.0)
+                    )
+                );
+            }
+            _ => panic!(),
+        }
+        assert_eq!(mesh.bounds(), data.bounds());
+    }
+
+    #[test]
+    fn merging_parsed_files() {
+        let quad = parse_obj_str("v -1 1 0\nv -1 0 0\nv 1 0 0\nv 1 1 0\nf 1 2 3 4\n").unwrap();
+        let tri = parse_obj_str("v 0 0 5\nv 1 0 5\nv 0 1 5\nf 1 2 3\n").unwrap();
+        let merged = ObjData::merge(&[quad, tri]);
+        match merged.shape() {
+            Shape::Group(g) => assert_eq!(g.children().len(), 2),
+            _ => panic!(),
+        }
+        let mut world = crate::world::World::new(vec![]);
+        world.add_shape(merged);
+        assert_eq!(world.summary().triangles, 3);
+        assert_eq!(world.summary().bounds.max, point(1.0, 1.0, 5.0));
+    }
+
+    #[test]
+    fn parallel_triangle_construction_matches_serial_order() {
+        let mut filedata = String::new();
+        for i in 0..1002 {
+            filedata.push_str(&format!("v {} {} 0\n", i, i % 2));
+        }
+        for k in 1..=1000 {
+            filedata.push_str(&format!("f {} {} {}\n", k, k + 1, k + 2));
+        }
+        let fname = test_filename("many-faces");
+        write_obj_file(fname.as_str(), &filedata).unwrap();
+
+        let data = parse_obj_file(fname.as_str()).unwrap();
+        let children = match data.default_group().unwrap().shape() {
+            Shape::Group(g) => g.children().clone(),
+            _ => panic!(),
+        };
+        assert_eq!(children.len(), 1000);
+
+        let mesh = &data.raw[0].mesh;
+        for (j, child) in children.iter().enumerate() {
+            let serial =
+                ObjData::make_triangle(&mesh.positions, &mesh.indices, &mesh.normals, j * 3);
+            match (child.shape(), serial.shape()) {
+                (Shape::Triangle(a), Shape::Triangle(b)) => assert_eq!(a, b),
+                _ => panic!(),
+            }
+        }
+    }
+
+    #[test]
+    fn imported_triangles_share_one_material() {
+        let mut filedata = String::new();
+        for i in 0..1000 {
+            filedata += &format!("v {} 0 0\nv {} 1 0\nv {} 0 1\n", i, i, i);
+        }
+        for i in 0..1000 {
+            filedata += &format!("f {} {} {}\n", i * 3 + 1, i * 3 + 2, i * 3 + 3);
+        }
+        let fname = test_filename("shared-material");
+        write_obj_file(fname.as_str(), &filedata).unwrap();
+
+        let group = parse_obj_file(fname.as_str()).unwrap().to_group();
+        let leaves = group.leaves();
+        assert_eq!(leaves.len(), 1000);
+        let first = &leaves[0].material;
+        assert!(leaves
+            .iter()
+            .all(|o| SharedMaterial::ptr_eq(&o.material, first)));
+        assert!(SharedMaterial::ptr_eq(first, &SharedMaterial::default()));
+    }
 }
@@ -2,34 +2,68 @@
  * Represents an object in a World
  */
 use crate::bounds::*;
+use crate::color::Color;
 use crate::intersection::*;
-use crate::materials::Material;
+use crate::materials::{Material, SharedMaterial};
 use crate::math;
-use crate::matrix::Matrix4;
+use crate::matrix::{checked_inverse, Matrix4, TransformError};
 use crate::ray::Ray;
-use crate::shapes::{csg::*, cylinder::*, group::*, shape::*, sphere::*};
+use crate::shapes::{
+    cone::*, csg::*, cylinder::*, group::*, plane::*, quad::*, shape::*, smooth_triangle::*,
+    sphere::*,
+};
+use crate::stats::RenderStats;
+use crate::transformation::Transformable;
 use crate::tuple::*;
-use glm::*;
 use std::fmt;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 pub fn get_unique_id() -> usize {
     static COUNTER: AtomicUsize = AtomicUsize::new(1);
     COUNTER.fetch_add(1, Ordering::Relaxed)
 }
 
-#[derive(Clone)]
 pub struct Object {
     pub id: String,
     pub transform: Matrix4,
     pub transformation_inverse: Matrix4,
     pub transformation_inverse_transpose: Matrix4,
-    pub material: Material,
+    // where the object has moved to at shutter time 1, None for static objects
+    pub transform_end: Option<Matrix4>,
+    pub material: SharedMaterial,
     pub bounds: Bounds,
     pub has_shadow: bool,
+    // baked ambient occlusion (see World::bake_ao), scales the ambient term
+    pub ao_factor: math::F3D,
     pub shape: Shape,
 }
 
+#[cfg(test)]
+thread_local! {
+    // Object clones made on this thread, for tests checking nothing is copied
+    pub static CLONE_COUNT: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+impl Clone for Object {
+    fn clone(&self) -> Self {
+        #[cfg(test)]
+        CLONE_COUNT.with(|c| c.set(c.get() + 1));
+        Object {
+            id: self.id.clone(),
+            transform: self.transform,
+            transformation_inverse: self.transformation_inverse,
+            transformation_inverse_transpose: self.transformation_inverse_transpose,
+            transform_end: self.transform_end,
+            material: self.material.clone(),
+            bounds: self.bounds,
+            has_shadow: self.has_shadow,
+            ao_factor: self.ao_factor,
+            shape: self.shape.clone(),
+        }
+    }
+}
+
 impl Object {
     pub fn new(id: Option<String>) -> Object {
         Object {
@@ -51,12 +85,108 @@ impl Object {
         }
     }
 
+    /**
+     * Triangle mesh approximating a unit sphere, built from rings (pole to pole)
+     * and segments (around the y axis) of smooth triangles
+     */
+    pub fn new_uv_sphere(rings: usize, segments: usize) -> Object {
+        let vertex = |ring: usize, segment: usize| {
+            let phi = glm::pi::<math::F3D>() * ring as math::F3D / rings as math::F3D;
+            let theta = glm::two_pi::<math::F3D>() * segment as math::F3D / segments as math::F3D;
+            let (x, y, z) = (phi.sin() * theta.cos(), phi.cos(), phi.sin() * theta.sin());
+            (point(x, y, z), vector(x, y, z))
+        };
+        let mut triangles = vec![];
+
+        for i in 0..rings {
+            for j in 0..segments {
+                let (p1, n1) = vertex(i, j);
+                let (p2, n2) = vertex(i + 1, j);
+                let (p3, n3) = vertex(i + 1, j + 1);
+                let (p4, n4) = vertex(i, j + 1);
+
+                // rings touching a pole collapse to a single triangle
+                if i != 0 {
+                    triangles.push(smooth_triangle(p1, p2, p4, n1, n2, n4));
+                }
+                if i != rings - 1 {
+                    triangles.push(smooth_triangle(p2, p3, p4, n2, n3, n4));
+                }
+            }
+        }
+        Object::new_group(triangles)
+    }
+
+    /**
+     * Object placing shared geometry with its own transform, so a heavy
+     * mesh can appear many times while stored once. Groups and CSG keep
+     * their transforms in their children and can't be shared this way,
+     * use an IndexedMesh (ObjData::to_indexed_mesh) instead of a group.
+     * Instances of instances share the innermost shape.
+     */
+    pub fn instance_of(mut shared: Arc<Shape>, transform: &Matrix4) -> Object {
+        while let Shape::Instance(inner) = &*shared {
+            shared = inner.clone();
+        }
+        assert!(
+            !matches!(*shared, Shape::Group(_) | Shape::Csg(_)),
+            "instance_of: groups and csg can't be instanced"
+        );
+        Object::new(None)
+            .with_shape(Shape::Instance(shared))
+            .with_transformation(*transform)
+    }
+
     pub fn new_cylinder(min: math::F3D, max: math::F3D, closed: bool) -> Object {
+        Object::new_cylinder_r(min, max, closed, 1.0)
+    }
+
+    // Like new_cylinder but rejects min > max, which would never be hit
+    pub fn try_new_cylinder(
+        min: math::F3D,
+        max: math::F3D,
+        closed: bool,
+    ) -> Result<Object, ShapeError> {
+        check_extent(min, max)?;
+        Ok(Object::new_cylinder(min, max, closed))
+    }
+
+    // Like new_cone_r(min, max, closed, 1.0) but rejects min > max
+    pub fn try_new_cone(
+        min: math::F3D,
+        max: math::F3D,
+        closed: bool,
+    ) -> Result<Object, ShapeError> {
+        check_extent(min, max)?;
+        Ok(Object::new_cone_r(min, max, closed, 1.0))
+    }
+
+    pub fn new_cylinder_r(
+        min: math::F3D,
+        max: math::F3D,
+        closed: bool,
+        radius: math::F3D,
+    ) -> Object {
         let mut o = Object {
             shape: Shape::Cylinder(Cylinder {
                 minimum: min,
                 maximum: max,
                 closed,
+                radius,
+            }),
+            ..Object::default()
+        };
+        o.bounds = o.shape.bounds();
+        o
+    }
+
+    pub fn new_cone_r(min: math::F3D, max: math::F3D, closed: bool, radius: math::F3D) -> Object {
+        let mut o = Object {
+            shape: Shape::Cone(Cone {
+                minimum: min,
+                maximum: max,
+                closed,
+                radius,
             }),
             ..Object::default()
         };
@@ -80,7 +210,7 @@ impl Object {
             })
             .collect();
         let group_builder = GroupBuilder::Node(Object::new_dummy(), children_group_builders);
-        let object = group_builder.build(false, &Material::default());
+        let object = group_builder.build(false, &SharedMaterial::default());
 
         Object {
             bounds: object.shape.bounds(),
@@ -88,6 +218,16 @@ impl Object {
         }
     }
 
+    // finite parallelogram, normal is u_edge x v_edge
+    pub fn new_quad(origin: Point, u_edge: Vector, v_edge: Vector) -> Object {
+        Object::new(None).with_shape(Shape::Quad(Quad::new(origin, u_edge, v_edge)))
+    }
+
+    // infinite plane through the origin with the given normal, see Plane::facing
+    pub fn new_plane_facing(normal: Vector) -> Object {
+        Object::new(None).with_shape(Shape::Plane(Plane::facing(normal)))
+    }
+
     pub fn new_csg(csg_op: CsgOp, left: &Object, right: &Object) -> Object {
         let mut o = Object {
             shape: Shape::Csg(Csg::new(csg_op, left, right)),
@@ -97,6 +237,38 @@ impl Object {
         o
     }
 
+    /**
+     * Union of all the shapes as a balanced tree of binary csg unions,
+     * depth is ceil(log2(n))
+     */
+    pub fn new_union_many(mut shapes: Vec<Object>) -> Object {
+        assert!(!shapes.is_empty(), "new_union_many: no shapes");
+        if shapes.len() == 1 {
+            return shapes.remove(0);
+        }
+        let right = shapes.split_off(shapes.len() / 2);
+        Object::new_csg(
+            CsgOp::Union,
+            &Object::new_union_many(shapes),
+            &Object::new_union_many(right),
+        )
+    }
+
+    /**
+     * True if other (compared by id) is anywhere below this object in
+     * a group or csg tree
+     */
+    pub fn contains(&self, other: &Object) -> bool {
+        match &self.shape {
+            Shape::Group(g) => g
+                .children()
+                .iter()
+                .any(|child| child.id == other.id || child.contains(other)),
+            Shape::Csg(c) => c.contains(other),
+            _ => false,
+        }
+    }
+
     pub fn with_shape(mut self, shape: Shape) -> Self {
         self.shape = shape;
         self.bounds = self.shape.bounds();
@@ -116,10 +288,61 @@ impl Object {
     }
 
     pub fn set_transform(&mut self, t: &Matrix4) {
+        if let Err(e) = self.try_set_transform(t) {
+            panic!("set_transform: {}", e);
+        }
+    }
+
+    // Like set_transform but leaves the object untouched if t can't be inverted
+    pub fn try_set_transform(&mut self, t: &Matrix4) -> Result<(), TransformError> {
+        let inverse = checked_inverse(t)?;
+
+        if let Shape::Csg(c) = &self.shape {
+            // swap the old transform for the new one in the operands
+            let delta = t * self.transformation_inverse;
+            self.shape = Shape::Csg(c.transformed(&delta));
+        }
         self.transform = *t;
-        self.transformation_inverse = glm::inverse(&self.transform);
+        self.transformation_inverse = inverse;
         self.transformation_inverse_transpose = glm::transpose(&self.transformation_inverse);
+        self.update_bounds();
+        Ok(())
+    }
+
+    // world bounds, covering the whole path of a moving object
+    fn update_bounds(&mut self) {
+        // csg operands already carry the transform, see try_set_transform
+        if let Shape::Csg(c) = &self.shape {
+            self.bounds = c.bounds();
+            return;
+        }
         self.bounds = self.shape.bounds().transform(&self.transform);
+        if let Some(end) = &self.transform_end {
+            self.bounds.add_bounds(&self.shape.bounds().transform(end));
+        }
+    }
+
+    /**
+     * Linear motion blur: the object moves from its transform at shutter
+     * time 0 to end at time 1. Groups and CSG bake transforms into their
+     * children so can't be moved this way. Shadow rays carry the shutter
+     * time of the hit; patterns use the time 0 position.
+     */
+    pub fn with_motion(mut self, end: Matrix4) -> Self {
+        if matches!(self.shape, Shape::Group(_) | Shape::Csg(_)) {
+            panic!("with_motion: {} can't move", self.get_id());
+        }
+        self.transform_end = Some(end);
+        self.update_bounds();
+        self
+    }
+
+    // inverse transform at shutter time t
+    fn inverse_at(&self, time: math::F3D) -> Matrix4 {
+        match &self.transform_end {
+            Some(end) if time != 0.0 => glm::inverse(&(self.transform * (1.0 - time) + end * time)),
+            _ => self.transformation_inverse,
+        }
     }
 
     pub fn with_transformation(mut self, transformation: Matrix4) -> Self {
@@ -128,7 +351,29 @@ impl Object {
         self
     }
 
-    pub fn with_material(mut self, material: Material) -> Self {
+    /**
+     * Add a child (in group space) to a group, updating the group's
+     * bounds and this object's world bounds
+     */
+    pub fn add_child(&mut self, child: Object) {
+        match &mut self.shape {
+            Shape::Group(g) => g.add_child(child),
+            _ => panic!("add_child: {} is not a group", self.get_id()),
+        }
+        self.update_bounds();
+    }
+
+    // per-vertex colors, only meaningful for smooth triangles
+    pub fn with_vertex_colors(mut self, c1: Color, c2: Color, c3: Color) -> Self {
+        if let Shape::SmoothTriangle(t) = &mut self.shape {
+            t.c1 = Some(c1);
+            t.c2 = Some(c2);
+            t.c3 = Some(c3);
+        }
+        self
+    }
+
+    pub fn with_material(mut self, material: impl Into<SharedMaterial>) -> Self {
         self.set_material(material);
 
         self
@@ -138,36 +383,107 @@ impl Object {
         &self.material
     }
 
-    pub fn set_material(&mut self, t: Material) {
+    pub fn set_material(&mut self, t: impl Into<SharedMaterial>) {
         // If I am a group, use set_group_material for now
-        self.material = t;
+        self.material = t.into();
     }
 
-    pub fn intersect(&self, ray: &Ray) -> Intersections {
-        let t_ray = ray.transform(inverse(&self.get_transform()));
-        match self.shape() {
-            Shape::Group(g) => g.intersects(&t_ray),
-            Shape::Csg(c) => c.intersect(&t_ray),
-            _ => Intersections::from_intersections(
-                self.shape
-                    .intersect(&t_ray)
-                    .into_iter()
-                    .map(|t| Intersection::with_uv(self, t.0, t.1, t.2))
-                    .collect(),
-            ),
+    pub fn intersect(&self, ray: &Ray) -> Intersections<'_> {
+        self.intersect_with_stats(ray, None)
+    }
+
+    // same as intersect, counting shape tests and skipped groups into stats
+    pub fn intersect_with_stats(
+        &self,
+        ray: &Ray,
+        stats: Option<&RenderStats>,
+    ) -> Intersections<'_> {
+        let mut xs = Intersections::new();
+        self.intersect_into_with_stats(ray, &mut xs, stats);
+        xs
+    }
+
+    // adds this object's hits to buf, keeping it sorted by t
+    pub fn intersect_into<'a>(&'a self, ray: &Ray, buf: &mut Intersections<'a>) {
+        self.intersect_into_with_stats(ray, buf, None);
+    }
+
+    pub fn intersect_into_with_stats<'a>(
+        &'a self,
+        ray: &Ray,
+        buf: &mut Intersections<'a>,
+        stats: Option<&RenderStats>,
+    ) {
+        // inverse is cached by set_transform, only moving objects invert per ray
+        let t_ray = ray.transform(self.inverse_at(ray.time));
+        match (stats, self.shape()) {
+            (Some(_), Shape::Group(_)) | (Some(_), Shape::None) | (None, _) => (),
+            (Some(s), _) => s.record_intersection_test(),
+        }
+        match self.shape().geometry() {
+            Shape::Group(g) => g.intersect_into(&t_ray, buf, stats),
+            // operands already carry the csg transform
+            Shape::Csg(c) => c
+                .intersect(ray)
+                .iter()
+                .for_each(|is| buf.insert_sorted(is.clone())),
+            // mesh hits also carry which face, for the normal
+            Shape::IndexedMesh(m) => {
+                for (t, u, v, face) in m.local_intersect(&t_ray) {
+                    buf.insert_sorted(Intersection::with_uv(self, t, u, v).with_face(face));
+                }
+            }
+            _ => {
+                for (t, u, v) in self.shape.intersect(&t_ray) {
+                    buf.insert_sorted(Intersection::with_uv(self, t, u, v));
+                }
+            }
         }
     }
 
     pub fn normal_at(&self, world_point: Point, is: Option<&Intersection>) -> Vector {
-        let local_point = self.world_to_object(&world_point);
-        let local_normal = self.shape().normal_at(&local_point, is);
-        self.normal_to_world(&local_normal)
+        self.normal_at_time(world_point, is, 0.0)
+    }
+
+    // normal of a moving object where it is at shutter time t
+    pub fn normal_at_time(
+        &self,
+        world_point: Point,
+        is: Option<&Intersection>,
+        time: math::F3D,
+    ) -> Vector {
+        if self.transform_end.is_none() || time == 0.0 {
+            let local_point = self.world_to_object(&world_point);
+            let local_normal = self.shape().normal_at(&local_point, is);
+            return self.checked_normal(self.normal_to_world(&local_normal));
+        }
+        let inverse = self.inverse_at(time);
+        let local_normal = self.shape().normal_at(&(inverse * world_point), is);
+        let mut n = glm::transpose(&inverse) * local_normal;
+        n.w = 0.0;
+        self.checked_normal(n.normalize())
+    }
+
+    // debug builds stop at NaN or non-unit normals (degenerate shapes) instead of shading black
+    fn checked_normal(&self, n: Vector) -> Vector {
+        debug_assert!(
+            n.iter().all(|c| c.is_finite()) && math::f_equals(n.magnitude(), 1.0),
+            "bad normal {:?} on {}",
+            n,
+            self.get_id()
+        );
+        n
     }
 
     pub fn world_to_object(&self, world_point: &Point) -> Point {
         self.get_transformation_inverse() * world_point
     }
 
+    /**
+     * No flip is needed for mirror (negative determinant) transforms: the
+     * inverse transpose keeps n . (p - p0) the same sign in both spaces, so
+     * a normal that points out of the local shape points out of the mirrored one.
+     */
     pub fn normal_to_world(&self, normal: &Vector) -> Vector {
         let mut n = self.transformation_inverse_transpose * normal;
         n.w = 0.0; // crucial
@@ -178,6 +494,25 @@ impl Object {
         &self.shape
     }
 
+    // groups pass the factor down, lighting reads it from the shape that was hit
+    pub fn set_ao_factor(&mut self, factor: math::F3D) {
+        self.ao_factor = factor;
+        if let Shape::Group(g) = &mut self.shape {
+            for child in g.children_mut() {
+                child.set_ao_factor(factor);
+            }
+        }
+    }
+
+    // itself for a plain shape, every leaf below it for a group, none for csg
+    pub fn leaves(&self) -> Vec<&Object> {
+        match self.shape() {
+            Shape::Group(g) => g.leaves(),
+            Shape::Csg(_) => vec![],
+            _ => vec![self],
+        }
+    }
+
     pub fn is_shape(&self) -> bool {
         match self.shape() {
             Shape::None => false,
@@ -189,6 +524,11 @@ impl Object {
         self.bounds
     }
 
+    // (center, radius) enclosing the object's bounds
+    pub fn bounding_sphere(&self) -> (Point, math::F3D) {
+        self.bounds.bounding_sphere()
+    }
+
     pub fn divide(self, threshold: usize) -> Self {
         Self {
             shape: self.shape.divide(threshold),
@@ -199,34 +539,24 @@ impl Object {
     /**
      * Need to call this manually on group objects for transformations
      */
-    pub fn transform(self, new_transformation: &Matrix4) -> Self {
-        if let Shape::Group(g) = self.shape() {
-            // Each time a Group is transformed, we convert it back to a GroupBuilder,
-            // which is easier to manipulate. It's not the most efficient, but as this
-            // is only peformed when constructing objects of a world, it has no impact on
-            // the rendering itself.
-            let children_group_builders =
-                g.children().iter().map(GroupBuilder::from_object).collect();
-
-            // We then create a new top GroupBuilder Node from which the new transformation is
-            // applied.
-            let group_builder = GroupBuilder::Node(
-                Object::new_dummy().with_transformation(*new_transformation),
-                children_group_builders,
-            );
-
-            // Convert back to a Group.
-            group_builder.build(false, self.get_material())
+    pub fn transform(mut self, new_transformation: &Matrix4) -> Self {
+        let new_t = new_transformation * self.transform;
+        if let Shape::Group(g) = &mut self.shape {
+            // Groups bake the transform into their children, which are moved
+            // down the tree rather than cloned into a new one. The group itself
+            // keeps the identity transform (and its id and material).
+            g.transform_children(&new_t);
+            self.with_transformation(glm::identity())
         } else {
-            let new_t = new_transformation * self.transform;
             self.with_transformation(new_t)
         }
     }
 
     /**
-     * Extra function for groups to propagate materials to their children
+     * Extra function for groups to propagate materials to their children,
+     * which all share the one material
      */
-    pub fn set_group_material(self, new_material: Material) -> Self {
+    pub fn set_group_material(self, new_material: impl Into<SharedMaterial>) -> Self {
         if let Shape::Group(g) = self.shape() {
             let children_group_builders =
                 g.children().iter().map(GroupBuilder::from_object).collect();
@@ -234,7 +564,7 @@ impl Object {
             let group_builder = GroupBuilder::Node(Object::new_dummy(), children_group_builders);
 
             // Convert back to a Group.
-            group_builder.build(true, &new_material)
+            group_builder.build(true, &new_material.into())
         } else {
             self
         }
@@ -248,9 +578,11 @@ impl Default for Object {
             transform: glm::identity(),
             transformation_inverse: glm::identity(),
             transformation_inverse_transpose: glm::identity(),
-            material: Material::default(),
+            transform_end: None,
+            material: SharedMaterial::default(),
             bounds: Bounds::default(),
             has_shadow: true,
+            ao_factor: 1.0,
             shape: Shape::None,
         }
     }
@@ -277,11 +609,30 @@ impl fmt::Debug for Object {
     }
 }
 
+impl Transformable for Object {
+    fn get_transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    fn set_transform(&mut self, m: &Matrix4) {
+        Object::set_transform(self, m);
+    }
+
+    // groups bake transforms into their children, so go through transform()
+    fn apply(&mut self, m: &Matrix4) {
+        let o = std::mem::replace(self, Object::new_dummy());
+        *self = o.transform(m);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::assert_eq_eps;
+    use crate::assert_eq_feps;
     use crate::shapes::sphere::*;
     use crate::transformation::*;
+    use glm::{identity, inverse};
 
     #[test]
     fn shape_instances_have_unique_ids() {
@@ -305,6 +656,110 @@ mod tests {
         assert_eq!(*s.get_transform(), t);
     }
 
+    #[test]
+    fn with_transform_then_apply_composes() {
+        let a = make_rotation_z(glm::half_pi());
+        let b = make_translation(2.0, 0.0, 0.0);
+        let mut s = test_shape().with_transform(a);
+        s.apply(&b);
+        assert_eq!(*s.get_transform(), b * a);
+        assert_eq!(
+            *s.get_transform(),
+            *test_shape().transform(&(b * a)).get_transform()
+        );
+    }
+
+    #[test]
+    fn applying_transform_to_group_moves_children() {
+        let mut g = Object::new_group(vec![sphere()]);
+        g.apply(&make_translation(5.0, 0.0, 0.0));
+        let r = Ray::new(point(5.0, 0.0, -5.0), vector_z());
+        assert_eq!(g.intersect(&r).len(), 2);
+    }
+
+    #[test]
+    fn intersect_uses_cached_inverse() {
+        let t =
+            make_translation(1.0, -2.0, 3.0) * make_rotation_y(0.7) * make_scaling(2.0, 0.5, 1.5);
+        let s = sphere().with_transformation(t);
+        let r = Ray::new(point(1.2, -2.1, -5.0), vector(0.0, 0.1, 1.0).normalize());
+        let expected = s.shape().intersect(&r.transform(inverse(&t)));
+        let xs = s.intersect(&r);
+        assert_eq!(xs.len(), expected.len());
+        for (i, e) in xs.iter().zip(expected.iter()) {
+            assert_eq_feps!(i.t, e.0);
+        }
+    }
+
+    #[test]
+    fn moving_sphere_is_hit_along_its_path() {
+        let s = sphere().with_motion(make_translation(4.0, 0.0, 0.0));
+        assert_eq!(s.bounds().min, point(-1.0, -1.0, -1.0));
+        assert_eq!(s.bounds().max, point(5.0, 1.0, 1.0));
+        // rays down z at each x only hit while the sphere is passing
+        let hit_xs = |time: math::F3D| -> Vec<math::F3D> {
+            (0..=8)
+                .map(|i| 0.25 + i as math::F3D * 0.5)
+                .filter(|x| {
+                    let r = Ray::new(point(*x, 0.0, -5.0), vector_z()).with_time(time);
+                    !s.intersect(&r).is_empty()
+                })
+                .collect()
+        };
+        assert_eq!(hit_xs(0.0), vec![0.25, 0.75]);
+        assert_eq!(hit_xs(0.5), vec![1.25, 1.75, 2.25, 2.75]);
+        assert_eq!(hit_xs(1.0), vec![3.25, 3.75, 4.25]);
+        let r = Ray::new(point(2.0, 0.0, -5.0), vector_z()).with_time(0.5);
+        let xs = s.intersect(&r);
+        assert_eq!(xs[0].t, 4.0);
+        assert_eq_eps!(
+            s.normal_at_time(point(2.0, 0.0, -1.0), None, 0.5),
+            vector(0.0, 0.0, -1.0)
+        );
+    }
+
+    #[test]
+    fn checked_cylinder_and_cone_constructors() {
+        assert_eq!(
+            Object::try_new_cylinder(2.0, 1.0, false).err(),
+            Some(ShapeError::InvertedExtent {
+                minimum: 2.0,
+                maximum: 1.0
+            })
+        );
+        assert!(Object::try_new_cone(0.0, math::F3D::NAN, true).is_err());
+        let c = Object::try_new_cylinder(1.0, 2.0, false).unwrap();
+        let r = Ray::new(point(0.0, 1.5, -5.0), vector_z());
+        assert_eq!(c.intersect(&r).len(), 2);
+        assert!(Object::try_new_cone(-1.0, 1.0, true).is_ok());
+    }
+
+    #[test]
+    fn try_set_transform_with_valid_transform() {
+        let mut s = test_shape();
+        let t = make_scaling(2.0, 2.0, 2.0);
+        assert!(s.try_set_transform(&t).is_ok());
+        assert_eq!(*s.get_transform(), t);
+        assert_eq!(*s.get_transformation_inverse(), make_scaling(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn try_set_transform_with_zero_scale_is_an_error() {
+        let mut s = test_shape();
+        let res = s.try_set_transform(&make_scaling(0.0, 1.0, 1.0));
+        assert!(matches!(res, Err(TransformError::Singular(_))));
+        // object keeps its previous transform
+        let ident: Matrix4 = identity();
+        assert_eq!(*s.get_transform(), ident);
+    }
+
+    #[test]
+    #[should_panic]
+    fn set_transform_with_singular_transform_panics() {
+        let mut s = test_shape();
+        s.set_transform(&make_scaling(0.0, 1.0, 1.0));
+    }
+
     /*
     #[test]
     fn intersect_scaled_shape_with_ray() {
@@ -325,6 +780,110 @@ mod tests {
         assert_eq!(xs.len(), 0);
     }
 
+    #[test]
+    fn instances_share_one_mesh() {
+        let corners = vec![
+            point(-1.0, 1.0, 0.0),
+            point(-1.0, -1.0, 0.0),
+            point(1.0, -1.0, 0.0),
+            point(1.0, 1.0, 0.0),
+        ];
+        let quad = crate::shapes::mesh::indexed_mesh(corners, vec![], &[0, 1, 2, 0, 2, 3]);
+        let shared = Arc::new(quad.shape().clone());
+
+        let before = CLONE_COUNT.with(|c| c.get());
+        let instances: Vec<Object> = (0..1000)
+            .map(|i| {
+                Object::instance_of(shared.clone(), &make_translation(0.0, 0.0, i as math::F3D))
+            })
+            .collect();
+        assert_eq!(CLONE_COUNT.with(|c| c.get()), before);
+        // one copy of the geometry, referenced by every instance
+        assert_eq!(Arc::strong_count(&shared), 1001);
+        assert!(instances.iter().all(|o| match o.shape() {
+            Shape::Instance(s) => Arc::ptr_eq(s, &shared),
+            _ => false,
+        }));
+
+        // hits and normals match the same mesh transformed on its own
+        let r = Ray::new(point(0.3, -0.2, -5.0), vector(0.0, 0.05, 1.0).normalize());
+        let moved = quad
+            .clone()
+            .with_transformation(make_translation(0.0, 0.0, 7.0));
+        let (xs, expected) = (instances[7].intersect(&r), moved.intersect(&r));
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].t, expected[0].t);
+        let p = r.position(xs[0].t);
+        assert_eq!(
+            instances[7].normal_at(p, Some(&xs[0])),
+            moved.normal_at(p, Some(&expected[0]))
+        );
+        assert_eq!(instances[7].bounds, moved.bounds);
+    }
+
+    #[test]
+    fn instances_of_instances_share_the_inner_shape() {
+        let corners = vec![
+            point(-1.0, 1.0, 0.0),
+            point(-1.0, -1.0, 0.0),
+            point(1.0, -1.0, 0.0),
+            point(1.0, 1.0, 0.0),
+        ];
+        let quad = crate::shapes::mesh::indexed_mesh(corners, vec![], &[0, 1, 2, 0, 2, 3]);
+        let shared = Arc::new(quad.shape().clone());
+        let inner = Object::instance_of(shared.clone(), &make_translation(0.0, 0.0, 2.0));
+        let outer = Object::instance_of(
+            Arc::new(inner.shape().clone()),
+            &make_translation(0.0, 0.0, 5.0),
+        );
+        match outer.shape() {
+            Shape::Instance(s) => assert!(Arc::ptr_eq(s, &shared)),
+            _ => panic!(),
+        }
+        // a nested instance that skipped instance_of still intersects
+        let nested = Object::new(None).with_shape(Shape::Instance(Arc::new(inner.shape().clone())));
+        let r = Ray::new(point(0.3, -0.2, -5.0), vector_z());
+        assert_eq!(outer.intersect(&r)[0].t, 10.0);
+        assert_eq!(nested.intersect(&r)[0].t, 5.0);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn valid_normals_pass_the_debug_check() {
+        let s = sphere();
+        let n = s.normal_at(point(0.0, 0.0, -1.0), None);
+        assert_eq!(n, vector(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "bad normal")]
+    fn zero_normals_fail_the_debug_check() {
+        // collinear corners, the face normal is 0 / 0
+        let t = crate::shapes::triangle::triangle(point_zero(), point_x(), point(2.0, 0.0, 0.0));
+        t.normal_at(point(0.5, 0.0, 0.0), None);
+    }
+
+    #[test]
+    fn mirrored_sphere_has_outward_normals() {
+        let mut s = sphere();
+        s.set_transform(&(make_translation(1.0, 0.0, 0.0) * make_scaling(-2.0, 1.0, 1.0)));
+        assert!(glm::determinant(s.get_transform()) < 0.0);
+
+        for r in [
+            Ray::new(point(1.5, 0.3, -5.0), vector_z()),
+            Ray::new(point(-5.0, 0.2, 0.1), vector_x()),
+            Ray::new(point(1.0, 5.0, 0.4), vector(0.0, -1.0, 0.0)),
+        ] {
+            let xs = s.intersect(&r);
+            let p = r.position(xs.hit().unwrap().t);
+            let n = s.normal_at(p, None);
+            // faces the ray, and away from the center
+            assert!(n.dot(&r.direction) < 0.0, "{:?}", n);
+            assert!(n.dot(&(p - point(1.0, 0.0, 0.0))) > 0.0, "{:?}", n);
+        }
+    }
+
     #[test]
     fn querying_shapes_bounding_box_in_its_parents_space() {
         let mut s = sphere();
@@ -333,4 +892,81 @@ mod tests {
         assert_eq!(b.min, point(0.5, -5.0, 1.0));
         assert_eq!(b.max, point(1.5, -1.0, 9.0));
     }
+
+    #[test]
+    fn uv_sphere_tessellation() {
+        let s = Object::new_uv_sphere(8, 8);
+        match s.shape() {
+            Shape::Group(g) => {
+                // 2 triangles per quad, except one per quad on the polar rings
+                assert_eq!(g.children().len(), 8 * 2 * 7);
+                assert!(g
+                    .children()
+                    .iter()
+                    .all(|c| matches!(c.shape(), Shape::SmoothTriangle(_))));
+            }
+            _ => panic!(),
+        }
+        let r = Ray::new(point(0.1, 0.1, -5.0), vector_z());
+        let xs = s.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert!((xs[0].t - 4.0).abs() < 0.1, "t = {}", xs[0].t);
+    }
+
+    #[test]
+    fn group_contains_direct_child() {
+        let s = sphere();
+        let g = Object::new_group(vec![s.clone(), sphere()]);
+        assert!(g.contains(&s));
+    }
+
+    #[test]
+    fn group_contains_nested_child() {
+        let s = sphere();
+        let inner = Object::new_group(vec![s.clone()]);
+        let outer = Object::new_group(vec![inner, sphere()]);
+        assert!(outer.contains(&s));
+    }
+
+    #[test]
+    fn group_does_not_contain_unrelated_object() {
+        let g = Object::new_group(vec![sphere(), sphere()]);
+        assert!(!g.contains(&sphere()));
+        assert!(!sphere().contains(&sphere()));
+    }
+
+    #[test]
+    fn csg_contains_operands() {
+        let s1 = sphere();
+        let s2 = sphere();
+        let g = Object::new_group(vec![s2.clone()]);
+        let c = Object::new_csg(CsgOp::Difference, &s1, &g);
+        assert!(c.contains(&s1));
+        assert!(c.contains(&s2));
+        assert!(!c.contains(&sphere()));
+    }
+
+    #[test]
+    fn bounding_sphere_of_unit_cube() {
+        let (center, radius) = crate::shapes::cube::cube().bounding_sphere();
+        assert_eq!(center, point_zero());
+        assert!(math::f_equals(radius, 3_f64.sqrt()));
+    }
+
+    #[test]
+    fn intersect_into_adds_sorted_hits_to_a_buffer() {
+        let near = sphere().with_transformation(make_translation(0.0, 0.0, -2.0));
+        let group = Object::new_group(vec![
+            sphere().with_transformation(make_translation(0.0, 0.0, 4.0)),
+            crate::shapes::cube::cube(),
+        ]);
+        let ray = Ray::new(point(0.0, 0.0, -10.0), vector_z());
+        let mut buf = Intersections::new();
+        group.intersect_into(&ray, &mut buf);
+        near.intersect_into(&ray, &mut buf);
+        let ts: Vec<_> = buf.iter().map(|i| i.t).collect();
+        assert_eq!(ts, vec![7.0, 9.0, 9.0, 11.0, 13.0, 15.0]);
+        let merged = Intersections::merge(group.intersect(&ray), near.intersect(&ray));
+        assert_eq!(buf.len(), merged.len());
+    }
 }
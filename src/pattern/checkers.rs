@@ -1,5 +1,5 @@
 use crate::color::Color;
-use crate::math::f_equals;
+use crate::math::{f_equals, F3D};
 use crate::matrix::Matrix4;
 use crate::pattern::{Pattern, TPattern};
 use crate::tuple::*;
@@ -8,31 +8,36 @@ use crate::tuple::*;
 pub struct CheckersPattern {
     a: Color,
     b: Color,
+    // squares per unit along each axis
+    frequency: F3D,
     transform: Matrix4,
 }
 
 pub fn checkers_pattern(a: Color, b: Color) -> CheckersPattern {
+    checkers_pattern_freq(a, b, 1.0)
+}
+
+pub fn checkers_pattern_freq(a: Color, b: Color, frequency: F3D) -> CheckersPattern {
     CheckersPattern {
         a,
         b,
+        frequency,
         transform: TPattern::default_transform(),
     }
 }
 
 impl Pattern for CheckersPattern {
-    fn get_transform(&self) -> Matrix4 {
-        self.transform
+    fn get_transform(&self) -> &Matrix4 {
+        &self.transform
     }
 
-    fn set_transform(&mut self, m: Matrix4) {
-        self.transform = m;
+    fn set_transform(&mut self, m: &Matrix4) {
+        self.transform = *m;
     }
 
     fn pattern_at(&self, point: &Point) -> Color {
-        if f_equals(
-            (point.x.floor() + point.y.floor() + point.z.floor()) % 2.0,
-            0.0,
-        ) {
+        let p = point * self.frequency;
+        if f_equals((p.x.floor() + p.y.floor() + p.z.floor()) % 2.0, 0.0) {
             self.a
         } else {
             self.b
@@ -45,11 +50,7 @@ mod tests {
     use super::*;
 
     fn setup() -> CheckersPattern {
-        CheckersPattern {
-            a: Color::white(),
-            b: Color::black(),
-            transform: TPattern::default_transform(),
-        }
+        checkers_pattern(Color::white(), Color::black())
     }
 
     #[test]
@@ -74,4 +75,12 @@ mod tests {
         assert_eq!(p.pattern_at(&point(0.0, 0.0, 0.99)), Color::white());
         assert_eq!(p.pattern_at(&point(0.0, 0.0, 1.01)), Color::black());
     }
+
+    #[test]
+    fn checkers_frequency_shrinks_squares() {
+        let p = checkers_pattern_freq(Color::white(), Color::black(), 2.0);
+        assert_eq!(p.pattern_at(&point(0.25, 0.0, 0.0)), Color::white());
+        assert_eq!(p.pattern_at(&point(0.6, 0.0, 0.0)), Color::black());
+        assert_eq!(setup().pattern_at(&point(0.6, 0.0, 0.0)), Color::white());
+    }
 }
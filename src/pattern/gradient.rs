@@ -19,12 +19,12 @@ pub fn gradient_pattern(a: Color, b: Color) -> GradientPattern {
 }
 
 impl Pattern for GradientPattern {
-    fn get_transform(&self) -> Matrix4 {
-        self.transform
+    fn get_transform(&self) -> &Matrix4 {
+        &self.transform
     }
 
-    fn set_transform(&mut self, m: Matrix4) {
-        self.transform = m;
+    fn set_transform(&mut self, m: &Matrix4) {
+        self.transform = *m;
     }
 
     fn pattern_at(&self, point: &Point) -> Color {
@@ -0,0 +1,57 @@
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::math::F3D;
+use std::sync::Arc;
+
+/**
+ * A canvas looked up by (u, v) texture coordinates, nearest pixel.
+ * u runs left to right and v bottom to top, so v = 1 is the first row.
+ * The canvas is shared, cloning the pattern doesn't copy the pixels.
+ */
+#[derive(Clone)]
+pub struct ImagePattern {
+    canvas: Arc<Canvas>,
+}
+
+impl ImagePattern {
+    pub fn new(canvas: Canvas) -> ImagePattern {
+        ImagePattern {
+            canvas: Arc::new(canvas),
+        }
+    }
+
+    pub fn uv_pattern_at(&self, u: F3D, v: F3D) -> Color {
+        let (width, height) = self.canvas.dimensions();
+        // each pixel covers an equal share of [0, 1], u = 1 lands in the last
+        let x = (u.clamp(0.0, 1.0) * width as F3D)
+            .floor()
+            .min((width - 1) as F3D) as usize;
+        let y = ((1.0 - v.clamp(0.0, 1.0)) * height as F3D)
+            .floor()
+            .min((height - 1) as F3D) as usize;
+        *self.canvas.pixel_at(x, y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_corners_map_to_uv_corners() {
+        let mut canvas = Canvas::new(3, 2, Some(Color::black()));
+        canvas.write_pixel(0, 0, Color::white());
+        canvas.write_pixel(2, 1, Color::new(1.0, 0.0, 0.0));
+        let image = ImagePattern::new(canvas);
+        assert_eq!(image.uv_pattern_at(0.0, 1.0), Color::white());
+        assert_eq!(image.uv_pattern_at(1.0, 0.0), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(image.uv_pattern_at(0.5, 1.0), Color::black());
+        // the first pixel spans the whole first third of u, and the top row
+        // the upper half of v
+        assert_eq!(image.uv_pattern_at(0.3, 0.6), Color::white());
+        assert_eq!(image.uv_pattern_at(0.34, 1.0), Color::black());
+        assert_eq!(image.uv_pattern_at(0.7, 0.4), Color::new(1.0, 0.0, 0.0));
+        // out of range coordinates stick to the edge
+        assert_eq!(image.uv_pattern_at(-0.5, 1.5), Color::white());
+    }
+}
@@ -1,25 +1,30 @@
 use crate::color::Color;
 use crate::matrix::Matrix4;
 use crate::object::Object;
+use crate::transformation::Transformable;
 use crate::tuple::Point;
 use glm;
 
 pub mod checkers;
 pub mod gradient;
+pub mod image;
+pub mod multi_stripe;
 pub mod ring;
+pub mod solid;
 pub mod stripe;
 pub mod texture_map;
+pub mod worley;
 
 pub trait Pattern {
-    fn get_transform(&self) -> Matrix4;
+    fn get_transform(&self) -> &Matrix4;
 
-    fn set_transform(&mut self, m: Matrix4);
+    fn set_transform(&mut self, m: &Matrix4);
 
     fn pattern_at(&self, point: &Point) -> Color;
 
     fn pattern_at_shape(&self, obj: &Object, point: &Point) -> Color {
         let local_point = obj.world_to_object(point);
-        let pattern_point = glm::inverse(&self.get_transform()) * local_point;
+        let pattern_point = glm::inverse(self.get_transform()) * local_point;
         self.pattern_at(&pattern_point)
     }
 }
@@ -29,9 +34,12 @@ pub enum TPattern {
     Test(TestPattern),
     Checkers(checkers::CheckersPattern),
     Gradient(gradient::GradientPattern),
+    MultiStripe(multi_stripe::MultiStripePattern),
     Ring(ring::RingPattern),
+    Solid(solid::SolidPattern),
     Stripe(stripe::StripePattern),
     TextureMap(texture_map::TextureMapPattern),
+    Worley(worley::WorleyPattern),
 }
 
 impl TPattern {
@@ -39,16 +47,53 @@ impl TPattern {
         glm::identity()
     }
 
-    pub fn pattern_at_shape(&self, object: &Object, point: &Point) -> Color {
+    fn as_pattern(&self) -> &dyn Pattern {
+        match self {
+            TPattern::Test(tp) => tp,
+            TPattern::Checkers(cp) => cp,
+            TPattern::Gradient(gp) => gp,
+            TPattern::MultiStripe(mp) => mp,
+            TPattern::Ring(rp) => rp,
+            TPattern::Solid(sp) => sp,
+            TPattern::Stripe(sp) => sp,
+            TPattern::TextureMap(tm) => tm,
+            TPattern::Worley(wp) => wp,
+        }
+    }
+
+    fn as_pattern_mut(&mut self) -> &mut dyn Pattern {
         match self {
-            TPattern::Test(tp) => tp.pattern_at_shape(object, point),
-            TPattern::Checkers(cp) => cp.pattern_at_shape(object, point),
-            TPattern::Gradient(gp) => gp.pattern_at_shape(object, point),
-            TPattern::Ring(rp) => rp.pattern_at_shape(object, point),
-            TPattern::Stripe(sp) => sp.pattern_at_shape(object, point),
-            TPattern::TextureMap(tm) => tm.pattern_at_shape(object, point),
+            TPattern::Test(tp) => tp,
+            TPattern::Checkers(cp) => cp,
+            TPattern::Gradient(gp) => gp,
+            TPattern::MultiStripe(mp) => mp,
+            TPattern::Ring(rp) => rp,
+            TPattern::Solid(sp) => sp,
+            TPattern::Stripe(sp) => sp,
+            TPattern::TextureMap(tm) => tm,
+            TPattern::Worley(wp) => wp,
         }
     }
+
+    pub fn pattern_at_shape(&self, object: &Object, point: &Point) -> Color {
+        self.as_pattern().pattern_at_shape(object, point)
+    }
+
+    // sample at a point not attached to any object (only the pattern transform applies)
+    pub fn pattern_at_point(&self, point: &Point) -> Color {
+        let p = self.as_pattern();
+        p.pattern_at(&(glm::inverse(p.get_transform()) * point))
+    }
+}
+
+impl Transformable for TPattern {
+    fn get_transform(&self) -> &Matrix4 {
+        self.as_pattern().get_transform()
+    }
+
+    fn set_transform(&mut self, m: &Matrix4) {
+        self.as_pattern_mut().set_transform(m);
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -57,12 +102,12 @@ pub struct TestPattern {
 }
 
 impl Pattern for TestPattern {
-    fn get_transform(&self) -> Matrix4 {
-        self.transform
+    fn get_transform(&self) -> &Matrix4 {
+        &self.transform
     }
 
-    fn set_transform(&mut self, m: Matrix4) {
-        self.transform = m;
+    fn set_transform(&mut self, m: &Matrix4) {
+        self.transform = *m;
     }
 
     fn pattern_at(&self, point: &Point) -> Color {
@@ -92,8 +137,8 @@ mod tests {
     fn assign_pattern_transformation() {
         let mut tp = setup();
         let t = make_translation(1.0, 0.0, 0.0);
-        tp.set_transform(t);
-        assert_eq!(tp.get_transform(), t);
+        tp.set_transform(&t);
+        assert_eq!(*tp.get_transform(), t);
     }
 
     #[test]
@@ -123,4 +168,41 @@ mod tests {
         let c = pattern.pattern_at_shape(&object, &point(2.5, 3.0, 3.5));
         assert_eq!(c, Color::new(0.75, 0.5, 0.25));
     }
+
+    #[test]
+    fn translated_pattern_shifts_with_its_transform() {
+        // the transform maps pattern space to object space, so stripes move +x
+        let mut pattern = stripe::stripe_pattern(Color::white(), Color::black());
+        pattern.set_transform(&make_translation(0.5, 0.0, 0.0));
+        let object = sphere();
+        assert_eq!(
+            pattern.pattern_at_shape(&object, &point(0.6, 0.0, 0.0)),
+            Color::white()
+        );
+        assert_eq!(
+            pattern.pattern_at_shape(&object, &point(1.4, 0.0, 0.0)),
+            Color::white()
+        );
+        assert_eq!(
+            pattern.pattern_at_shape(&object, &point(0.4, 0.0, 0.0)),
+            Color::black()
+        );
+        assert_eq!(
+            pattern.pattern_at_shape(&object, &point(1.6, 0.0, 0.0)),
+            Color::black()
+        );
+    }
+
+    #[test]
+    fn applying_transform_to_tpattern() {
+        let a = make_scaling(2.0, 2.0, 2.0);
+        let b = make_translation(1.0, 0.0, 0.0);
+        let mut p = TPattern::Test(setup()).with_transform(a);
+        p.apply(&b);
+        assert_eq!(*p.get_transform(), b * a);
+        assert_eq!(
+            p.pattern_at_point(&point(3.0, 2.0, 0.0)),
+            Color::new(1.0, 1.0, 0.0)
+        );
+    }
 }
@@ -0,0 +1,66 @@
+use crate::color::Color;
+use crate::matrix::Matrix4;
+use crate::pattern::{Pattern, TPattern};
+use crate::tuple::*;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MultiStripePattern {
+    colors: Vec<Color>,
+    transform: Matrix4,
+}
+
+pub fn multi_stripe_pattern(colors: Vec<Color>) -> MultiStripePattern {
+    assert!(
+        !colors.is_empty(),
+        "multi stripe pattern needs at least one color"
+    );
+    MultiStripePattern {
+        colors,
+        transform: TPattern::default_transform(),
+    }
+}
+
+impl Pattern for MultiStripePattern {
+    fn get_transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    fn set_transform(&mut self, m: &Matrix4) {
+        self.transform = *m;
+    }
+
+    fn pattern_at(&self, point: &Point) -> Color {
+        // rem_euclid keeps negative x cycling in the same direction
+        let i = (point.x.floor() as i64).rem_euclid(self.colors.len() as i64);
+        self.colors[i as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> MultiStripePattern {
+        multi_stripe_pattern(vec![
+            Color::new(1.0, 0.0, 0.0),
+            Color::new(0.0, 1.0, 0.0),
+            Color::new(0.0, 0.0, 1.0),
+        ])
+    }
+
+    #[test]
+    fn multi_stripe_cycles_colors_in_x() {
+        let p = setup();
+        assert_eq!(p.pattern_at(&point(0.5, 0.0, 0.0)), p.colors[0]);
+        assert_eq!(p.pattern_at(&point(1.5, 0.0, 0.0)), p.colors[1]);
+        assert_eq!(p.pattern_at(&point(2.5, 0.0, 0.0)), p.colors[2]);
+        assert_eq!(p.pattern_at(&point(3.5, 0.0, 0.0)), p.colors[0]);
+    }
+
+    #[test]
+    fn multi_stripe_wraps_for_negative_x() {
+        let p = setup();
+        assert_eq!(p.pattern_at(&point(-0.5, 0.0, 0.0)), p.colors[2]);
+        assert_eq!(p.pattern_at(&point(-2.5, 0.0, 0.0)), p.colors[0]);
+    }
+}
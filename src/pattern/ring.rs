@@ -20,12 +20,12 @@ pub fn ring_pattern(a: Color, b: Color) -> RingPattern {
 }
 
 impl Pattern for RingPattern {
-    fn get_transform(&self) -> Matrix4 {
-        self.transform
+    fn get_transform(&self) -> &Matrix4 {
+        &self.transform
     }
 
-    fn set_transform(&mut self, m: Matrix4) {
-        self.transform = m;
+    fn set_transform(&mut self, m: &Matrix4) {
+        self.transform = *m;
     }
 
     fn pattern_at(&self, point: &Point) -> Color {
@@ -0,0 +1,56 @@
+use crate::color::Color;
+use crate::matrix::Matrix4;
+use crate::object::Object;
+use crate::pattern::{Pattern, TPattern};
+use crate::tuple::*;
+
+// the same color everywhere, what a material without a pattern looks like
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SolidPattern {
+    pub color: Color,
+    transform: Matrix4,
+}
+
+pub fn solid_pattern(color: Color) -> SolidPattern {
+    SolidPattern {
+        color,
+        transform: TPattern::default_transform(),
+    }
+}
+
+impl Pattern for SolidPattern {
+    fn get_transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    fn set_transform(&mut self, m: &Matrix4) {
+        self.transform = *m;
+    }
+
+    fn pattern_at(&self, _point: &Point) -> Color {
+        self.color
+    }
+
+    // no point transforming a point nothing depends on
+    fn pattern_at_shape(&self, _obj: &Object, _point: &Point) -> Color {
+        self.color
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solid_pattern_is_constant() {
+        let c = Color::new(0.2, 0.4, 0.6);
+        let pattern = solid_pattern(c);
+        for p in [
+            point_zero(),
+            point(1.5, -3.0, 0.25),
+            point(-100.0, 7.0, 42.0),
+        ] {
+            assert_eq!(pattern.pattern_at(&p), c);
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use crate::color::Color;
-use crate::math::f_equals;
+use crate::math::{f_equals, F3D};
 use crate::matrix::Matrix4;
 use crate::pattern::{Pattern, TPattern};
 use crate::tuple::*;
@@ -8,6 +8,8 @@ use crate::tuple::*;
 pub struct StripePattern {
     a: Color,
     b: Color,
+    // half width of the blend across each stripe edge, 0 for hard edges
+    softness: F3D,
     transform: Matrix4,
 }
 
@@ -15,24 +17,47 @@ pub fn stripe_pattern(a: Color, b: Color) -> StripePattern {
     StripePattern {
         a,
         b,
+        softness: 0.0,
         transform: TPattern::default_transform(),
     }
 }
 
+impl StripePattern {
+    // blend linearly over x +/- softness around each edge (0 -> 0.5)
+    pub fn with_softness(mut self, softness: F3D) -> Self {
+        self.softness = softness.clamp(0.0, 0.5);
+        self
+    }
+
+    fn hard_stripe_at(&self, x: F3D) -> Color {
+        if f_equals(x.floor() % 2.0, 0.0) {
+            self.a
+        } else {
+            self.b
+        }
+    }
+}
+
 impl Pattern for StripePattern {
-    fn get_transform(&self) -> Matrix4 {
-        self.transform
+    fn get_transform(&self) -> &Matrix4 {
+        &self.transform
     }
 
-    fn set_transform(&mut self, m: Matrix4) {
-        self.transform = m;
+    fn set_transform(&mut self, m: &Matrix4) {
+        self.transform = *m;
     }
 
     fn pattern_at(&self, point: &Point) -> Color {
-        if f_equals(point.x.floor() % 2.0, 0.0) {
-            self.a
+        let edge = point.x.round();
+        let d = point.x - edge;
+        if d.abs() >= self.softness {
+            self.hard_stripe_at(point.x)
         } else {
-            self.b
+            Color::lerp(
+                self.hard_stripe_at(edge - 0.5),
+                self.hard_stripe_at(edge + 0.5),
+                (d + self.softness) / (2.0 * self.softness),
+            )
         }
     }
 }
@@ -76,4 +101,20 @@ mod tests {
         assert_eq!(p.pattern_at(&point(-1.0, 0.0, 0.0)), Color::black());
         assert_eq!(p.pattern_at(&point(-1.1, 0.0, 0.0)), Color::white());
     }
+
+    #[test]
+    fn soft_stripes_blend_at_edges() {
+        let p = setup().with_softness(0.25);
+        let mid = Color::new(0.5, 0.5, 0.5);
+        assert_eq!(p.pattern_at(&point_x()), mid);
+        assert_eq!(p.pattern_at(&point_zero()), mid);
+        assert_eq!(p.pattern_at(&point(0.5, 0.0, 0.0)), Color::white());
+        assert_eq!(p.pattern_at(&point(1.5, 0.0, 0.0)), Color::black());
+        assert_eq!(p.pattern_at(&point(-0.5, 0.0, 0.0)), Color::black());
+        // a quarter of the way into the band
+        assert_eq!(
+            p.pattern_at(&point(0.875, 0.0, 0.0)),
+            Color::new(0.75, 0.75, 0.75)
+        );
+    }
 }
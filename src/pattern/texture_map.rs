@@ -1,7 +1,9 @@
 use crate::color::Color;
 use crate::math::*;
 use crate::matrix::Matrix4;
-use crate::pattern::{Pattern, TPattern};
+use crate::object::Object;
+use crate::pattern::Pattern;
+use crate::shapes::shape::Shape;
 use crate::tuple::*;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -12,6 +14,12 @@ pub enum UVPattern {
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum UVMap {
     Spherical,
+    // xz plane, for floors
+    Planar,
+    // around the y axis, for labels on cylinders and cones
+    Cylindrical,
+    // cross unfold over the unit cube, what cube objects always use
+    Cube,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -63,26 +71,43 @@ impl TextureMapPattern {
     pub fn uv_map_point(&self, p: &Point) -> (F3D, F3D) {
         match self.uv_map {
             UVMap::Spherical => spherical_map(p),
-            _ => panic!(),
+            UVMap::Planar => planar_map(p),
+            UVMap::Cylindrical => cylindrical_map(p),
+            UVMap::Cube => cube_map(p),
+        }
+    }
+
+    fn uv_pattern_at(&self, u: F3D, v: F3D) -> Color {
+        match &self.uv_pattern {
+            UVPattern::Checkers(c) => c.uv_pattern_at(&point(u, v, 0.0)),
         }
     }
 }
 
 impl Pattern for TextureMapPattern {
-    fn get_transform(&self) -> Matrix4 {
-        TPattern::default_transform()
+    fn get_transform(&self) -> &Matrix4 {
+        &self.transform
     }
 
-    fn set_transform(&mut self, m: Matrix4) {
-        self.transform = m;
+    fn set_transform(&mut self, m: &Matrix4) {
+        self.transform = *m;
     }
 
     fn pattern_at(&self, p: &Point) -> Color {
         let (u, v) = self.uv_map_point(p);
+        self.uv_pattern_at(u, v)
+    }
 
-        match &self.uv_pattern {
-            UVPattern::Checkers(c) => c.uv_pattern_at(&point(u, v, 0.0)),
-            _ => panic!(),
+    // other maps leave seams where cube faces meet, so cubes get the cube layout
+    fn pattern_at_shape(&self, obj: &Object, point: &Point) -> Color {
+        let local_point = obj.world_to_object(point);
+        let p = glm::inverse(self.get_transform()) * local_point;
+        match obj.shape() {
+            Shape::Cube() => {
+                let (u, v) = cube_map(&p);
+                self.uv_pattern_at(u, v)
+            }
+            _ => self.pattern_at(&p),
         }
     }
 }
@@ -122,4 +147,41 @@ mod tests {
             assert_eq!(pattern.pattern_at(&c.0), c.1);
         }
     }
+
+    #[test]
+    fn texture_map_selects_uv_map() {
+        let checkers = UVPattern::Checkers(uv_checkers());
+        let around = TextureMapPattern::new(checkers, UVMap::Cylindrical);
+        // a quarter turn around the cylinder crosses into the next check
+        assert_eq!(around.pattern_at(&point(0.0, 0.25, -1.0)), Color::black());
+        assert_eq!(around.pattern_at(&point(1.0, 0.25, 0.0)), Color::black());
+        assert_eq!(around.pattern_at(&point(0.0, 0.25, 1.0)), Color::white());
+        assert_eq!(around.pattern_at(&point(0.0, 0.75, 1.0)), Color::black());
+
+        let cross = TextureMapPattern::new(checkers, UVMap::Cube);
+        let (u, v) = cube_map(&point(0.9, 0.2, 1.0));
+        assert_eq!(
+            cross.pattern_at(&point(0.9, 0.2, 1.0)),
+            uv_checkers().uv_pattern_at(&point(u, v, 0.0))
+        );
+
+        let flat = TextureMapPattern::new(checkers, UVMap::Planar);
+        assert_eq!(flat.pattern_at(&point(0.25, 5.0, 0.25)), Color::black());
+        assert_eq!(flat.pattern_at(&point(0.75, 5.0, 0.25)), Color::white());
+    }
+
+    #[test]
+    fn cubes_use_the_cube_layout() {
+        let checkers = UVPattern::Checkers(uv_checkers());
+        let spherical = TextureMapPattern::new(checkers, UVMap::Spherical);
+        let cross = TextureMapPattern::new(checkers, UVMap::Cube);
+        let cube = crate::shapes::cube::cube();
+        for p in [
+            point(0.9, 0.2, 1.0),
+            point(1.0, -0.7, 0.3),
+            point(-0.2, 1.0, -0.6),
+        ] {
+            assert_eq!(spherical.pattern_at_shape(&cube, &p), cross.pattern_at(&p));
+        }
+    }
 }
@@ -0,0 +1,119 @@
+use crate::color::Color;
+use crate::math::F3D;
+use crate::matrix::Matrix4;
+use crate::pattern::{Pattern, TPattern};
+use crate::tuple::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/**
+ * Cellular (Worley) noise: a at the seed points fading to b along the
+ * borders between cells, using F2 - F1 of the distances to the two
+ * nearest seeds
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorleyPattern {
+    pub points: Vec<Point>,
+    a: Color,
+    b: Color,
+    transform: Matrix4,
+}
+
+impl WorleyPattern {
+    pub fn new(points: Vec<Point>, a: Color, b: Color) -> WorleyPattern {
+        WorleyPattern {
+            points,
+            a,
+            b,
+            transform: TPattern::default_transform(),
+        }
+    }
+}
+
+// n seeds scattered over the unit cube, the same every time for a given seed
+pub fn worley_pattern(n: usize, seed: u64, a: Color, b: Color) -> WorleyPattern {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let points = (0..n)
+        .map(|_| point(rng.gen(), rng.gen(), rng.gen()))
+        .collect();
+    WorleyPattern::new(points, a, b)
+}
+
+impl Pattern for WorleyPattern {
+    fn get_transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    fn set_transform(&mut self, m: &Matrix4) {
+        self.transform = *m;
+    }
+
+    fn pattern_at(&self, p: &Point) -> Color {
+        let (mut f1, mut f2) = (F3D::INFINITY, F3D::INFINITY);
+        for seed in &self.points {
+            let d = glm::distance(seed, p);
+            if d < f1 {
+                f2 = f1;
+                f1 = d;
+            } else if d < f2 {
+                f2 = d;
+            }
+        }
+        if f2.is_infinite() || f2 <= 0.0 {
+            // fewer than two seeds, no borders, or sitting on a repeated seed
+            return self.a;
+        }
+        // 1 on a seed, 0 on the border between two cells
+        let t = ((f2 - f1) / f2).clamp(0.0, 1.0);
+        Color::lerp(self.b, self.a, t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> WorleyPattern {
+        WorleyPattern::new(
+            vec![point_zero(), point(2.0, 0.0, 0.0)],
+            Color::white(),
+            Color::black(),
+        )
+    }
+
+    #[test]
+    fn seed_points_are_color_a() {
+        let p = setup();
+        assert_eq!(p.pattern_at(&point_zero()), Color::white());
+        assert_eq!(p.pattern_at(&point(2.0, 0.0, 0.0)), Color::white());
+    }
+
+    #[test]
+    fn cell_border_is_color_b() {
+        let p = setup();
+        assert_eq!(p.pattern_at(&point(1.0, 0.0, 0.0)), Color::black());
+        // a quarter of the way between seeds: f1 = 0.5, f2 = 1.5
+        let c = p.pattern_at(&point(0.5, 0.0, 0.0));
+        assert_eq!(c, Color::lerp(Color::black(), Color::white(), 2.0 / 3.0));
+    }
+
+    #[test]
+    fn seeded_constructor_is_reproducible() {
+        let a = worley_pattern(8, 3, Color::white(), Color::black());
+        let b = worley_pattern(8, 3, Color::white(), Color::black());
+        assert_eq!(a.points.len(), 8);
+        assert_eq!(a, b);
+        assert!(a.points.iter().all(|p| (0.0..1.0).contains(&p.x)));
+        assert_ne!(a, worley_pattern(8, 4, Color::white(), Color::black()));
+    }
+
+    #[test]
+    fn repeated_seeds_are_not_nan() {
+        let p = WorleyPattern::new(
+            vec![point_zero(), point_zero()],
+            Color::white(),
+            Color::black(),
+        );
+        assert_eq!(p.pattern_at(&point_zero()), Color::white());
+    }
+}
@@ -0,0 +1,212 @@
+/**
+ * Stanford PLY file parser (ASCII only)
+ *
+ * Vertices (with optional nx/ny/nz normals) and faces are loaded into the
+ * same ObjData an OBJ file gives, so groups, meshes and normalized_group
+ * work the same. Polygons are fan triangulated; any other elements are skipped.
+ */
+use crate::obj_file::ObjData;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use tobj::{Mesh, Model};
+
+fn invalid(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+// an element declared in the header, with its property names in order
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<String>,
+}
+
+fn parse_header<'a>(lines: &mut impl Iterator<Item = &'a str>) -> Result<Vec<Element>, Error> {
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err(invalid("not a ply file".to_string()));
+    }
+    let mut elements: Vec<Element> = vec![];
+
+    for line in lines.by_ref() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", "ascii", _] => (),
+            ["format", f, _] => return Err(invalid(format!("unsupported ply format {}", f))),
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| invalid(format!("bad element count {}", count)))?,
+                properties: vec![],
+            }),
+            ["property", .., name] => match elements.last_mut() {
+                Some(e) => e.properties.push(name.to_string()),
+                None => return Err(invalid("property before any element".to_string())),
+            },
+            ["end_header"] => return Ok(elements),
+            _ => (), // comments, obj_info
+        }
+    }
+    Err(invalid("missing end_header".to_string()))
+}
+
+fn parse_numbers<T: std::str::FromStr>(line: &str) -> Result<Vec<T>, Error> {
+    line.split_whitespace()
+        .map(|w| w.parse().map_err(|_| invalid(format!("bad number {}", w))))
+        .collect()
+}
+
+// same as parse_ply_file with the PLY text in memory
+pub fn parse_ply_str(data: &str) -> Result<ObjData, Error> {
+    let mut lines = data.lines();
+    let elements = parse_header(&mut lines)?;
+    let mut mesh = Mesh::default();
+    let mut body = lines.filter(|l| !l.trim().is_empty());
+
+    for e in &elements {
+        let column = |name: &str| e.properties.iter().position(|p| p == name);
+        for _ in 0..e.count {
+            let line = body
+                .next()
+                .ok_or_else(|| invalid(format!("too few {} lines", e.name)))?;
+            match e.name.as_str() {
+                "vertex" => {
+                    let values: Vec<f32> = parse_numbers(line)?;
+                    let get = |name: &str| column(name).and_then(|i| values.get(i).copied());
+                    for axis in ["x", "y", "z"] {
+                        let v =
+                            get(axis).ok_or_else(|| invalid(format!("vertex without {}", axis)))?;
+                        mesh.positions.push(v);
+                    }
+                    if let (Some(nx), Some(ny), Some(nz)) = (get("nx"), get("ny"), get("nz")) {
+                        mesh.normals.extend([nx, ny, nz]);
+                    }
+                }
+                "face" => {
+                    // vertex count followed by that many indices
+                    let values: Vec<u32> = parse_numbers(line)?;
+                    let indices = values.get(1..).unwrap_or(&[]);
+                    if indices.len() < 3 || indices.len() != values[0] as usize {
+                        return Err(invalid(format!("bad face {}", line)));
+                    }
+                    for i in 1..indices.len() - 1 {
+                        mesh.indices
+                            .extend([indices[0], indices[i], indices[i + 1]]);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    let vertex_count = mesh.positions.len() / 3;
+    if let Some(i) = mesh.indices.iter().find(|&&i| i as usize >= vertex_count) {
+        return Err(invalid(format!("face index {} out of range", i)));
+    }
+    Ok(ObjData::new(vec![Model::new(
+        mesh,
+        "unnamed_object".to_string(),
+    )]))
+}
+
+pub fn parse_ply_file(filename: &str) -> Result<ObjData, Error> {
+    parse_ply_str(&fs::read_to_string(filename)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::shape::Shape;
+    use crate::tuple::*;
+
+    const QUAD: &str = "ply
+format ascii 1.0
+comment two triangles making a unit quad
+element vertex 4
+property float x
+property float y
+property float z
+element face 2
+property list uchar int vertex_indices
+end_header
+-1 1 0
+-1 0 0
+1 0 0
+1 1 0
+3 0 1 2
+3 0 2 3
+";
+
+    #[test]
+    fn parsing_a_two_triangle_quad() {
+        let data = parse_ply_str(QUAD).unwrap();
+        let group = data.default_group().unwrap();
+        let children = match group.shape() {
+            Shape::Group(g) => g.children(),
+            _ => panic!("expected a group"),
+        };
+        assert_eq!(children.len(), 2);
+        let corners = [
+            point(-1.0, 1.0, 0.0),
+            point(-1.0, 0.0, 0.0),
+            point(1.0, 0.0, 0.0),
+            point(1.0, 1.0, 0.0),
+        ];
+        for (child, [a, b, c]) in children.iter().zip([[0, 1, 2], [0, 2, 3]]) {
+            match child.shape() {
+                Shape::Triangle(t) => {
+                    assert_eq!(t.p1(), corners[a]);
+                    assert_eq!(t.p2(), corners[b]);
+                    assert_eq!(t.p3(), corners[c]);
+                }
+                _ => panic!("expected a triangle"),
+            }
+        }
+    }
+
+    #[test]
+    fn normals_give_smooth_triangles_and_quads_are_split() {
+        let data = parse_ply_str(
+            "ply
+format ascii 1.0
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+element face 1
+property list uchar int vertex_indices
+end_header
+-1 1 0 0 0 -1
+-1 0 0 0 0 -1
+1 0 0 0 0 -1
+1 1 0 0 0 -1
+4 0 1 2 3
+",
+        )
+        .unwrap();
+        match data.default_group().unwrap().shape() {
+            Shape::Group(g) => {
+                assert_eq!(g.children().len(), 2);
+                assert!(g
+                    .children()
+                    .iter()
+                    .all(|c| matches!(c.shape(), Shape::SmoothTriangle(_))));
+            }
+            _ => panic!("expected a group"),
+        }
+    }
+
+    #[test]
+    fn binary_and_broken_files_are_errors() {
+        let binary = QUAD.replace("ascii", "binary_little_endian");
+        assert_eq!(
+            parse_ply_str(&binary).err().unwrap().kind(),
+            ErrorKind::InvalidData
+        );
+        assert!(parse_ply_str(&QUAD.replace("3 0 2 3", "3 0 2 9")).is_err());
+        assert!(parse_ply_str("v 1 2 3").is_err());
+    }
+}
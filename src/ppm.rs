@@ -1,12 +1,54 @@
+use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
 use std::path::Path;
 
 use crate::canvas::Canvas;
+use crate::color::Color;
 use crate::math::F3D;
 
 const PPM_MAX_COLOR: usize = 255;
 
+// 4x4 Bayer threshold matrix for ordered dithering
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PpmError {
+    // first token wasn't P3
+    BadMagic(String),
+    // width, height or max color missing or not a number
+    BadHeader(String),
+    // a color value that isn't a number in 0..=max color
+    BadPixel(String),
+    // fewer color values than width * height * 3
+    MissingPixels { expected: usize, found: usize },
+    // more color values than width * height * 3
+    ExtraPixels { expected: usize, found: usize },
+}
+
+impl fmt::Display for PpmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PpmError::BadMagic(m) => write!(f, "not a P3 ppm (found {:?})", m),
+            PpmError::BadHeader(h) => write!(f, "bad ppm header value {:?}", h),
+            PpmError::BadPixel(p) => write!(f, "bad ppm color value {:?}", p),
+            PpmError::MissingPixels { expected, found } => {
+                write!(f, "expected {} color values, found {}", expected, found)
+            }
+            PpmError::ExtraPixels { expected, found } => {
+                write!(
+                    f,
+                    "expected {} color values, found {} more",
+                    expected,
+                    found - expected
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for PpmError {}
+
 /**
  * Scale rgb color from 0 -> 255
  */
@@ -22,22 +64,57 @@ fn scale_color(val: F3D) -> u16 {
     }
 }
 
+/**
+ * Offset of up to half a quantization step, varying over a 4x4 tile,
+ * so smooth gradients round to a mix of neighbouring levels
+ */
+fn bayer_offset(x: usize, y: usize) -> F3D {
+    let threshold = (BAYER_4X4[y % 4][x % 4] as F3D + 0.5) / 16.0;
+    (threshold - 0.5) / PPM_MAX_COLOR as F3D
+}
+
 pub fn canvas_to_string(c: &Canvas) -> String {
+    let mut buf = Vec::new();
+    // writing to a Vec can't fail
+    write_canvas(c, &mut buf).unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+pub fn canvas_to_string_dithered(c: &Canvas) -> String {
+    let mut buf = Vec::new();
+    write_canvas_with(c, &mut buf, bayer_offset).unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+/**
+ * Stream the canvas as PPM one row at a time
+ */
+pub fn write_canvas<W: Write>(c: &Canvas, out: &mut W) -> std::io::Result<()> {
+    write_canvas_with(c, out, |_, _| 0.0)
+}
+
+// offset(x, y) is added to each channel before quantizing
+fn write_canvas_with<W: Write, F: Fn(usize, usize) -> F3D>(
+    c: &Canvas,
+    out: &mut W,
+    offset: F,
+) -> std::io::Result<()> {
     let (w, h) = c.dimensions();
-    let header = String::from(format!("P3\n{} {}\n{}", w, h, PPM_MAX_COLOR));
-    let mut body_lines: Vec<String> = vec![];
+    writeln!(out, "P3\n{} {}\n{}", w, h, PPM_MAX_COLOR)?;
 
     // for each row
     for i in 0..h {
+        let mut body_lines: Vec<String> = vec![];
         let mut rgbs: Vec<String> = vec![];
         // for each column
         for j in 0..w {
             let color = c.pixel_at(j, i);
+            let d = offset(j, i);
             rgbs.push(String::from(format!(
                 "{} {} {}",
-                scale_color(color.red()),
-                scale_color(color.green()),
-                scale_color(color.blue()),
+                scale_color(color.red() + d),
+                scale_color(color.green() + d),
+                scale_color(color.blue() + d),
             )));
         }
         let mut line = rgbs.join(" ");
@@ -52,8 +129,69 @@ pub fn canvas_to_string(c: &Canvas) -> String {
             }
         }
         body_lines.push(line);
+
+        for l in body_lines {
+            writeln!(out, "{}", l)?;
+        }
+    }
+    Ok(())
+}
+
+/**
+ * Parse a plain (P3) ppm. Comments run from # to the end of a line and
+ * values may be wrapped over any number of lines.
+ */
+pub fn canvas_from_string(data: &str) -> Result<Canvas, PpmError> {
+    let mut tokens = data
+        .lines()
+        .map(|l| l.split('#').next().unwrap_or(""))
+        .flat_map(|l| l.split_whitespace());
+
+    match tokens.next() {
+        Some("P3") => (),
+        other => return Err(PpmError::BadMagic(other.unwrap_or("").to_string())),
+    }
+    let mut header = || {
+        let t = tokens.next().unwrap_or("");
+        t.parse::<usize>()
+            .map_err(|_| PpmError::BadHeader(t.to_string()))
+    };
+    let (width, height, max_color) = (header()?, header()?, header()?);
+    if max_color == 0 {
+        return Err(PpmError::BadHeader(max_color.to_string()));
+    }
+
+    // the header isn't trusted with an allocation, values only grow as they're read
+    let expected = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(3))
+        .ok_or_else(|| PpmError::BadHeader(format!("{} {}", width, height)))?;
+
+    let mut values = vec![];
+    for t in tokens {
+        match t.parse::<usize>() {
+            Ok(v) if v <= max_color => values.push(v as F3D / max_color as F3D),
+            _ => return Err(PpmError::BadPixel(t.to_string())),
+        }
+    }
+    if values.len() < expected {
+        return Err(PpmError::MissingPixels {
+            expected,
+            found: values.len(),
+        });
     }
-    format!("{}\n{}\n", header, body_lines.join("\n"))
+    if values.len() > expected {
+        return Err(PpmError::ExtraPixels {
+            expected,
+            found: values.len(),
+        });
+    }
+
+    let mut c = Canvas::new(width, height, None);
+    for (i, rgb) in values.chunks(3).enumerate() {
+        c.write_pixel(i % width, i / width, Color::new(rgb[0], rgb[1], rgb[2]));
+    }
+    Ok(c)
 }
 
 pub fn create_file_from_data(filename: &str, data: &String) -> std::io::Result<()> {
@@ -69,7 +207,6 @@ pub fn create_file_from_data(filename: &str, data: &String) -> std::io::Result<(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::color::Color;
 
     #[test]
     fn scale_color_when_over_255() {
@@ -134,4 +271,94 @@ mod tests {
         let ppm = c.to_ppm();
         assert_eq!(ppm.chars().last().unwrap(), '\n');
     }
+
+    #[test]
+    fn dithering_breaks_up_flat_bands() {
+        // a shallow gradient that spans less than one 8-bit step
+        let mut c = Canvas::new(16, 4, None);
+        for y in 0..4 {
+            for x in 0..16 {
+                let v = (100.0 + x as F3D * 0.02) / 255.0;
+                c.write_pixel(x, y, Color::new(v, v, v));
+            }
+        }
+        let changes = |ppm: String| {
+            let reds: Vec<u16> = canvas_from_string(&ppm)
+                .unwrap()
+                .pixels
+                .iter()
+                .map(|p| scale_color(p.red()))
+                .collect();
+            reds.chunks(16)
+                .map(|row| row.windows(2).filter(|w| w[0] != w[1]).count())
+                .sum::<usize>()
+        };
+        assert_eq!(changes(c.to_ppm()), 0);
+        assert!(changes(c.to_ppm_dithered()) > 0);
+        // values stay within one level of the undithered output
+        let plain = canvas_from_string(&c.to_ppm()).unwrap();
+        let dithered = canvas_from_string(&c.to_ppm_dithered()).unwrap();
+        for (a, b) in plain.pixels.iter().zip(dithered.pixels.iter()) {
+            assert!((scale_color(a.red()) as i32 - scale_color(b.red()) as i32).abs() <= 1);
+        }
+    }
+
+    #[test]
+    fn reading_back_a_written_ppm() {
+        let mut c = Canvas::new(10, 3, Some(Color::new(1.0, 0.8, 0.6)));
+        c.write_pixel(0, 0, Color::new(0.2, 0.4, 0.0));
+        c.write_pixel(9, 2, Color::black());
+        let ppm = c.to_ppm();
+        let read = canvas_from_string(&ppm).unwrap();
+        assert_eq!(read.dimensions(), (10, 3));
+        assert_eq!(read.pixels, c.pixels);
+        assert_eq!(read.to_ppm(), ppm);
+    }
+
+    #[test]
+    fn reading_ppm_with_comments_and_max_color() {
+        let ppm = "P3\n# made by hand\n2 1\n100\n100 50 0 # first\n0\n0 100\n";
+        let c = canvas_from_string(ppm).unwrap();
+        assert_eq!(*c.pixel_at(0, 0), Color::new(1.0, 0.5, 0.0));
+        assert_eq!(*c.pixel_at(1, 0), Color::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn reading_malformed_ppm() {
+        assert_eq!(
+            canvas_from_string("P6\n1 1\n255\n0 0 0\n").err(),
+            Some(PpmError::BadMagic(String::from("P6")))
+        );
+        assert_eq!(
+            canvas_from_string("P3\n1 x\n255\n0 0 0\n").err(),
+            Some(PpmError::BadHeader(String::from("x")))
+        );
+        assert_eq!(
+            canvas_from_string("P3\n2 1\n255\n0 0 0\n").err(),
+            Some(PpmError::MissingPixels {
+                expected: 6,
+                found: 3
+            })
+        );
+        assert_eq!(
+            canvas_from_string("P3\n1 1\n255\n0 0 0 255\n").err(),
+            Some(PpmError::ExtraPixels {
+                expected: 3,
+                found: 4
+            })
+        );
+        // sizes that overflow are a bad header, not a panic
+        assert_eq!(
+            canvas_from_string("P3\n4294967296 4294967296 255\n0 0 0\n").err(),
+            Some(PpmError::BadHeader(String::from("4294967296 4294967296")))
+        );
+        // nor does a huge but representable size allocate up front
+        assert_eq!(
+            canvas_from_string("P3\n100000000 100000000 255\n0 0 0\n").err(),
+            Some(PpmError::MissingPixels {
+                expected: 30_000_000_000_000_000,
+                found: 3
+            })
+        );
+    }
 }
@@ -1,3 +1,4 @@
+use crate::computations::Computations;
 use crate::math::F3D;
 use crate::matrix::Matrix4;
 use crate::tuple::*;
@@ -6,11 +7,28 @@ use crate::tuple::*;
 pub struct Ray {
     pub origin: Point,
     pub direction: Vector,
+    // when in the shutter interval the ray was cast, 0 -> 1, see Object::with_motion
+    pub time: F3D,
 }
 
 impl Ray {
+    // direction is used as given, so t counts multiples of its length
     pub fn new(origin: Point, direction: Vector) -> Ray {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            time: 0.0,
+        }
+    }
+
+    // unit direction, so t along the ray is a world space distance
+    pub fn new_normalized(origin: Point, direction: Vector) -> Ray {
+        Ray::new(origin, direction.normalize())
+    }
+
+    pub fn with_time(mut self, time: F3D) -> Ray {
+        self.time = time;
+        self
     }
 
     pub fn position(&self, t: F3D) -> Tuple {
@@ -18,14 +36,45 @@ impl Ray {
     }
 
     pub fn transform(&self, m: Matrix4) -> Ray {
-        Ray::new(m * self.origin, m * self.direction)
+        Ray::new(m * self.origin, m * self.direction).with_time(self.time)
+    }
+
+    // mirror ray leaving the hit point
+    pub fn reflected(comps: &Computations) -> Ray {
+        Ray::new(comps.over_point, comps.reflectv).with_time(comps.time)
+    }
+
+    /**
+     * Ray bent through the surface by Snell's law, None on total internal
+     * reflection
+     */
+    pub fn refracted(comps: &Computations) -> Option<Ray> {
+        // glm::refract_vec() should work here but does not
+        let eta = comps.n1 / comps.n2;
+        let ni = comps.eyev.dot(&comps.normalv);
+        let sin2_t = eta.powi(2) * (1.0 - ni.powi(2));
+
+        if sin2_t > 1.0 {
+            None
+        } else {
+            let k = (1.0 - sin2_t).sqrt();
+            let direction = (comps.normalv * (eta * ni - k)) - (comps.eyev * eta);
+            Some(Ray::new(comps.under_point, direction).with_time(comps.time))
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::computations::prepare_computations;
+    use crate::intersection::*;
+    use crate::materials::Material;
+    use crate::math::SQRT_2_DIV_2;
+    use crate::shapes::plane::plane;
+    use crate::shapes::sphere::glass_sphere;
     use crate::transformation::*;
+    use crate::{assert_eq_eps, assert_eq_feps};
 
     #[test]
     fn constructor_assigns_args() {
@@ -43,6 +92,14 @@ mod tests {
         assert_eq!(r.position(2.5), point(4.5, 3.0, 4.0));
     }
 
+    #[test]
+    fn normalized_ray_positions_are_distances() {
+        let r = Ray::new_normalized(point(1.0, 0.0, 0.0), vector(0.0, 3.0, 4.0));
+        assert_eq!(r.direction, vector(0.0, 0.6, 0.8));
+        assert_eq_eps!(r.position(5.0), point(1.0, 3.0, 4.0));
+        assert_eq_feps!((r.position(2.0) - r.origin).magnitude(), 2.0);
+    }
+
     #[test]
     fn translating_a_ray() {
         let r = Ray::new(point(1.0, 2.0, 3.0), vector(0.0, 1.0, 0.0));
@@ -60,4 +117,50 @@ mod tests {
         assert_eq!(r2.origin, point(2.0, 6.0, 12.0));
         assert_eq!(r2.direction, vector(0.0, 3.0, 0.0));
     }
+
+    #[test]
+    fn reflected_ray_starts_at_over_point() {
+        let p = plane();
+        let r = Ray::new(
+            point(0.0, 1.0, -1.0),
+            vector(0.0, -SQRT_2_DIV_2, SQRT_2_DIV_2),
+        );
+        let xs = Intersections::from_intersections(vec![Intersection::new(&p, 2_f64.sqrt())]);
+        let comps = prepare_computations(&xs[0], &r, &xs);
+        let reflected = Ray::reflected(&comps);
+        assert_eq!(reflected.origin, comps.over_point);
+        assert_eq_eps!(reflected.direction, vector(0.0, SQRT_2_DIV_2, SQRT_2_DIV_2));
+    }
+
+    #[test]
+    fn refracted_ray_follows_snells_law() {
+        let mut p = plane();
+        p.set_material(Material {
+            transparency: 1.0,
+            refractive_index: 1.5,
+            ..Material::default()
+        });
+        let r = Ray::new(
+            point(0.0, 1.0, -1.0),
+            vector(0.0, -SQRT_2_DIV_2, SQRT_2_DIV_2),
+        );
+        let xs = Intersections::from_intersections(vec![Intersection::new(&p, 2_f64.sqrt())]);
+        let comps = prepare_computations(&xs[0], &r, &xs);
+        let refracted = Ray::refracted(&comps).unwrap();
+        assert_eq!(refracted.origin, comps.under_point);
+        // sin(45deg) / 1.5
+        assert_eq_eps!(refracted.direction, vector(0.0, -0.88192, 0.47140));
+    }
+
+    #[test]
+    fn refracted_ray_under_total_internal_reflection() {
+        let s = glass_sphere();
+        let r = Ray::new(point(0.0, 0.0, SQRT_2_DIV_2), vector_y());
+        let xs = Intersections::from_intersections(vec![
+            Intersection::new(&s, -SQRT_2_DIV_2),
+            Intersection::new(&s, SQRT_2_DIV_2),
+        ]);
+        let comps = prepare_computations(&xs[1], &r, &xs);
+        assert!(Ray::refracted(&comps).is_none());
+    }
 }
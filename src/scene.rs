@@ -0,0 +1,366 @@
+/**
+ * JSON scene files (serde feature).
+ *
+ * Objects are saved as a shape description plus transform, material and id.
+ * Matrices are 16 element row-major arrays, points and vectors [x, y, z, w].
+ * Patterns, CSG and smooth triangles have no representation yet and are
+ * reported as SceneError::Unsupported instead of being silently dropped.
+ */
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::lights::Light;
+use crate::materials::Material;
+use crate::math::F3D;
+use crate::matrix::{checked_inverse, TransformError};
+use crate::object::Object;
+use crate::shapes::{cube::cube, shape::Shape, sphere::sphere, triangle::triangle};
+use crate::tuple::*;
+
+#[derive(Debug, PartialEq)]
+pub enum SceneError {
+    // something in the world that can't be written out, with what/where
+    Unsupported(String),
+    // malformed json or a missing field
+    Json(String),
+    // a saved transform that can't be inverted
+    Transform(TransformError),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Unsupported(what) => write!(f, "can't serialize {}", what),
+            SceneError::Json(e) => write!(f, "bad scene json: {}", e),
+            SceneError::Transform(e) => write!(f, "bad scene transform: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<serde_json::Error> for SceneError {
+    fn from(e: serde_json::Error) -> Self {
+        SceneError::Json(e.to_string())
+    }
+}
+
+impl From<TransformError> for SceneError {
+    fn from(e: TransformError) -> Self {
+        SceneError::Transform(e)
+    }
+}
+
+// #[serde(with = "crate::scene::matrix_serde")] for Matrix4 fields
+pub mod matrix_serde {
+    use crate::math::F3D;
+    use crate::matrix::Matrix4;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(m: &Matrix4, s: S) -> Result<S::Ok, S::Error> {
+        let mut rows = [0.0; 16];
+        for (i, v) in rows.iter_mut().enumerate() {
+            *v = m[(i / 4, i % 4)];
+        }
+        rows.serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Matrix4, D::Error> {
+        let rows = <[F3D; 16]>::deserialize(d)?;
+        Ok(Matrix4::from_row_slice(&rows))
+    }
+}
+
+// #[serde(with = "crate::scene::tuple_serde")] for Point / Vector fields
+pub mod tuple_serde {
+    use crate::math::F3D;
+    use crate::tuple::{tuple, Tuple};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(t: &Tuple, s: S) -> Result<S::Ok, S::Error> {
+        [t.x, t.y, t.z, t.w].serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Tuple, D::Error> {
+        let [x, y, z, w] = <[F3D; 4]>::deserialize(d)?;
+        Ok(tuple(x, y, z, w))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ShapeDesc {
+    Sphere,
+    // older scenes have no normal, meaning +y
+    Plane {
+        #[serde(default = "up", with = "tuple_serde")]
+        normal: Vector,
+    },
+    Cube,
+    Cylinder {
+        minimum: F3D,
+        maximum: F3D,
+        closed: bool,
+        radius: F3D,
+    },
+    Cone {
+        minimum: F3D,
+        maximum: F3D,
+        closed: bool,
+        radius: F3D,
+    },
+    Quad {
+        #[serde(with = "tuple_serde")]
+        origin: Point,
+        #[serde(with = "tuple_serde")]
+        u_edge: Vector,
+        #[serde(with = "tuple_serde")]
+        v_edge: Vector,
+    },
+    Triangle {
+        #[serde(with = "tuple_serde")]
+        p1: Point,
+        #[serde(with = "tuple_serde")]
+        p2: Point,
+        #[serde(with = "tuple_serde")]
+        p3: Point,
+    },
+    // children are stored with the group transform already applied
+    Group {
+        children: Vec<ObjectDesc>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ObjectDesc {
+    pub id: String,
+    pub shape: ShapeDesc,
+    #[serde(with = "matrix_serde")]
+    pub transform: crate::matrix::Matrix4,
+    pub material: Material,
+    pub has_shadow: bool,
+    // baked ambient occlusion, see World::bake_ao. Missing in older scenes
+    #[serde(default = "one")]
+    pub ao_factor: F3D,
+}
+
+impl ObjectDesc {
+    pub fn from_object(o: &Object) -> Result<ObjectDesc, SceneError> {
+        let m = o.get_material();
+        if m.pattern.is_some() || m.normal_perturb.is_some() {
+            return Err(SceneError::Unsupported(format!(
+                "pattern on {}",
+                o.get_id()
+            )));
+        }
+        if o.transform_end.is_some() {
+            return Err(SceneError::Unsupported(format!("motion on {}", o.get_id())));
+        }
+        let shape = match o.shape() {
+            Shape::Sphere() => ShapeDesc::Sphere,
+            Shape::Plane(p) => ShapeDesc::Plane { normal: p.normal() },
+            Shape::Cube() => ShapeDesc::Cube,
+            Shape::Cylinder(c) => ShapeDesc::Cylinder {
+                minimum: c.minimum,
+                maximum: c.maximum,
+                closed: c.closed,
+                radius: c.radius,
+            },
+            Shape::Cone(c) => ShapeDesc::Cone {
+                minimum: c.minimum,
+                maximum: c.maximum,
+                closed: c.closed,
+                radius: c.radius,
+            },
+            Shape::Quad(q) => ShapeDesc::Quad {
+                origin: q.origin,
+                u_edge: q.u_edge,
+                v_edge: q.v_edge,
+            },
+            Shape::Triangle(t) => ShapeDesc::Triangle {
+                p1: t.p1(),
+                p2: t.p2(),
+                p3: t.p3(),
+            },
+            Shape::Group(g) => ShapeDesc::Group {
+                children: g
+                    .children()
+                    .iter()
+                    .map(ObjectDesc::from_object)
+                    .collect::<Result<_, _>>()?,
+            },
+            _ => return Err(SceneError::Unsupported(o.get_id())),
+        };
+
+        Ok(ObjectDesc {
+            id: o.id.clone(),
+            shape,
+            transform: *o.get_transform(),
+            material: m.clone(),
+            has_shadow: o.has_shadow,
+            ao_factor: o.ao_factor,
+        })
+    }
+
+    pub fn to_object(&self) -> Result<Object, SceneError> {
+        let mut o = match &self.shape {
+            ShapeDesc::Sphere => sphere(),
+            ShapeDesc::Plane { normal } => Object::new_plane_facing(*normal),
+            ShapeDesc::Cube => cube(),
+            ShapeDesc::Cylinder {
+                minimum,
+                maximum,
+                closed,
+                radius,
+            } => Object::new_cylinder_r(*minimum, *maximum, *closed, *radius),
+            ShapeDesc::Cone {
+                minimum,
+                maximum,
+                closed,
+                radius,
+            } => Object::new_cone_r(*minimum, *maximum, *closed, *radius),
+            ShapeDesc::Quad {
+                origin,
+                u_edge,
+                v_edge,
+            } => Object::new_quad(*origin, *u_edge, *v_edge),
+            ShapeDesc::Triangle { p1, p2, p3 } => triangle(*p1, *p2, *p3),
+            ShapeDesc::Group { children } => Object::new_group(
+                children
+                    .iter()
+                    .map(ObjectDesc::to_object)
+                    .collect::<Result<_, _>>()?,
+            ),
+        };
+        // transform() rather than set_transform so groups push it into their children
+        checked_inverse(&self.transform)?;
+        o = o.transform(&self.transform);
+        o.id = self.id.clone();
+        o.set_material(self.material.clone());
+        o.has_shadow = self.has_shadow;
+        o.ao_factor = self.ao_factor;
+        Ok(o)
+    }
+}
+
+fn one() -> F3D {
+    1.0
+}
+
+fn up() -> Vector {
+    vector_y()
+}
+
+fn default_surface_offset() -> F3D {
+    crate::math::EPSILON
+}
+
+/**
+ * Serializable form of a World, see World::to_json / World::from_json
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WorldDesc {
+    pub lights: Vec<Light>,
+    pub objects: Vec<ObjectDesc>,
+    pub use_ambient_occlusion: bool,
+    pub occlusion_samples: usize,
+    pub occlusion_radius: F3D,
+    pub max_depth: u8,
+    // older scenes used max_depth for both
+    #[serde(default)]
+    pub max_refraction_depth: Option<u8>,
+    // missing in scenes saved before the option existed
+    #[serde(default)]
+    pub fade_reflections: bool,
+    #[serde(default = "default_surface_offset")]
+    pub surface_offset: F3D,
+    #[serde(default)]
+    pub shadow_bias: F3D,
+    pub background: crate::color::Color,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+    use crate::color::Color;
+    use crate::shapes::csg::CsgOp;
+    use crate::transformation::*;
+    use crate::world::World;
+
+    #[test]
+    fn round_tripping_world_and_camera_renders_the_same() {
+        let world = World::default();
+        let mut camera = Camera::new(11, 11, glm::half_pi());
+        camera.transform = view_transform(&point(0.0, 0.0, -5.0), &point_zero(), &vector_y());
+
+        let world2 = World::from_json(&world.to_json().unwrap()).unwrap();
+        let camera2: Camera =
+            serde_json::from_str(&serde_json::to_string(&camera).unwrap()).unwrap();
+
+        assert_eq!(camera2.transform, camera.transform);
+        assert_eq!(world2.shape_count(), 2);
+        assert!(world2.find_shape("sphere_s1").is_some());
+        assert_eq!(camera2.render(&world2).pixels, camera.render(&world).pixels);
+    }
+
+    #[test]
+    fn groups_and_transforms_survive_round_trip() {
+        let mut world = World::new(vec![]);
+        let g = Object::new_group(vec![
+            cube().transform(&make_translation(2.0, 0.0, 0.0)),
+            Object::new_cylinder_r(0.0, 1.0, true, 0.5),
+        ])
+        .transform(&make_scaling(2.0, 2.0, 2.0));
+        world.add_shape(g);
+        world.background = Color::new(0.1, 0.2, 0.3);
+
+        let json = world.to_json().unwrap();
+        let world2 = World::from_json(&json).unwrap();
+        assert_eq!(world2.background, world.background);
+        assert_eq!(world2.to_json().unwrap(), json);
+    }
+
+    #[test]
+    fn plane_normals_survive_round_trip() {
+        let ceiling = Object::new_plane_facing(vector(0.0, -1.0, 0.0));
+        let desc = ObjectDesc::from_object(&ceiling).unwrap();
+        let back = ObjectDesc::to_object(&desc).unwrap();
+        assert_eq!(back.shape().bounds(), ceiling.shape().bounds());
+        assert_eq!(back.normal_at(point_zero(), None), vector(0.0, -1.0, 0.0));
+        // scenes saved before planes had a normal
+        let old: ShapeDesc = serde_json::from_str("{\"type\": \"plane\"}").unwrap();
+        assert_eq!(old, ShapeDesc::Plane { normal: vector_y() });
+    }
+
+    #[test]
+    fn baked_ao_survives_round_trip() {
+        let mut world = World::new(vec![]);
+        let mut baked = sphere();
+        baked.set_ao_factor(0.4);
+        world.add_shape(baked);
+        world.add_shape(cube());
+
+        let world2 = World::from_json(&world.to_json().unwrap()).unwrap();
+        assert_eq!(world2.get_shape(0).ao_factor, 0.4);
+        assert_eq!(world2.get_shape(1).ao_factor, 1.0);
+
+        // scenes saved before ao_factor existed
+        let mut old: serde_json::Value =
+            serde_json::to_value(ObjectDesc::from_object(&sphere()).unwrap()).unwrap();
+        old.as_object_mut().unwrap().remove("ao_factor");
+        let desc: ObjectDesc = serde_json::from_value(old).unwrap();
+        assert_eq!(desc.ao_factor, 1.0);
+    }
+
+    #[test]
+    fn unsupported_shapes_are_errors() {
+        let mut world = World::new(vec![]);
+        world.add_shape(Object::new_csg(CsgOp::Union, &sphere(), &cube()));
+        assert!(matches!(world.to_json(), Err(SceneError::Unsupported(_))));
+        assert!(matches!(
+            World::from_json("{\"lights\": 3}"),
+            Err(SceneError::Json(_))
+        ));
+    }
+}
@@ -11,6 +11,8 @@ pub struct Cone {
     pub minimum: math::F3D,
     pub maximum: math::F3D,
     pub closed: bool,
+    // radius of the cone at y = +/-1
+    pub radius: math::F3D,
 }
 
 impl Cone {
@@ -27,22 +29,22 @@ impl Cone {
         }
         let t = (self.minimum - ray.origin.y) / ray.direction.y;
 
-        if check_cap(ray, t, self.minimum) {
+        if check_cap(ray, t, self.radius * self.minimum) {
             xs.push(t);
         }
         let t = (self.maximum - ray.origin.y) / ray.direction.y;
 
-        if check_cap(ray, t, self.maximum) {
+        if check_cap(ray, t, self.radius * self.maximum) {
             xs.push(t);
         }
         xs
     }
 }
 
-// helper for intersect_caps
-pub fn check_cap(ray: &Ray, t: math::F3D, y: math::F3D) -> bool {
+// helper for intersect_caps, radius is the cone's radius at the cap
+pub fn check_cap(ray: &Ray, t: math::F3D, radius: math::F3D) -> bool {
     let v = ray.origin + t * ray.direction;
-    (v.x.powi(2) + v.z.powi(2)) <= y.abs()
+    (v.x.powi(2) + v.z.powi(2)) <= radius.powi(2)
 }
 
 // constructor utilities
@@ -51,6 +53,7 @@ pub fn cone_with_id(id: Option<String>, min: math::F3D, max: math::F3D, closed:
         minimum: min,
         maximum: max,
         closed,
+        radius: 1.0,
     }))
 }
 
@@ -67,9 +70,10 @@ impl Cone {
         let mut xs = vec![];
         let ro = ray.origin;
         let rd = ray.direction;
-        let a = rd.x.powi(2) - rd.y.powi(2) + rd.z.powi(2);
-        let c = ro.x.powi(2) - ro.y.powi(2) + ro.z.powi(2);
-        let b = 2.0 * ro.x * rd.x - 2.0 * ro.y * rd.y + 2.0 * ro.z * rd.z;
+        let r2 = self.radius.powi(2);
+        let a = rd.x.powi(2) - r2 * rd.y.powi(2) + rd.z.powi(2);
+        let c = ro.x.powi(2) - r2 * ro.y.powi(2) + ro.z.powi(2);
+        let b = 2.0 * ro.x * rd.x - 2.0 * r2 * ro.y * rd.y + 2.0 * ro.z * rd.z;
 
         if math::f_equals(a, 0.0) {
             if math::f_equals(b, 0.0) {
@@ -102,12 +106,19 @@ impl Cone {
 
     pub fn local_normal_at(&self, point: &Point) -> Vector {
         let dist = point.x.powi(2) + point.z.powi(2);
-        if dist < 1.0 && point.y >= self.maximum - math::EPSILON {
+        let r2 = self.radius.powi(2);
+        if self.closed
+            && dist < r2 * self.maximum.powi(2)
+            && point.y >= self.maximum - math::EPSILON
+        {
             vector_y()
-        } else if dist < 1.0 && point.y <= self.minimum + math::EPSILON {
+        } else if self.closed
+            && dist < r2 * self.minimum.powi(2)
+            && point.y <= self.minimum + math::EPSILON
+        {
             vector(0.0, -1.0, 0.0)
         } else {
-            let y = (point.x.powi(2) + point.z.powi(2)).sqrt();
+            let y = dist.sqrt() * self.radius;
             if point.y > 0.0 {
                 vector(point.x, -y, point.z)
             } else {
@@ -125,7 +136,7 @@ impl Cone {
         } else {
             let a = self.minimum.abs();
             let b = self.maximum.abs();
-            let limit = a.max(b);
+            let limit = a.max(b) * self.radius;
 
             Bounds {
                 min: point(-limit, self.minimum, -limit),
@@ -216,4 +227,15 @@ mod tests {
             assert_eq!(n, t.1);
         }
     }
+
+    #[test]
+    fn wider_cone_is_hit_where_unit_cone_misses() {
+        let r = Ray::new(point(1.5, 1.0, -5.0), vector_z());
+        let c = cone(-2.0, 2.0, false);
+        assert!(c.intersect(&r).is_empty());
+
+        let c = Object::new_cone_r(-2.0, 2.0, false, 2.0);
+        assert_eq!(c.intersect(&r).len(), 2);
+        assert_eq!(c.bounds().max, point(4.0, 2.0, 4.0));
+    }
 }
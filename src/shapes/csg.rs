@@ -1,12 +1,14 @@
 use crate::bounds::*;
 use crate::intersection::*;
 use crate::math;
+use crate::matrix::Matrix4;
 use crate::object::Object;
 use crate::ray::Ray;
+use crate::shapes::shape::Shape;
 use crate::tuple::*;
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
 
-type ChildNode = Arc<RwLock<CsgNode>>;
+type ChildNode = Arc<CsgNode>;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum CsgOp {
@@ -24,16 +26,44 @@ pub enum CsgNode {
 impl CsgNode {
     pub fn is_object_in_tree(&self, obj: &Object) -> bool {
         match self {
-            CsgNode::Node(n) => {
-                let ll = n.left.read().unwrap();
-                let lr = n.right.read().unwrap();
-                ll.is_object_in_tree(obj) || lr.is_object_in_tree(obj)
-            }
+            CsgNode::Node(n) => n.left.is_object_in_tree(obj) || n.right.is_object_in_tree(obj),
             CsgNode::Leaf(o) => o == obj,
         }
     }
 
-    pub fn intersect(&self, ray: &Ray) -> Intersections {
+    // matches by id, descending into groups held by leaves
+    pub fn contains(&self, obj: &Object) -> bool {
+        match self {
+            CsgNode::Node(n) => n.contains(obj),
+            CsgNode::Leaf(o) => o.id == obj.id || o.contains(obj),
+        }
+    }
+
+    fn depth(&self) -> usize {
+        match self {
+            CsgNode::Node(n) => n.depth(),
+            CsgNode::Leaf(o) => match o.shape() {
+                Shape::Csg(c) => c.depth(),
+                _ => 0,
+            },
+        }
+    }
+
+    fn bounds(&self) -> Bounds {
+        match self {
+            CsgNode::Node(n) => n.bounds(),
+            CsgNode::Leaf(o) => o.bounds(),
+        }
+    }
+
+    fn transformed(&self, m: &Matrix4) -> CsgNode {
+        match self {
+            CsgNode::Node(n) => CsgNode::Node(n.transformed(m)),
+            CsgNode::Leaf(o) => CsgNode::Leaf(o.clone().transform(m)),
+        }
+    }
+
+    pub fn intersect(&self, ray: &Ray) -> Intersections<'_> {
         match self {
             CsgNode::Node(n) => n.intersect(ray),
             CsgNode::Leaf(o) => o.intersect(ray),
@@ -55,8 +85,30 @@ impl Csg {
         let right = CsgNode::Leaf(s2.clone());
         Csg {
             op,
-            left: Arc::new(RwLock::new(left)),
-            right: Arc::new(RwLock::new(right)),
+            left: Arc::new(left),
+            right: Arc::new(right),
+        }
+    }
+
+    pub fn contains(&self, obj: &Object) -> bool {
+        self.left.contains(obj) || self.right.contains(obj)
+    }
+
+    // number of csg levels down to the deepest leaf
+    pub fn depth(&self) -> usize {
+        1 + self.left.depth().max(self.right.depth())
+    }
+
+    /**
+     * Copy of the tree with m applied on top of every leaf's transform.
+     * Like groups, a csg bakes its transform into its operands so hits,
+     * normals and patterns on a leaf see the whole transform chain.
+     */
+    pub fn transformed(&self, m: &Matrix4) -> Csg {
+        Csg {
+            op: self.op.clone(),
+            left: Arc::new(self.left.transformed(m)),
+            right: Arc::new(self.right.transformed(m)),
         }
     }
 
@@ -69,14 +121,13 @@ impl Csg {
         }
     }
 
-    pub fn filter_intersections(&self, xs: &Intersections) -> Intersections {
+    pub fn filter_intersections<'a>(&self, xs: &Intersections<'a>) -> Intersections<'a> {
         let mut inl = false;
         let mut inr = false;
-        let l = self.left.read().unwrap();
 
         xs.iter()
             .filter_map(|is| {
-                let lhit = l.is_object_in_tree(&is.object);
+                let lhit = self.left.is_object_in_tree(is.object);
 
                 if lhit {
                     inl = !inl;
@@ -96,25 +147,26 @@ impl Csg {
         unreachable!()
     }
 
-    pub fn intersect(&self, ray: &Ray) -> Intersections {
-        let l = self.left.read().unwrap(); // CsgNode
-        let r = self.right.read().unwrap();
-
-        // combine, sort, & filter
-        let mut xs = l.intersect(ray);
-        xs.extend(&r.intersect(ray));
+    pub fn intersect(&self, ray: &Ray) -> Intersections<'_> {
+        // combine (both sides are sorted) & filter
+        let xs = Intersections::merge(self.left.intersect(ray), self.right.intersect(ray));
 
-        self.filter_intersections(&xs.sort_intersections())
+        self.filter_intersections(&xs)
     }
 
+    // both operands whatever the op, in the space their transforms lead to
     pub fn bounds(&self) -> Bounds {
-        Bounds::new(point(-1.0, -1.0, -1.0), point(1.0, 1.0, 1.0))
+        let mut b = self.left.bounds();
+        b.add_bounds(&self.right.bounds());
+        b
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::assert_eq_eps;
+    use crate::color::Color;
     use crate::shapes::{cube, shape, sphere};
     use crate::transformation::*;
 
@@ -126,13 +178,11 @@ mod tests {
         match o.shape() {
             shape::Shape::Csg(c) => {
                 assert_eq!(c.op, CsgOp::Union);
-                let l = c.left.read().unwrap();
-                match &*l {
+                match &*c.left {
                     CsgNode::Leaf(n) => assert_eq!(n, &s1),
                     _ => panic!(),
                 }
-                let r = c.right.read().unwrap();
-                match &*r {
+                match &*c.right {
                     CsgNode::Leaf(n) => assert_eq!(n, &s2),
                     _ => panic!(),
                 }
@@ -219,4 +269,73 @@ mod tests {
         assert_eq!(xs[1].t, 6.5);
         assert_eq!(*xs[1].object, s2);
     }
+
+    #[test]
+    fn union_of_many_is_balanced() {
+        let row = || {
+            (0..4)
+                .map(|i| {
+                    let mut s = sphere::sphere();
+                    s.set_transform(&make_translation(i as math::F3D * 3.0, 0.0, 0.0));
+                    s
+                })
+                .collect::<Vec<Object>>()
+        };
+        let s = row();
+        let nested = Object::new_csg(
+            CsgOp::Union,
+            &s[0],
+            &Object::new_csg(
+                CsgOp::Union,
+                &s[1],
+                &Object::new_csg(CsgOp::Union, &s[2], &s[3]),
+            ),
+        );
+        let many = Object::new_union_many(row());
+        let r = Ray::new(point(-5.0, 0.0, 0.0), vector_x());
+        assert_eq!(many.intersect(&r).len(), 8);
+        assert_eq!(many.intersect(&r).len(), nested.intersect(&r).len());
+
+        let depth = |o: &Object| match o.shape() {
+            shape::Shape::Csg(c) => c.depth(),
+            _ => 0,
+        };
+        assert_eq!(depth(&nested), 3);
+        assert_eq!(depth(&many), 2);
+        let mut five = row();
+        five.push(sphere::sphere());
+        assert_eq!(depth(&Object::new_union_many(five)), 3);
+    }
+
+    #[test]
+    fn pattern_on_operand_follows_csg_transform() {
+        use crate::computations::prepare_computations;
+        use crate::pattern::{stripe::stripe_pattern, TPattern};
+
+        let mut s1 = sphere::sphere();
+        s1.material.pattern = Some(TPattern::Stripe(stripe_pattern(
+            Color::white(),
+            Color::black(),
+        )));
+        let mut s2 = cube::cube();
+        s2.set_transform(&make_translation(0.0, 10.0, 0.0));
+        let mut c = Object::new_csg(CsgOp::Union, &s1, &s2);
+        c.set_transform(&make_translation(1.0, 0.0, 0.0));
+
+        // sphere local x = 0.5 is a white stripe, world x = 1.5 would be black
+        let r = Ray::new(point(1.5, 0.0, -5.0), vector_z());
+        let xs = c.intersect(&r);
+        let t = xs[0].t;
+        let comps = prepare_computations(&xs[0], &r, &xs);
+        let pattern = comps.object.get_material().pattern.as_ref().unwrap();
+        assert_eq!(
+            pattern.pattern_at_shape(comps.object, &comps.point),
+            Color::white()
+        );
+        assert_eq_eps!(comps.normalv, vector(0.5, 0.0, -(0.75_f64).sqrt()));
+
+        // setting the transform again replaces it rather than stacking
+        c.set_transform(&make_translation(1.0, 0.0, 0.0));
+        assert_eq!(c.intersect(&r)[0].t, t);
+    }
 }
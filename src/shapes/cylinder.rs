@@ -11,12 +11,13 @@ pub struct Cylinder {
     pub minimum: math::F3D,
     pub maximum: math::F3D,
     pub closed: bool,
+    pub radius: math::F3D,
 }
 
 // helper for intersect_caps
-pub fn check_cap(ray: &Ray, t: math::F3D) -> bool {
+pub fn check_cap(ray: &Ray, t: math::F3D, radius: math::F3D) -> bool {
     let v = ray.origin + t * ray.direction;
-    (v.x.powi(2) + v.z.powi(2)) <= 1.0
+    (v.x.powi(2) + v.z.powi(2)) <= radius.powi(2)
 }
 
 // constructor utilities
@@ -30,6 +31,7 @@ pub fn cylinder_with_id(
         minimum,
         maximum,
         closed,
+        radius: 1.0,
     }))
 }
 
@@ -55,12 +57,12 @@ impl Cylinder {
         }
         let t = (self.minimum - ray.origin.y) / ray.direction.y;
 
-        if check_cap(ray, t) {
+        if check_cap(ray, t, self.radius) {
             xs.push(t);
         }
         let t = (self.maximum - ray.origin.y) / ray.direction.y;
 
-        if check_cap(ray, t) {
+        if check_cap(ray, t, self.radius) {
             xs.push(t);
         }
         xs
@@ -74,7 +76,7 @@ impl Cylinder {
             return self.intersect_caps(ray);
         }
         let b = 2.0 * ray.origin.x * ray.direction.x + 2.0 * ray.origin.z * ray.direction.z;
-        let c = ray.origin.x.powi(2) + ray.origin.z.powi(2) - 1.0;
+        let c = ray.origin.x.powi(2) + ray.origin.z.powi(2) - self.radius.powi(2);
         let disc = b * b - 4.0 * a * c;
 
         if disc >= 0.0 {
@@ -99,19 +101,22 @@ impl Cylinder {
 
     pub fn local_normal_at(&self, point: &Point) -> Vector {
         let dist = point.x.powi(2) + point.z.powi(2);
-        if dist < 1.0 && point.y >= (self.maximum - math::EPSILON) {
+        let r2 = self.radius.powi(2);
+        // open ends have no caps, so the rim always gets the side normal
+        if self.closed && dist < r2 && point.y >= (self.maximum - math::EPSILON) {
             vector_y()
-        } else if dist < 1.0 && point.y <= (self.minimum + math::EPSILON) {
+        } else if self.closed && dist < r2 && point.y <= (self.minimum + math::EPSILON) {
             vector(0.0, -1.0, 0.0)
         } else {
-            vector(point.x, 0.0, point.z)
+            vector(point.x, 0.0, point.z) / self.radius
         }
     }
 
     pub fn bounds(&self) -> Bounds {
+        let r = self.radius;
         Bounds {
-            min: point(-1.0, self.minimum, -1.0),
-            max: point(1.0, self.maximum, 1.0),
+            min: point(-r, self.minimum, -r),
+            max: point(r, self.maximum, r),
         }
     }
 }
@@ -236,4 +241,41 @@ mod tests {
             assert_eq!(n, t.1);
         }
     }
+
+    #[test]
+    fn wider_cylinder_is_hit_where_unit_cylinder_misses() {
+        let r = Ray::new(point(1.5, 0.0, -5.0), vector_z());
+        assert!(default_cylinder().intersect(&r).is_empty());
+
+        let c = Object::new_cylinder_r(-1.0, 1.0, false, 2.0);
+        let xs = c.intersect(&r);
+        assert_eq!(xs.len(), 2);
+
+        let n = c.normal_at(point(0.0, 0.5, -2.0), None);
+        assert_eq!(n, vector(0.0, 0.0, -1.0));
+        assert_eq_feps!(n.magnitude(), 1.0);
+        assert_eq!(c.bounds().min, point(-2.0, -1.0, -2.0));
+        assert_eq!(c.bounds().max, point(2.0, 1.0, 2.0));
+    }
+
+    #[test]
+    fn open_cylinder_rim_has_radial_normal() {
+        if let Shape::Cylinder(c) = cylinder(1.0, 2.0, false).shape {
+            assert_eq!(c.local_normal_at(&point(1.0, 2.0, 0.0)), vector_x());
+            // just inside the rim would hit the cap branch on a closed cylinder
+            let n = c.local_normal_at(&point(0.9999, 2.0, 0.0));
+            assert_eq!(n.y, 0.0);
+            assert!(n.x > 0.0);
+        } else {
+            panic!("no cylinder shape");
+        }
+    }
+
+    #[test]
+    fn caps_of_wider_cylinder() {
+        let c = Object::new_cylinder_r(1.0, 2.0, true, 2.0);
+        let r = Ray::new(point(1.5, 3.0, 0.0), vector(0.0, -1.0, 0.0));
+        assert_eq!(c.intersect(&r).len(), 2);
+        assert_eq!(c.normal_at(point(1.5, 2.0, 0.0), None), vector_y());
+    }
 }
@@ -7,17 +7,23 @@
 use crate::{
     bounds::Bounds,
     intersection::Intersections,
-    materials::Material,
+    materials::SharedMaterial,
     matrix::Matrix4,
     object::Object,
     ray::Ray,
     shapes::shape::Shape,
+    stats::RenderStats,
     tuple::{Point, Vector},
 };
 //use serde::{Deserialize, Serialize};
 
 /* ---------------------------------------------------------------------------------------------- */
 
+/**
+ * bounds covers the children in group space and is owned by the group:
+ * it is rebuilt by every constructor and extended by add_child. The
+ * owning Object keeps its own world space copy, see Object::add_child.
+ */
 #[derive(Clone, Debug, PartialEq)]
 pub struct Group {
     bounds: Bounds,
@@ -33,15 +39,34 @@ impl Group {
         Self { children, bounds }
     }
 
-    pub fn intersects(&self, ray: &Ray) -> Intersections {
+    pub fn intersects(&self, ray: &Ray) -> Intersections<'_> {
+        self.intersects_with_stats(ray, None)
+    }
+
+    pub fn intersects_with_stats(
+        &self,
+        ray: &Ray,
+        stats: Option<&RenderStats>,
+    ) -> Intersections<'_> {
         let mut xs = Intersections::new();
+        self.intersect_into(ray, &mut xs, stats);
+        xs
+    }
+
+    // children add their hits straight into buf, which stays sorted
+    pub fn intersect_into<'a>(
+        &'a self,
+        ray: &Ray,
+        buf: &mut Intersections<'a>,
+        stats: Option<&RenderStats>,
+    ) {
         if self.bounds().intersects(ray) {
             for child in self.children() {
-                xs.extend(&child.intersect(ray));
+                child.intersect_into_with_stats(ray, buf, stats);
             }
+        } else if let Some(s) = stats {
+            s.record_bounding_box_skip();
         }
-        // sort results here
-        xs.sort_intersections()
     }
 
     pub fn normal_at(&self, _object_point: &Point) -> Vector {
@@ -52,10 +77,37 @@ impl Group {
         &self.children
     }
 
+    // all non-group, non-csg objects in the tree, depth first
+    pub fn leaves(&self) -> Vec<&Object> {
+        self.children().iter().flat_map(Object::leaves).collect()
+    }
+
+    // for edits that leave the children's bounds alone
+    pub(crate) fn children_mut(&mut self) -> &mut [Object] {
+        &mut self.children
+    }
+
+    // child is given in group space
+    pub fn add_child(&mut self, child: Object) {
+        self.bounds.add_bounds(&child.bounds);
+        self.children.push(child);
+    }
+
     pub fn bounds(&self) -> Bounds {
         self.bounds
     }
 
+    // bake m into every descendant in place, dropping empty subgroups
+    pub fn transform_children(&mut self, m: &Matrix4) {
+        let children = std::mem::take(&mut self.children);
+        self.children = children
+            .into_iter()
+            .filter(|c| !matches!(c.shape(), Shape::Group(g) if g.children.is_empty()))
+            .map(|c| c.transform(m))
+            .collect();
+        self.bounds = Group::mk_bounding_box(&self.children);
+    }
+
     fn partition(self) -> Self {
         let mut left_children = Vec::with_capacity(self.children.len());
         let mut right_children = Vec::with_capacity(self.children.len());
@@ -124,11 +176,16 @@ pub enum GroupBuilder {
 }
 
 impl GroupBuilder {
-    pub fn build(self, propagate_material: bool, material: &Material) -> Object {
+    pub fn build(self, propagate_material: bool, material: &SharedMaterial) -> Object {
         GroupBuilder::rec(self, &glm::identity(), propagate_material, material)
     }
 
-    fn rec(gb: Self, transform: &Matrix4, propagate_material: bool, material: &Material) -> Object {
+    fn rec(
+        gb: Self,
+        transform: &Matrix4,
+        propagate_material: bool,
+        material: &SharedMaterial,
+    ) -> Object {
         match gb {
             GroupBuilder::Leaf(o) => {
                 //println!("building leaf {:#?} with transform {}", o, transform);
@@ -199,7 +256,31 @@ mod tests {
     use crate::materials::Material;
     use crate::transformation::*;
     use crate::world::*;
+    use crate::{math::F3D, shapes::triangle::triangle};
     use crate::{shapes::cylinder::*, shapes::shape, shapes::sphere::*, tuple::*};
+    use std::collections::HashSet;
+
+    #[test]
+    fn adding_a_child_grows_group_bounds() {
+        let mut group = Object::new_group(vec![sphere()]);
+        let mut far = sphere();
+        far.set_transform(&make_translation(10.0, 0.0, 0.0));
+        group.add_child(far);
+        let expected = Bounds::new(point(-1.0, -1.0, -1.0), point(11.0, 1.0, 1.0));
+        match group.shape() {
+            Shape::Group(g) => assert_eq!(g.bounds(), expected),
+            _ => panic!(),
+        }
+        assert_eq!(group.bounds, expected);
+
+        // the group is hit where only the new child is
+        let r = Ray::new(point(10.0, 0.0, -5.0), vector_z());
+        assert_eq!(group.intersect(&r).len(), 2);
+
+        // world bounds follow the group transform
+        group.set_transform(&make_translation(0.0, 5.0, 0.0));
+        assert_eq!(group.bounds.max, point(11.0, 6.0, 1.0));
+    }
 
     #[test]
     fn intersecting_a_ray_with_an_empty_group() {
@@ -298,10 +379,7 @@ mod tests {
             let group_1 = Object::new_group(vec![s]);
             let group_2 = Object::new_group(vec![group_1]).transform(&make_scaling(2.0, 2.0, 2.0));
 
-            let ray = Ray {
-                origin: point(10.0, 0.0, -10.0),
-                direction: vector_z(),
-            };
+            let ray = Ray::new(point(10.0, 0.0, -10.0), vector_z());
 
             let xs = group_2.intersect(&ray);
 
@@ -315,10 +393,7 @@ mod tests {
             group_1.set_transform(&make_scaling(2.0, 2.0, 2.0));
             let group_2 = Object::new_group(vec![group_1]);
 
-            let ray = Ray {
-                origin: point(10.0, 0.0, -10.0),
-                direction: vector_z(),
-            };
+            let ray = Ray::new(point(10.0, 0.0, -10.0), vector_z());
 
             let xs = group_2.intersect(&ray);
 
@@ -415,10 +490,7 @@ mod tests {
 
         let g = Object::new_group(vec![ts]);
 
-        let ray = Ray {
-            origin: point(0.0, 0.0, -5.0),
-            direction: vector_y(),
-        };
+        let ray = Ray::new(point(0.0, 0.0, -5.0), vector_y());
 
         g.intersect(&ray);
 
@@ -437,10 +509,7 @@ mod tests {
 
         let g = Object::new_group(vec![ts]);
 
-        let ray = Ray {
-            origin: point(0.0, 0.0, -5.0),
-            direction: vector_z(),
-        };
+        let ray = Ray::new(point(0.0, 0.0, -5.0), vector_z());
 
         g.intersect(&ray);
 
@@ -453,6 +522,16 @@ mod tests {
         assert!(ts.unwrap().ray().is_some());
     }
 
+    #[test]
+    fn leaves_of_a_nested_group() {
+        let inner = Object::new_group(vec![sphere(), sphere()]);
+        let outer = Object::new_group(vec![sphere(), inner]);
+        let leaves = outer.leaves();
+        assert_eq!(leaves.len(), 3);
+        assert!(leaves.iter().all(|o| matches!(o.shape(), Shape::Sphere())));
+        assert_eq!(sphere().leaves().len(), 1);
+    }
+
     #[test]
     fn partitioning_a_group_s_children() {
         let s1 = Object::new_sphere().with_transformation(make_translation(-2.0, 0.0, 0.0));
@@ -486,6 +565,39 @@ mod tests {
         assert_eq!(s.get_material().color, Color::new(0.8, 1.0, 0.6));
     }
 
+    #[test]
+    fn group_material_is_stored_once() {
+        let triangles = (0..1000)
+            .map(|i| {
+                let z = i as F3D;
+                triangle(
+                    point(0.0, 1.0, z),
+                    point(-1.0, -1.0, z),
+                    point(1.0, -1.0, z),
+                )
+            })
+            .collect();
+        let shared = SharedMaterial::new(Material::glass());
+        let mut g = Object::new_group(triangles).set_group_material(shared.clone());
+
+        let leaves = g.leaves();
+        assert_eq!(leaves.len(), 1000);
+        assert!(leaves
+            .iter()
+            .all(|o| SharedMaterial::ptr_eq(&o.material, &shared)));
+
+        // editing one leaf copies the material for it alone
+        if let Shape::Group(grp) = &mut g.shape {
+            grp.children_mut()[0].material.color = Color::new(1.0, 0.0, 0.0);
+        }
+        let leaves = g.leaves();
+        assert!(!SharedMaterial::ptr_eq(&leaves[0].material, &shared));
+        assert_eq!(shared.color, Color::white());
+        assert!(leaves[1..]
+            .iter()
+            .all(|o| SharedMaterial::ptr_eq(&o.material, &shared)));
+    }
+
     #[test]
     fn group_material_should_not_clobber_children_materials() {
         let s = sphere_with_id(Some("s1".to_string()));
@@ -502,4 +614,73 @@ mod tests {
         let s = from_shape(pg.shape()).unwrap().children()[0].clone();
         assert_eq!(s.get_material().color, Color::new(0.8, 1.0, 0.6));
     }
+
+    #[test]
+    fn intersecting_a_large_group_references_children_without_cloning() {
+        let triangles = (0..1000)
+            .map(|i| {
+                let z = i as F3D;
+                triangle(
+                    point(0.0, 1.0, z),
+                    point(-1.0, -1.0, z),
+                    point(1.0, -1.0, z),
+                )
+            })
+            .collect();
+        let group = Object::new_group(triangles);
+        let ray = Ray::new(point(0.0, 0.0, -5.0), vector_z());
+
+        let xs = group.intersect(&ray);
+
+        assert_eq!(xs.len(), 1000);
+        // every hit should point at a triangle owned by the group, not a copy of it
+        let children: HashSet<*const Object> = from_shape(group.shape())
+            .unwrap()
+            .children()
+            .iter()
+            .map(|c| c as *const Object)
+            .collect();
+        assert!(xs
+            .iter()
+            .all(|is| children.contains(&(is.object as *const Object))));
+    }
+
+    #[test]
+    fn transforming_a_group_twice_moves_children_instead_of_cloning() {
+        let spheres = || -> Vec<Object> {
+            (0..500)
+                .map(|i| sphere().transform(&make_translation(i as F3D, 0.0, 0.0)))
+                .collect()
+        };
+        let (m1, m2) = (make_scaling(2.0, 2.0, 2.0), make_rotation_y(0.5));
+
+        // what transform() used to do: rebuild through a GroupBuilder
+        let rebuild = |children: &[Object], m: &Matrix4| {
+            GroupBuilder::Node(
+                Object::new_dummy().with_transformation(*m),
+                children.iter().map(GroupBuilder::from_object).collect(),
+            )
+            .build(false, &SharedMaterial::default())
+        };
+        let expected = rebuild(&spheres(), &(m2 * m1));
+
+        let group = Object::new_group(spheres());
+        let before = crate::object::CLONE_COUNT.with(|c| c.get());
+        let group = group.transform(&m1).transform(&m2);
+        let clones = crate::object::CLONE_COUNT.with(|c| c.get()) - before;
+
+        // children are moved down, not copied, on every transform
+        assert_eq!(clones, 0);
+        assert_eq!(group.get_transform(), &glm::identity::<F3D, 4>());
+        assert_eq!(group.bounds, expected.bounds);
+        let (got, want) = (
+            from_shape(group.shape()).unwrap().children(),
+            from_shape(expected.shape()).unwrap().children(),
+        );
+        assert_eq!(got.len(), 500);
+        for (a, b) in got.iter().zip(want.iter()) {
+            let diff = a.get_transform() - b.get_transform();
+            assert!(diff.amax() < 1e-9, "{}", diff);
+        }
+    }
 }
@@ -0,0 +1,316 @@
+/**
+ * Indexed triangle mesh
+ *
+ * All triangles share one vertex buffer and are intersected straight from
+ * the index list, so a large model is a single Object instead of one
+ * Triangle object (with its own transforms and material) per face.
+ * Faces are sorted into a bounding volume hierarchy when the mesh is built,
+ * split the same way Group::divide splits children.
+ */
+use crate::bounds::*;
+use crate::intersection::*;
+use crate::math;
+use crate::object::Object;
+use crate::ray::Ray;
+use crate::shapes::shape::*;
+use crate::tuple::*;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexedMesh {
+    vertices: Vec<Point>,
+    // one per vertex for smooth shading, empty for flat faces
+    normals: Vec<Vector>,
+    faces: Vec<[u32; 3]>,
+    bounds: Bounds,
+    bvh: FaceNode,
+}
+
+// faces per node before it's split in two
+const LEAF_FACES: usize = 8;
+
+// BVH node over face indices
+#[derive(Clone, Debug, PartialEq)]
+struct FaceNode {
+    bounds: Bounds,
+    // faces that fit in neither half of the split stay at this level
+    faces: Vec<usize>,
+    children: Vec<FaceNode>,
+}
+
+impl FaceNode {
+    fn new(faces: Vec<usize>, face_bounds: &[Bounds]) -> FaceNode {
+        let mut bounds = Bounds::default();
+        for &i in &faces {
+            bounds.add_bounds(&face_bounds[i]);
+        }
+        FaceNode {
+            bounds,
+            faces,
+            children: vec![],
+        }
+    }
+
+    // see Group::partition and Group::divide
+    fn divide(mut self, face_bounds: &[Bounds]) -> FaceNode {
+        if self.faces.len() <= LEAF_FACES {
+            return self;
+        }
+        let (left_bbox, right_bbox) = self.bounds.split();
+        let mut left = vec![];
+        let mut right = vec![];
+        let mut rest = vec![];
+        for i in self.faces {
+            if left_bbox.contains_bounds(&face_bounds[i]) {
+                left.push(i);
+            } else if right_bbox.contains_bounds(&face_bounds[i]) {
+                right.push(i);
+            } else {
+                rest.push(i);
+            }
+        }
+        // everything on one side means the split didn't separate anything
+        if rest.is_empty() && (left.is_empty() || right.is_empty()) {
+            left.append(&mut right);
+            self.faces = left;
+            return self;
+        }
+        self.faces = rest;
+        self.children = [left, right]
+            .into_iter()
+            .filter(|half| !half.is_empty())
+            .map(|half| FaceNode::new(half, face_bounds).divide(face_bounds))
+            .collect();
+        self
+    }
+}
+
+// constructor utility, indices are taken three at a time
+pub fn indexed_mesh(vertices: Vec<Point>, normals: Vec<Vector>, indices: &[u32]) -> Object {
+    Object::new(None).with_shape(Shape::IndexedMesh(IndexedMesh::new(
+        vertices, normals, indices,
+    )))
+}
+
+// Möller-Trumbore, (t, u, v) of a hit on p1, p1 + e1, p1 + e2
+fn intersect_triangle(
+    ray: &Ray,
+    p1: &Point,
+    e1: &Vector,
+    e2: &Vector,
+) -> Option<(math::F3D, math::F3D, math::F3D)> {
+    let dir_cross_e2 = ray.direction.xyz().cross(&e2.xyz());
+    let det = e1.xyz().dot(&dir_cross_e2);
+    if math::f_equals(det.abs(), 0.0) {
+        return None;
+    }
+    let f = 1.0 / det;
+    let p1_to_origin = ray.origin - p1;
+    let u = f * p1_to_origin.xyz().dot(&dir_cross_e2);
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let origin_cross_e1 = p1_to_origin.xyz().cross(&e1.xyz());
+    let v = f * ray.direction.xyz().dot(&origin_cross_e1);
+    if v < 0.0 || (u + v) > 1.0 {
+        return None;
+    }
+    Some((f * e2.xyz().dot(&origin_cross_e1), u, v))
+}
+
+impl IndexedMesh {
+    pub fn new(vertices: Vec<Point>, normals: Vec<Vector>, indices: &[u32]) -> IndexedMesh {
+        assert!(
+            normals.is_empty() || normals.len() == vertices.len(),
+            "indexed mesh needs one normal per vertex"
+        );
+        let faces: Vec<[u32; 3]> = indices
+            .chunks_exact(3)
+            .map(|f| [f[0], f[1], f[2]])
+            .collect();
+        let face_bounds: Vec<Bounds> = faces
+            .iter()
+            .map(|f| {
+                let mut b = Bounds::default();
+                for &i in f {
+                    b.add_point(&vertices[i as usize]);
+                }
+                b
+            })
+            .collect();
+        let bvh = FaceNode::new((0..faces.len()).collect(), &face_bounds).divide(&face_bounds);
+        IndexedMesh {
+            vertices,
+            normals,
+            faces,
+            bounds: bvh.bounds,
+            bvh,
+        }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.faces.len()
+    }
+
+    // corner points of the i'th triangle
+    pub fn face(&self, i: usize) -> (Point, Point, Point) {
+        let [a, b, c] = self.faces[i];
+        (
+            self.vertices[a as usize],
+            self.vertices[b as usize],
+            self.vertices[c as usize],
+        )
+    }
+
+    // hits as (t, u, v, face index)
+    pub fn local_intersect(&self, ray: &Ray) -> Vec<(math::F3D, math::F3D, math::F3D, usize)> {
+        let mut hits = vec![];
+        self.intersect_node(&self.bvh, ray, &mut hits);
+        hits
+    }
+
+    fn intersect_node(
+        &self,
+        node: &FaceNode,
+        ray: &Ray,
+        hits: &mut Vec<(math::F3D, math::F3D, math::F3D, usize)>,
+    ) {
+        if !node.bounds.intersects(ray) {
+            return;
+        }
+        for &i in &node.faces {
+            let (p1, p2, p3) = self.face(i);
+            if let Some((t, u, v)) = intersect_triangle(ray, &p1, &(p2 - p1), &(p3 - p1)) {
+                hits.push((t, u, v, i));
+            }
+        }
+        for child in &node.children {
+            self.intersect_node(child, ray, hits);
+        }
+    }
+
+    // needs the hit to know which face, normals are interpolated when the mesh has them
+    pub fn local_normal_at(&self, _point: &Point, maybe_hit: Option<&Intersection>) -> Vector {
+        let hit = maybe_hit.expect("local_normal_at without intersection arg");
+        if self.normals.is_empty() {
+            let (p1, p2, p3) = self.face(hit.face);
+            let n = (p3 - p1).xyz().cross(&(p2 - p1).xyz()).normalize();
+            vector(n.x, n.y, n.z)
+        } else {
+            let [a, b, c] = self.faces[hit.face];
+            self.normals[b as usize] * hit.u
+                + self.normals[c as usize] * hit.v
+                + self.normals[a as usize] * (1.0 - hit.u - hit.v)
+        }
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::triangle::triangle;
+    use crate::{assert_eq_eps, assert_eq_feps};
+
+    #[test]
+    fn quad_mesh_matches_triangle_group() {
+        let corners = vec![
+            point(-1.0, 1.0, 0.0),
+            point(-1.0, -1.0, 0.0),
+            point(1.0, -1.0, 0.0),
+            point(1.0, 1.0, 0.0),
+        ];
+        let mesh = indexed_mesh(corners.clone(), vec![], &[0, 1, 2, 0, 2, 3]);
+        let group = Object::new_group(vec![
+            triangle(corners[0], corners[1], corners[2]),
+            triangle(corners[0], corners[2], corners[3]),
+        ]);
+        match mesh.shape() {
+            Shape::IndexedMesh(m) => assert_eq!(m.triangle_count(), 2),
+            _ => panic!(),
+        }
+
+        for origin in [
+            point(-0.5, -0.5, -2.0),
+            point(0.5, 0.5, -2.0),
+            point(0.9, -0.2, -3.0),
+            point(2.0, 0.0, -2.0),
+        ] {
+            let ray = Ray::new(origin, vector(0.1, 0.0, 1.0).normalize());
+            let (xs, expected) = (mesh.intersect(&ray), group.intersect(&ray));
+            assert_eq!(xs.len(), expected.len());
+            for (a, b) in xs.iter().zip(expected.iter()) {
+                assert_eq!(a.t, b.t);
+                let p = ray.position(a.t);
+                assert_eq!(
+                    a.object.normal_at(p, Some(a)),
+                    b.object.normal_at(p, Some(b))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn smooth_mesh_interpolates_vertex_normals() {
+        let mesh = indexed_mesh(
+            vec![point_y(), point(-1.0, 0.0, 0.0), point_x()],
+            vec![vector_y(), vector(-1.0, 0.0, 0.0), vector_x()],
+            &[0, 1, 2],
+        );
+        let ray = Ray::new(point(-0.2, 0.3, -5.0), vector_z());
+        let xs = mesh.intersect(&ray);
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].face, 0);
+        assert_eq_feps!(xs[0].u, 0.45);
+        assert_eq_feps!(xs[0].v, 0.25);
+        let n = mesh.normal_at(ray.position(xs[0].t), Some(&xs[0]));
+        assert_eq_eps!(n, vector(-0.2, 0.3, 0.0).normalize());
+    }
+
+    #[test]
+    fn large_mesh_is_split_into_a_hierarchy() {
+        // 20x20 grid of separate small triangles in the xy plane
+        let mut vertices = vec![];
+        let mut indices = vec![];
+        for y in 0..20 {
+            for x in 0..20 {
+                let (fx, fy) = (x as math::F3D, y as math::F3D);
+                let base = vertices.len() as u32;
+                vertices.extend([
+                    point(fx, fy + 0.5, 0.0),
+                    point(fx, fy, 0.0),
+                    point(fx + 0.5, fy, 0.0),
+                ]);
+                indices.extend([base, base + 1, base + 2]);
+            }
+        }
+        let mesh = IndexedMesh::new(vertices, vec![], &indices);
+        assert!(!mesh.bvh.children.is_empty());
+        fn check(node: &FaceNode, seen: &mut Vec<usize>) {
+            assert!(!node.children.is_empty() || node.faces.len() <= LEAF_FACES);
+            seen.extend(&node.faces);
+            for c in &node.children {
+                assert!(node.bounds.contains_bounds(&c.bounds));
+                check(c, seen);
+            }
+        }
+        let mut seen = vec![];
+        check(&mesh.bvh, &mut seen);
+        seen.sort_unstable();
+        assert_eq!(seen, (0..400).collect::<Vec<_>>());
+
+        // every face is still found
+        for i in [0, 57, 210, 399] {
+            let (p1, p2, p3) = mesh.face(i);
+            let center = point((p1.x + p2.x + p3.x) / 3.0, (p1.y + p2.y + p3.y) / 3.0, -1.0);
+            let hits = mesh.local_intersect(&Ray::new(center, vector_z()));
+            assert_eq!(hits.len(), 1);
+            assert_eq!(hits[0].3, i);
+        }
+        assert!(mesh
+            .local_intersect(&Ray::new(point(0.9, 0.9, -1.0), vector_z()))
+            .is_empty());
+    }
+}
@@ -4,7 +4,9 @@ pub mod cylinder;
 #[macro_use]
 pub mod group;
 pub mod csg;
+pub mod mesh;
 pub mod plane;
+pub mod quad;
 pub mod shape;
 pub mod smooth_triangle;
 pub mod sphere;
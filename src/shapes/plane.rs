@@ -5,12 +5,25 @@ use crate::ray::Ray;
 use crate::shapes::shape::*;
 use crate::tuple::*;
 
+/**
+ * Plane through the object space origin. The normal is +y unless built with
+ * Object::new_plane_facing, which saves rotating the plane (and its pattern)
+ * for walls and ceilings.
+ */
 #[derive(Clone, Debug, PartialEq)]
-pub struct Plane {}
+pub struct Plane {
+    normal: Vector,
+}
+
+impl Default for Plane {
+    fn default() -> Self {
+        Plane { normal: vector_y() }
+    }
+}
 
 // constructor utilities
 pub fn plane_with_id(id: Option<String>) -> Object {
-    Object::new(id).with_shape(Shape::Plane())
+    Object::new(id).with_shape(Shape::Plane(Plane::default()))
 }
 
 pub fn plane() -> Object {
@@ -18,23 +31,48 @@ pub fn plane() -> Object {
 }
 
 impl Plane {
-    pub fn local_intersect(ray: &Ray) -> Vec<math::F3D> {
-        if math::f_equals(ray.direction.y, 0.0) {
+    pub fn facing(normal: Vector) -> Plane {
+        assert!(
+            normal.magnitude() > 0.0,
+            "plane needs a nonzero normal to face along"
+        );
+        Plane {
+            normal: normal.normalize(),
+        }
+    }
+
+    pub fn normal(&self) -> Vector {
+        self.normal
+    }
+
+    pub fn local_intersect(&self, ray: &Ray) -> Vec<math::F3D> {
+        let denom = ray.direction.dot(&self.normal);
+        if math::f_equals(denom, 0.0) {
             vec![]
         } else {
-            let t = -ray.origin.y / ray.direction.y;
+            let t = -(ray.origin - point_zero()).dot(&self.normal) / denom;
             vec![t]
         }
     }
 
-    pub fn local_normal_at(_point: &Point) -> Vector {
-        point_y()
+    pub fn local_normal_at(&self, _point: &Point) -> Vector {
+        self.normal
     }
 
-    pub fn bounds() -> Bounds {
+    // flat along an axis aligned normal, otherwise unbounded
+    pub fn bounds(&self) -> Bounds {
+        let extent = |c: math::F3D| {
+            if math::f_equals(c.abs(), 1.0) {
+                0.0
+            } else {
+                math::INFINITY
+            }
+        };
+        let n = self.normal;
+        let e = point(extent(n.x), extent(n.y), extent(n.z));
         Bounds {
-            min: point(-math::INFINITY, 0.0, -math::INFINITY),
-            max: point(math::INFINITY, 0.0, math::INFINITY),
+            min: point(-e.x, -e.y, -e.z),
+            max: e,
         }
     }
 }
@@ -42,35 +80,81 @@ impl Plane {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::color::Color;
 
     #[test]
     fn normal_is_constant_everywhere() {
-        let n1 = Plane::local_normal_at(&point_zero());
-        let n2 = Plane::local_normal_at(&point(10.0, 0.0, -10.0));
-        assert_eq!(n1, point_y());
-        assert_eq!(n2, point_y());
+        let n1 = Plane::default().local_normal_at(&point_zero());
+        let n2 = Plane::default().local_normal_at(&point(10.0, 0.0, -10.0));
+        assert_eq!(n1, vector_y());
+        assert_eq!(n2, vector_y());
     }
 
     #[test]
     fn intersect_with_ray_parallel_to_plane() {
         let r = Ray::new(point(0.0, 10.0, 0.0), vector_z());
-        let xs = Plane::local_intersect(&r);
+        let xs = Plane::default().local_intersect(&r);
         assert!(xs.is_empty());
     }
 
     #[test]
     fn intersect_with_coplanar_ray() {
         let r = Ray::new(point_zero(), vector_z());
-        let xs = Plane::local_intersect(&r);
+        let xs = Plane::default().local_intersect(&r);
         assert!(xs.is_empty());
     }
 
     #[test]
     fn ray_intersect_from_above() {
         let r = Ray::new(point_y(), vector(0.0, -1.0, 0.0));
-        let xs = Plane::local_intersect(&r);
+        let xs = Plane::default().local_intersect(&r);
         assert_eq!(xs.len(), 1);
         assert_eq!(xs[0], 1.0);
         //assert_eq!(xs[0].object.get_id(), format!("g_{}", p.get_id()));
     }
+
+    #[test]
+    fn plane_facing_down() {
+        let p = Plane::facing(vector(0.0, -2.0, 0.0));
+        assert_eq!(p.local_normal_at(&point_zero()), vector(0.0, -1.0, 0.0));
+        let xs = p.local_intersect(&Ray::new(point(0.0, -3.0, 0.0), vector_y()));
+        assert_eq!(xs, vec![3.0]);
+        assert_eq!(p.bounds(), Plane::default().bounds());
+
+        let wall = Plane::facing(vector_x());
+        assert_eq!(
+            wall.bounds().max,
+            point(0.0, math::INFINITY, math::INFINITY)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "nonzero normal")]
+    fn plane_facing_nowhere_panics() {
+        Plane::facing(vector(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn ceiling_is_lit_from_below_only() {
+        use crate::lights::point_light;
+        use crate::world::World;
+        // ceiling at y = 1 seen from below
+        let ceiling = Object::new_plane_facing(vector(0.0, -1.0, 0.0))
+            .with_transformation(crate::transformation::make_translation(0.0, 1.0, 0.0));
+        let ray = Ray::new(point(0.0, 0.0, -1.0), vector(0.0, 1.0, 1.0).normalize());
+        let shade = |light_y| {
+            let mut world = World::new(vec![point_light(point(0.0, light_y, 0.0), Color::white())]);
+            world.add_shape(ceiling.clone());
+            world.color_at(&ray)
+        };
+        let below = shade(-5.0);
+        let above = shade(5.0);
+        assert!(below.red() > 0.5);
+        // only the ambient term when the light is behind the ceiling
+        assert_eq!(above, Color::white() * 0.1);
+
+        let xs = ceiling.intersect(&ray);
+        let comps = crate::computations::prepare_computations(&xs[0], &ray, &xs);
+        assert!(!comps.inside);
+    }
 }
@@ -0,0 +1,113 @@
+/**
+ * Finite parallelogram spanned by two edge vectors from a corner
+ */
+use crate::bounds::*;
+use crate::math;
+use crate::ray::Ray;
+use crate::tuple::*;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quad {
+    pub origin: Point,
+    pub u_edge: Vector,
+    pub v_edge: Vector,
+    // u_edge x v_edge, unnormalized
+    n: Vector,
+    normal: Vector,
+}
+
+impl Quad {
+    pub fn new(origin: Point, u_edge: Vector, v_edge: Vector) -> Quad {
+        let cross = u_edge.xyz().cross(&v_edge.xyz());
+        let n = vector(cross.x, cross.y, cross.z);
+        Quad {
+            origin,
+            u_edge,
+            v_edge,
+            n,
+            normal: n.normalize(),
+        }
+    }
+
+    pub fn local_intersect(&self, ray: &Ray) -> Vec<math::F3D> {
+        let denom = self.normal.dot(&ray.direction);
+        if math::f_equals(denom, 0.0) {
+            return vec![];
+        }
+        let t = self.normal.dot(&(self.origin - ray.origin)) / denom;
+        // express the hit in edge coordinates
+        let p = (ray.position(t) - self.origin).xyz();
+        let w = self.n.xyz() / self.n.dot(&self.n);
+        let alpha = w.dot(&p.cross(&self.v_edge.xyz()));
+        let beta = w.dot(&self.u_edge.xyz().cross(&p));
+
+        if (0.0..=1.0).contains(&alpha) && (0.0..=1.0).contains(&beta) {
+            vec![t]
+        } else {
+            vec![]
+        }
+    }
+
+    pub fn local_normal_at(&self, _point: &Point) -> Vector {
+        self.normal
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        let corners = [
+            self.origin,
+            self.origin + self.u_edge,
+            self.origin + self.v_edge,
+            self.origin + self.u_edge + self.v_edge,
+        ];
+        let mut b = Bounds::new(corners[0], corners[0]);
+        for c in corners.iter().skip(1) {
+            b.add_point(c);
+        }
+        b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::Object;
+    use crate::shapes::shape::Shape;
+
+    fn setup() -> Quad {
+        Quad::new(
+            point(-1.0, -1.0, 0.0),
+            vector(2.0, 0.0, 0.0),
+            vector(0.0, 2.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn ray_hits_quad_center() {
+        let q = setup();
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector_z());
+        let xs = q.local_intersect(&r);
+        assert_eq!(xs, vec![5.0]);
+        assert_eq!(q.local_normal_at(&point_zero()), vector_z());
+    }
+
+    #[test]
+    fn ray_just_outside_edge_misses() {
+        let q = setup();
+        let r = Ray::new(point(1.01, 0.0, -5.0), vector_z());
+        assert!(q.local_intersect(&r).is_empty());
+        let r = Ray::new(point(0.0, -1.01, -5.0), vector_z());
+        assert!(q.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn quad_has_finite_bounds() {
+        let o = Object::new_quad(
+            point(-1.0, -1.0, 0.0),
+            vector(2.0, 0.0, 0.0),
+            vector(0.0, 2.0, 0.0),
+        );
+        assert!(matches!(o.shape(), Shape::Quad(_)));
+        assert_eq!(o.bounds.min, point(-1.0, -1.0, 0.0));
+        assert_eq!(o.bounds.max, point(1.0, 1.0, 0.0));
+    }
+}
@@ -3,10 +3,47 @@ use crate::intersection::*;
 use crate::math::F3D;
 use crate::object::Object;
 use crate::ray::Ray;
-use crate::shapes::{cone, csg, cube, cylinder, group, plane, smooth_triangle, sphere, triangle};
+use crate::shapes::{
+    cone, csg, cube, cylinder, group, mesh, plane, quad, smooth_triangle, sphere, triangle,
+};
 use crate::tuple::*;
+use std::fmt;
 use std::sync::{Arc, Mutex};
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShapeError {
+    // minimum above maximum (or NaN), the shape could never be hit
+    InvertedExtent { minimum: F3D, maximum: F3D },
+    // collinear (or repeated) corners, there's no face normal
+    DegenerateTriangle { p1: Point, p2: Point, p3: Point },
+}
+
+impl fmt::Display for ShapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShapeError::InvertedExtent { minimum, maximum } => {
+                write!(f, "minimum {} is not below maximum {}", minimum, maximum)
+            }
+            ShapeError::DegenerateTriangle { p1, p2, p3 } => write!(
+                f,
+                "degenerate triangle ({}, {}, {}) ({}, {}, {}) ({}, {}, {})",
+                p1.x, p1.y, p1.z, p2.x, p2.y, p2.z, p3.x, p3.y, p3.z
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ShapeError {}
+
+// y extent check for cylinders and cones
+pub fn check_extent(minimum: F3D, maximum: F3D) -> Result<(), ShapeError> {
+    if minimum.is_nan() || maximum.is_nan() || minimum > maximum {
+        Err(ShapeError::InvertedExtent { minimum, maximum })
+    } else {
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum Shape {
     None,
@@ -15,10 +52,15 @@ pub enum Shape {
     Csg(csg::Csg),
     Cylinder(cylinder::Cylinder),
     Group(group::Group),
-    Plane(),
+    Plane(plane::Plane),
+    Quad(quad::Quad),
     Sphere(),
     Triangle(triangle::Triangle),
-    SmoothTriangle(smooth_triangle::SmoothTriangle),
+    // boxed, vertex colors make it much larger than the other shapes
+    SmoothTriangle(Box<smooth_triangle::SmoothTriangle>),
+    IndexedMesh(mesh::IndexedMesh),
+    // geometry shared by many objects, see Object::instance_of
+    Instance(Arc<Shape>),
     TestShape(TestShape),
 }
 
@@ -34,10 +76,13 @@ impl Shape {
             Shape::Csg(_) => "cone",
             Shape::Cylinder(_) => "cylinder",
             Shape::Group(_) => "group",
-            Shape::Plane() => "plane",
+            Shape::Plane(_) => "plane",
+            Shape::Quad(_) => "quad",
             Shape::Sphere() => "sphere",
             Shape::Triangle(_) => "triangle",
             Shape::SmoothTriangle(_) => "smooth_triangle",
+            Shape::IndexedMesh(_) => "indexed_mesh",
+            Shape::Instance(_) => "instance",
             Shape::TestShape(_) => "test_shape",
             Shape::None => "none",
         }
@@ -48,11 +93,15 @@ impl Shape {
             Shape::Cube() => add_uvs_to_ts(&cube::Cube::local_intersect(ray)),
             Shape::Cone(c) => add_uvs_to_ts(&c.local_intersect(ray)),
             Shape::Cylinder(c) => add_uvs_to_ts(&c.local_intersect(ray)),
-            Shape::Plane() => add_uvs_to_ts(&plane::Plane::local_intersect(ray)),
+            Shape::Plane(p) => add_uvs_to_ts(&p.local_intersect(ray)),
+            Shape::Quad(q) => add_uvs_to_ts(&q.local_intersect(ray)),
             Shape::Sphere() => add_uvs_to_ts(&sphere::Sphere::local_intersect(ray)),
-            Shape::Triangle(t) => add_uvs_to_ts(&t.local_intersect(ray)),
+            Shape::Triangle(t) => t.local_intersect(ray),
             Shape::SmoothTriangle(t) => t.local_intersect(ray),
             Shape::TestShape(c) => add_uvs_to_ts(&c.local_intersect(ray)),
+            Shape::Instance(s) => s.intersect(ray),
+            // placeholder objects are never hit
+            Shape::None => vec![],
             _ => unreachable!("illegal intersect from Shape"),
         }
     }
@@ -63,10 +112,13 @@ impl Shape {
             Shape::Cone(c) => c.local_normal_at(point),
             Shape::Csg(c) => c.local_normal_at(point),
             Shape::Cylinder(c) => c.local_normal_at(point),
-            Shape::Plane() => plane::Plane::local_normal_at(point),
+            Shape::Plane(p) => p.local_normal_at(point),
+            Shape::Quad(q) => q.local_normal_at(point),
             Shape::Sphere() => sphere::Sphere::local_normal_at(point),
             Shape::Triangle(t) => t.local_normal_at(point),
             Shape::SmoothTriangle(t) => t.local_normal_at(point, is),
+            Shape::IndexedMesh(m) => m.local_normal_at(point, is),
+            Shape::Instance(s) => s.normal_at(point, is),
             Shape::TestShape(c) => c.local_normal_at(point),
             Shape::Group(g) => g.normal_at(point),
             Shape::None => unreachable!("Shape::None::normal_at"),
@@ -79,16 +131,36 @@ impl Shape {
             Shape::Cone(c) => c.bounds(),
             Shape::Cylinder(c) => c.bounds(),
             Shape::Csg(c) => c.bounds(),
-            Shape::Plane() => plane::Plane::bounds(),
+            Shape::Plane(p) => p.bounds(),
+            Shape::Quad(q) => q.bounds(),
             Shape::Sphere() => sphere::Sphere::bounds(),
             Shape::Triangle(t) => t.bounds(),
             Shape::SmoothTriangle(t) => t.bounds(),
+            Shape::IndexedMesh(m) => m.bounds(),
+            Shape::Instance(s) => s.bounds(),
             Shape::TestShape(c) => c.bounds(),
             Shape::Group(g) => g.bounds(),
             Shape::None => Bounds::default(),
         }
     }
 
+    // the shape an instance shares, or the shape itself
+    pub fn geometry(&self) -> &Shape {
+        match self {
+            Shape::Instance(s) => s.geometry(),
+            _ => self,
+        }
+    }
+
+    // corner points of triangle shapes
+    pub fn vertices(&self) -> Option<(Point, Point, Point)> {
+        match self {
+            Shape::Triangle(t) => Some((t.p1(), t.p2(), t.p3())),
+            Shape::SmoothTriangle(t) => Some((t.p1(), t.p2(), t.p3())),
+            _ => None,
+        }
+    }
+
     pub fn divide(self, threshold: usize) -> Self {
         match self {
             Shape::Group(g) => Shape::Group(g.divide(threshold)),
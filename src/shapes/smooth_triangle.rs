@@ -2,6 +2,7 @@
  * Triangle shape
  */
 use crate::bounds::*;
+use crate::color::Color;
 use crate::intersection::*;
 use crate::math;
 use crate::object::Object;
@@ -17,6 +18,10 @@ pub struct SmoothTriangle {
     n1: Vector,
     n2: Vector,
     n3: Vector,
+    // optional per-vertex colors, used instead of the material color
+    pub c1: Option<Color>,
+    pub c2: Option<Color>,
+    pub c3: Option<Color>,
     e1: Vector,
     e2: Vector,
     normal: Vector,
@@ -36,17 +41,20 @@ pub fn smooth_triangle_with_id(
     let e2 = p3 - p1;
     let norm = (e2.xyz()).cross(&e1.xyz()).normalize();
 
-    Object::new(id).with_shape(Shape::SmoothTriangle(SmoothTriangle {
+    Object::new(id).with_shape(Shape::SmoothTriangle(Box::new(SmoothTriangle {
         p1,
         p2,
         p3,
         n1,
         n2,
         n3,
+        c1: None,
+        c2: None,
+        c3: None,
         e1,
         e2,
         normal: vector(norm.x, norm.y, norm.z),
-    }))
+    })))
 }
 
 pub fn smooth_triangle(
@@ -61,6 +69,38 @@ pub fn smooth_triangle(
 }
 
 impl SmoothTriangle {
+    pub fn p1(&self) -> Point {
+        self.p1
+    }
+
+    pub fn p2(&self) -> Point {
+        self.p2
+    }
+
+    pub fn p3(&self) -> Point {
+        self.p3
+    }
+
+    pub fn n1(&self) -> Vector {
+        self.n1
+    }
+
+    pub fn n2(&self) -> Vector {
+        self.n2
+    }
+
+    pub fn n3(&self) -> Vector {
+        self.n3
+    }
+
+    // vertex colors blended by the hit's barycentric u, v (all three must be set)
+    pub fn vertex_color(&self, u: math::F3D, v: math::F3D) -> Option<Color> {
+        match (self.c1, self.c2, self.c3) {
+            (Some(c1), Some(c2), Some(c3)) => Some(c2 * u + c3 * v + c1 * (1.0 - u - v)),
+            _ => None,
+        }
+    }
+
     pub fn local_normal_at(&self, point: &Point, maybe_hit: Option<&Intersection>) -> Vector {
         if let Some(hit) = maybe_hit {
             self.n2 * hit.u + self.n3 * hit.v + self.n1 * (1.0 - hit.u - hit.v)
@@ -151,4 +191,50 @@ mod tests {
         let comps = prepare_computations(&i, &ray, &xs);
         assert_eq_eps!(comps.normalv, vector(-0.5547, 0.83205, 0.0));
     }
+
+    #[test]
+    fn reading_back_vertices_and_normals() {
+        let tri = setup();
+        match tri.shape() {
+            Shape::SmoothTriangle(t) => {
+                assert_eq!(t.p1(), point_y());
+                assert_eq!(t.p2(), point_x() * -1.0);
+                assert_eq!(t.p3(), point_x());
+                assert_eq!(t.n1(), vector_y());
+                assert_eq!(t.n2(), vector_x() * -1.0);
+                assert_eq!(t.n3(), vector_x());
+            }
+            _ => panic!(),
+        }
+        assert_eq!(
+            tri.shape().vertices(),
+            Some((point_y(), point_x() * -1.0, point_x()))
+        );
+        assert_eq!(Shape::Sphere().vertices(), None);
+    }
+
+    #[test]
+    fn vertex_colors_blend_at_centroid() {
+        let tri = setup().with_vertex_colors(
+            Color::new(1.0, 0.0, 0.0),
+            Color::new(0.0, 1.0, 0.0),
+            Color::new(0.0, 0.0, 1.0),
+        );
+        let third = 1.0 / 3.0;
+        match tri.shape() {
+            Shape::SmoothTriangle(t) => {
+                let c = t.vertex_color(third, third).unwrap();
+                assert_eq_eps!(c.tuple(), Color::new(third, third, third).tuple());
+            }
+            _ => panic!(),
+        }
+        let i = Intersection::with_uv(&tri, 1.0, third, third);
+        let ray = Ray::new(point(0.0, third, -1.0), vector_z());
+        let xs = Intersections::from_intersections(vec![i.clone()]);
+        let comps = prepare_computations(&i, &ray, &xs);
+        assert_eq_eps!(
+            comps.vertex_color.unwrap().tuple(),
+            Color::new(third, third, third).tuple()
+        );
+    }
 }
@@ -38,12 +38,67 @@ pub fn triangle(p1: Point, p2: Point, p3: Point) -> Object {
     triangle_with_id(None, p1, p2, p3)
 }
 
+// edges from one corner that are (nearly) parallel, relative to their lengths
+// so tiny and huge triangles are judged alike
+fn collinear(e1: &Vector, e2: &Vector) -> bool {
+    let cross = e2.xyz().cross(&e1.xyz());
+    cross.magnitude() <= math::EPSILON * e1.magnitude() * e2.magnitude()
+}
+
+// Like triangle but rejects collinear corners, which would give a NaN normal
+pub fn try_triangle(p1: Point, p2: Point, p3: Point) -> Result<Object, ShapeError> {
+    if collinear(&(p2 - p1), &(p3 - p1)) {
+        Err(ShapeError::DegenerateTriangle { p1, p2, p3 })
+    } else {
+        Ok(triangle(p1, p2, p3))
+    }
+}
+
 impl Triangle {
+    pub fn p1(&self) -> Point {
+        self.p1
+    }
+
+    pub fn p2(&self) -> Point {
+        self.p2
+    }
+
+    pub fn p3(&self) -> Point {
+        self.p3
+    }
+
+    // flat triangles share the face normal at every vertex
+    pub fn n1(&self) -> Vector {
+        self.normal
+    }
+
+    pub fn n2(&self) -> Vector {
+        self.normal
+    }
+
+    pub fn n3(&self) -> Vector {
+        self.normal
+    }
+
+    pub fn area(&self) -> math::F3D {
+        self.e1.xyz().cross(&self.e2.xyz()).magnitude() / 2.0
+    }
+
+    pub fn centroid(&self) -> Point {
+        (self.p1 + self.p2 + self.p3) / 3.0
+    }
+
+    // true for collinear corners, see try_triangle
+    pub fn is_degenerate(&self) -> bool {
+        collinear(&self.e1, &self.e2)
+    }
+
     pub fn local_normal_at(&self, point: &Point) -> Vector {
         self.normal
     }
 
-    pub fn local_intersect(&self, ray: &Ray) -> Vec<math::F3D> {
+    // hits as (t, u, v), u and v being the barycentric coordinates of the hit
+    pub fn local_intersect(&self, ray: &Ray) -> Vec<(math::F3D, math::F3D, math::F3D)> {
         let dir_cross_e2 = ray.direction.xyz().cross(&self.e2.xyz());
         let det = self.e1.xyz().dot(&dir_cross_e2);
         if math::f_equals(det.abs(), 0.0) {
@@ -63,7 +118,7 @@ impl Triangle {
                     vec![]
                 } else {
                     let t = f * self.e2.xyz().dot(&origin_cross_e1);
-                    vec![t]
+                    vec![(t, u, v)]
                 }
             }
         }
@@ -84,6 +139,7 @@ impl Triangle {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{assert_eq_eps, assert_eq_feps};
 
     #[test]
     fn constructor_calculates_props() {
@@ -155,4 +211,72 @@ mod tests {
         assert_eq!(xs.len(), 1);
         assert_eq!(xs[0].t, 2.0);
     }
+
+    #[test]
+    fn hit_at_centroid_has_equal_barycentrics() {
+        let t = triangle(point_y(), point(-1.0, 0.0, 0.0), point_x());
+        let ray = Ray::new(point(0.0, 1.0 / 3.0, -2.0), vector_z());
+        let xs = t.intersect(&ray);
+        assert_eq!(xs.len(), 1);
+        assert_eq_feps!(xs[0].u, 1.0 / 3.0);
+        assert_eq_feps!(xs[0].v, 1.0 / 3.0);
+    }
+
+    #[test]
+    fn area_and_centroid() {
+        let t = triangle(point_zero(), point(4.0, 0.0, 0.0), point(0.0, 3.0, 0.0));
+        match t.shape() {
+            Shape::Triangle(tr) => {
+                assert_eq!(tr.area(), 6.0);
+                assert_eq_eps!(tr.centroid(), point(4.0 / 3.0, 1.0, 0.0));
+                assert!(!tr.is_degenerate());
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn collinear_triangle_is_rejected() {
+        let (p1, p2, p3) = (point_zero(), point_x(), point(2.0, 0.0, 0.0));
+        assert_eq!(
+            try_triangle(p1, p2, p3).err(),
+            Some(ShapeError::DegenerateTriangle { p1, p2, p3 })
+        );
+        assert!(try_triangle(point_y(), point(-1.0, 0.0, 0.0), point_x()).is_ok());
+        match triangle(p1, p2, p1).shape() {
+            Shape::Triangle(tr) => assert!(tr.is_degenerate()),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn tiny_triangles_are_not_degenerate() {
+        let (p1, p2, p3) = (point_zero(), point(0.01, 0.0, 0.0), point(0.0, 0.01, 0.0));
+        let t = try_triangle(p1, p2, p3).unwrap();
+        match t.shape() {
+            Shape::Triangle(tr) => assert!(!tr.is_degenerate()),
+            _ => panic!(),
+        }
+        // but nearly collinear ones are, whatever their size
+        let sliver = point(1000.0, 1e-5, 0.0);
+        assert!(try_triangle(p1, point(2000.0, 0.0, 0.0), sliver).is_err());
+    }
+
+    #[test]
+    fn accessors_return_points_and_face_normal() {
+        let t = triangle(point_y(), point(-1.0, 0.0, 0.0), point_x());
+        match t.shape() {
+            Shape::Triangle(tr) => {
+                assert_eq!(tr.p1(), point_y());
+                assert_eq!(tr.p3(), point_x());
+                assert_eq!(tr.n1(), vector(0.0, 0.0, -1.0));
+                assert_eq!(tr.n2(), tr.n3());
+            }
+            _ => panic!(),
+        }
+        assert_eq!(
+            t.shape().vertices(),
+            Some((point_y(), point(-1.0, 0.0, 0.0), point_x()))
+        );
+    }
 }
@@ -0,0 +1,132 @@
+/**
+ * Counters for profiling how much work a render does. Safe to share
+ * between render threads. They're off unless enabled, since every thread
+ * updating the same counters slows down the hottest paths.
+ */
+use crate::bounds::Bounds;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Debug, Default)]
+pub struct RenderStats {
+    enabled: bool,
+    rays_cast: AtomicUsize,
+    intersection_tests: AtomicUsize,
+    bounding_box_skips: AtomicUsize,
+    shadow_shortcuts: AtomicUsize,
+    secondary_shades: AtomicUsize,
+}
+
+// plain copy of the counters at one point in time
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RenderStatsSnapshot {
+    pub rays_cast: usize,
+    pub intersection_tests: usize,
+    pub bounding_box_skips: usize,
+    // shadow rays answered without intersecting, nothing's bounds were in the way
+    pub shadow_shortcuts: usize,
+    // reflected_color / refracted_color evaluations
+    pub secondary_shades: usize,
+}
+
+// what a world is made of, see World::summary
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SceneSummary {
+    // top level shapes
+    pub objects: usize,
+    // non-group shapes, counting everything nested in groups
+    pub primitives: usize,
+    // flat and smooth triangles, counting everything nested in groups
+    pub triangles: usize,
+    pub lights: usize,
+    pub bounds: Bounds,
+}
+
+impl RenderStats {
+    pub fn new() -> RenderStats {
+        RenderStats::default()
+    }
+
+    pub fn set_enabled(&mut self, on: bool) {
+        self.enabled = on;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn record(&self, counter: &AtomicUsize) {
+        if self.enabled {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_ray(&self) {
+        self.record(&self.rays_cast);
+    }
+
+    pub fn record_intersection_test(&self) {
+        self.record(&self.intersection_tests);
+    }
+
+    pub fn record_bounding_box_skip(&self) {
+        self.record(&self.bounding_box_skips);
+    }
+
+    pub fn record_shadow_shortcut(&self) {
+        self.record(&self.shadow_shortcuts);
+    }
+
+    pub fn record_secondary_shade(&self) {
+        self.record(&self.secondary_shades);
+    }
+
+    pub fn snapshot(&self) -> RenderStatsSnapshot {
+        RenderStatsSnapshot {
+            rays_cast: self.rays_cast.load(Ordering::Relaxed),
+            intersection_tests: self.intersection_tests.load(Ordering::Relaxed),
+            bounding_box_skips: self.bounding_box_skips.load(Ordering::Relaxed),
+            shadow_shortcuts: self.shadow_shortcuts.load(Ordering::Relaxed),
+            secondary_shades: self.secondary_shades.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn reset(&self) {
+        self.rays_cast.store(0, Ordering::Relaxed);
+        self.intersection_tests.store(0, Ordering::Relaxed);
+        self.bounding_box_skips.store(0, Ordering::Relaxed);
+        self.shadow_shortcuts.store(0, Ordering::Relaxed);
+        self.secondary_shades.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_snapshot_and_reset() {
+        let mut stats = RenderStats::new();
+        stats.record_ray();
+        assert_eq!(stats.snapshot(), RenderStatsSnapshot::default());
+
+        stats.set_enabled(true);
+        stats.record_ray();
+        stats.record_ray();
+        stats.record_intersection_test();
+        stats.record_bounding_box_skip();
+        stats.record_shadow_shortcut();
+        stats.record_secondary_shade();
+        assert_eq!(
+            stats.snapshot(),
+            RenderStatsSnapshot {
+                rays_cast: 2,
+                intersection_tests: 1,
+                bounding_box_skips: 1,
+                shadow_shortcuts: 1,
+                secondary_shades: 1,
+            }
+        );
+        stats.reset();
+        assert_eq!(stats.snapshot(), RenderStatsSnapshot::default());
+    }
+}
@@ -36,6 +36,52 @@ pub fn view_transform(from: &Point, to: &Point, up: &Vector) -> Matrix4 {
     glm::look_at(&from.xyz(), &to.xyz(), &up.xyz())
 }
 
+/**
+ * Split a T * R * S matrix into (translation, scale, rotation). Shear is
+ * not recovered, a mirrored matrix comes back with a negative x scale.
+ */
+pub fn decompose(m: &Matrix4) -> (Vector, Vector, Matrix4) {
+    let translation = vector(m[(0, 3)], m[(1, 3)], m[(2, 3)]);
+    let column = |c: usize| glm::vec3(m[(0, c)], m[(1, c)], m[(2, c)]);
+    let (cx, cy, cz) = (column(0), column(1), column(2));
+    let mut sx = cx.magnitude();
+    if cx.dot(&cy.cross(&cz)) < 0.0 {
+        sx = -sx;
+    }
+    let scale = vector(sx, cy.magnitude(), cz.magnitude());
+
+    let mut rotation: Matrix4 = glm::identity();
+    for (c, (col, s)) in [(cx, scale.x), (cy, scale.y), (cz, scale.z)]
+        .iter()
+        .enumerate()
+    {
+        for r in 0..3 {
+            rotation[(r, c)] = col[r] / s;
+        }
+    }
+    (translation, scale, rotation)
+}
+
+/**
+ * Common interface for anything placed in the scene by a matrix
+ */
+pub trait Transformable: Sized {
+    fn get_transform(&self) -> &Matrix4;
+
+    fn set_transform(&mut self, m: &Matrix4);
+
+    fn with_transform(mut self, m: Matrix4) -> Self {
+        self.set_transform(&m);
+        self
+    }
+
+    // compose m on top of the current transform (m * current)
+    fn apply(&mut self, m: &Matrix4) {
+        let t = m * self.get_transform();
+        self.set_transform(&t);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -107,4 +153,18 @@ mod tests {
         let t = view_transform(&from, &to, &up);
         assert_eq!(t, make_scaling(-1.0, 1.0, -1.0));
     }
+
+    #[test]
+    fn decomposing_trs_matrix() {
+        let r = make_rotation_y(0.7) * make_rotation_x(-0.3);
+        let m = make_translation(1.0, -2.0, 3.5) * r * make_scaling(2.0, 0.5, 3.0);
+        let (t, s, rot) = decompose(&m);
+        assert_eq_eps!(t, vector(1.0, -2.0, 3.5));
+        assert_eq_eps!(s, vector(2.0, 0.5, 3.0));
+        for row in 0..4 {
+            for col in 0..4 {
+                assert!((rot[(row, col)] - r[(row, col)]).abs() < crate::math::EPSILON);
+            }
+        }
+    }
 }
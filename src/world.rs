@@ -3,17 +3,81 @@ use crate::computations::*;
 use crate::intersection::*;
 use crate::lights::*;
 use crate::materials::Material;
+use crate::math::{spherical_map, EPSILON, F3D};
 use crate::object::*;
+use crate::pattern::{image::ImagePattern, TPattern};
 use crate::ray::Ray;
+#[cfg(feature = "serde")]
+use crate::scene::{ObjectDesc, SceneError, WorldDesc};
+use crate::shapes::cube::cube;
+use crate::shapes::shape::Shape;
 use crate::shapes::sphere::sphere_with_id;
-use crate::transformation::make_scaling;
+use crate::stats::{RenderStats, RenderStatsSnapshot, SceneSummary};
+use crate::transformation::{make_scaling, make_translation};
 use crate::tuple::*;
+use rand::Rng;
 
 pub const MAX_RAY_DEPTH: u8 = 5;
+const DEFAULT_OCCLUSION_SAMPLES: usize = 16;
+const DEFAULT_OCCLUSION_RADIUS: F3D = 1.0;
+
+// which term of shade_hit to return, for debugging materials
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderChannel {
+    Full,
+    Surface,
+    Reflected,
+    Refracted,
+}
+
+/**
+ * Bounces left for secondary rays, counted separately for reflection and
+ * refraction. A plain u8 gives both the same budget.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Depth {
+    pub reflection: u8,
+    pub refraction: u8,
+}
+
+impl From<u8> for Depth {
+    fn from(n: u8) -> Self {
+        Depth {
+            reflection: n,
+            refraction: n,
+        }
+    }
+}
 
 pub struct World {
     lights: Vec<Light>,
     objects: Vec<Object>,
+    // ambient occlusion is off by default
+    pub use_ambient_occlusion: bool,
+    pub occlusion_samples: usize,
+    pub occlusion_radius: F3D,
+    // how many reflection bounces color_at follows
+    pub max_depth: u8,
+    // and refraction bounces, nested glass needs more of these
+    pub max_refraction_depth: u8,
+    // fade reflections/refractions towards the background as the bounces run
+    // out, instead of cutting them to black at max_depth
+    pub fade_reflections: bool,
+    // how far secondary rays start off the surface, see prepare_computations_eps
+    pub surface_offset: F3D,
+    // how far shadow rays start off the surface as a fraction of the point's
+    // largest coordinate, so it follows the scene's scale and rounding error.
+    // Replaces surface_offset for shadows when set, 0 keeps surface_offset
+    pub shadow_bias: F3D,
+    // color of rays that miss everything, unless background_pattern or
+    // environment is set
+    pub background: Color,
+    // sampled at the ray direction (as a point on the unit sphere) for skies
+    pub background_pattern: Option<TPattern>,
+    // equirectangular sky image looked up by ray direction, takes priority
+    // over background_pattern
+    pub environment: Option<ImagePattern>,
+    stats: RenderStats,
 }
 
 impl World {
@@ -21,6 +85,57 @@ impl World {
         World {
             lights,
             objects: vec![],
+            use_ambient_occlusion: false,
+            occlusion_samples: DEFAULT_OCCLUSION_SAMPLES,
+            occlusion_radius: DEFAULT_OCCLUSION_RADIUS,
+            max_depth: MAX_RAY_DEPTH,
+            max_refraction_depth: MAX_RAY_DEPTH,
+            fade_reflections: false,
+            surface_offset: EPSILON,
+            shadow_bias: 0.0,
+            background: Color::black(),
+            background_pattern: None,
+            environment: None,
+            stats: RenderStats::new(),
+        }
+    }
+
+    pub fn with_max_depth(mut self, n: u8) -> World {
+        self.max_depth = n;
+        self
+    }
+
+    pub fn with_max_refraction_depth(mut self, n: u8) -> World {
+        self.max_refraction_depth = n;
+        self
+    }
+
+    // the full budget a primary ray starts with
+    fn depth(&self) -> Depth {
+        Depth {
+            reflection: self.max_depth,
+            refraction: self.max_refraction_depth,
+        }
+    }
+
+    pub fn with_background(mut self, color: Color) -> World {
+        self.background = color;
+        self
+    }
+
+    fn background_at(&self, ray: &Ray) -> Color {
+        // look ups are by direction, as a point on the unit sphere
+        let on_sphere = || {
+            let d = ray.direction.normalize();
+            point(d.x, d.y, d.z)
+        };
+        if let Some(image) = &self.environment {
+            let (u, v) = spherical_map(&on_sphere());
+            return image.uv_pattern_at(u, v);
+        }
+        match &self.background_pattern {
+            Some(p) => p.pattern_at_point(&on_sphere()),
+            None => self.background,
         }
     }
 
@@ -28,6 +143,22 @@ impl World {
         self.objects.push(s);
     }
 
+    pub fn add_objects(&mut self, objs: impl IntoIterator<Item = Object>) {
+        self.objects.extend(objs);
+    }
+
+    pub fn push_light(&mut self, light: Light) {
+        self.lights.push(light);
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    pub fn clear_lights(&mut self) {
+        self.lights.clear();
+    }
+
     pub fn get_shape(&self, i: usize) -> &Object {
         &self.objects[i]
     }
@@ -36,124 +167,595 @@ impl World {
         self.objects[i] = shape;
     }
 
-    // returns all ray/shape intersections sorted by t
-    pub fn intersect(&self, ray: &Ray) -> Intersections {
-        self.objects
-            .iter()
-            .fold(Intersections::new(), |mut acc, curr| {
-                for is in curr.intersect(ray).vec() {
-                    acc.push(is.clone());
-                }
-                acc
-            })
-            .sort_intersections()
+    pub fn remove_shape(&mut self, i: usize) -> Object {
+        self.objects.remove(i)
     }
 
-    pub fn shade_hit(&self, comps: &Computations, remaining: u8) -> Color {
-        let colors: Vec<Color> = self
-            .lights
-            .iter()
-            .map(|l| {
-                // Instead of bool, calculate color intensity from the light source
-                let light_intensity = l.intensity_at(&self, &comps.over_point); // self.is_shadowed(&l, &comps.over_point);
-
-                let surface = comps.object.get_material().lighting(
-                    &comps.object,
-                    &l,
-                    &comps.over_point,
-                    &comps.eyev,
-                    &comps.normalv,
-                    light_intensity,
-                );
-                let reflected = self.reflected_color(comps, remaining);
-                let refracted = self.refracted_color(comps, remaining);
+    // looks up a top level object by Object::get_id, e.g. "sphere_s1"
+    pub fn find_shape(&self, id: &str) -> Option<&Object> {
+        self.objects.iter().find(|o| o.get_id() == id)
+    }
+
+    pub fn shape_count(&self) -> usize {
+        self.objects.len()
+    }
+
+    // edit every top level object in place
+    pub fn map_shapes(&mut self, f: impl FnMut(&mut Object)) {
+        self.objects.iter_mut().for_each(f);
+    }
+
+    // the default world with every object's ambient set to a
+    pub fn default_with_ambient(a: F3D) -> World {
+        let mut world = World::default();
+        world.map_shapes(|o| o.material.ambient = a);
+        world
+    }
+
+    // counting is off by default, see RenderStats
+    pub fn set_collect_stats(&mut self, on: bool) {
+        self.stats.set_enabled(on);
+    }
 
-                let material = comps.object.get_material();
-                if material.transparency > 0.0 && material.reflective > 0.0 {
-                    let reflectance = schlick(comps);
+    pub fn with_stats(mut self) -> World {
+        self.set_collect_stats(true);
+        self
+    }
+
+    // counters for every ray cast into this world so far, all zero unless
+    // collecting stats
+    pub fn stats(&self) -> RenderStatsSnapshot {
+        self.stats.snapshot()
+    }
 
-                    surface + reflected * reflectance + refracted * (1.0 - reflectance)
-                } else {
-                    surface + reflected + refracted
+    // object, triangle and light counts plus the bounds of everything
+    pub fn summary(&self) -> SceneSummary {
+        fn tally(o: &Object, summary: &mut SceneSummary) {
+            match o.shape().geometry() {
+                Shape::Group(g) => g.children().iter().for_each(|c| tally(c, summary)),
+                Shape::Triangle(_) | Shape::SmoothTriangle(_) => {
+                    summary.primitives += 1;
+                    summary.triangles += 1;
                 }
-            })
-            .collect();
+                Shape::IndexedMesh(m) => {
+                    summary.primitives += 1;
+                    summary.triangles += m.triangle_count();
+                }
+                _ => summary.primitives += 1,
+            }
+        }
+
+        let mut summary = SceneSummary {
+            objects: self.objects.len(),
+            lights: self.lights.len(),
+            ..SceneSummary::default()
+        };
+        for o in &self.objects {
+            tally(o, &mut summary);
+            summary.bounds.add_bounds(&o.bounds());
+        }
+        summary
+    }
+
+    // returns all ray/shape intersections sorted by t
+    pub fn intersect(&self, ray: &Ray) -> Intersections<'_> {
+        let mut xs = Intersections::new();
+        self.intersect_into(ray, &mut xs);
+        xs
+    }
+
+    /**
+     * Same as intersect, but fills a caller owned buffer (clearing it first)
+     * so a renderer can reuse one allocation for many rays
+     */
+    pub fn intersect_into<'w>(&'w self, ray: &Ray, buf: &mut Intersections<'w>) {
+        self.stats.record_ray();
+        buf.clear();
+        let stats = self.stats.is_enabled().then_some(&self.stats);
+        // objects insert their hits in order, no sort afterwards
+        for o in &self.objects {
+            o.intersect_into_with_stats(ray, buf, stats);
+        }
+    }
+
+    pub fn shade_hit(&self, comps: &Computations, remaining: impl Into<Depth>) -> Color {
+        self.shade_hit_channel(comps, remaining, RenderChannel::Full)
+    }
+
+    /**
+     * Only the top level hit is split into channels, secondary rays
+     * always return the full color
+     */
+    pub fn shade_hit_channel(
+        &self,
+        comps: &Computations,
+        remaining: impl Into<Depth>,
+        channel: RenderChannel,
+    ) -> Color {
+        let remaining = remaining.into();
+        let occlusion = if self.use_ambient_occlusion {
+            self.ambient_occlusion(comps, self.occlusion_samples)
+        } else {
+            1.0
+        };
+        // vertex colored triangles replace the material's own color
+        let vertex_material = comps.vertex_color.map(|color| Material {
+            color,
+            pattern: None,
+            ..comps.object.get_material().clone()
+        });
+        let m = comps.object.get_material();
+        let material = vertex_material.as_ref().unwrap_or(m);
+        let shadow_origin = self.shadow_origin(comps);
         // add up light source colors
-        colors
-            .into_iter()
-            .fold(Color::black(), |acc, curr| acc + curr)
+        let surface = self.lights.iter().fold(Color::black(), |acc, l| {
+            // Instead of bool, calculate color intensity from the light source
+            let light_intensity = l.intensity_at(&self, &shadow_origin, comps.time);
+
+            acc + material.lighting_with_occlusion(
+                comps.object,
+                &l,
+                &comps.over_point,
+                &comps.eyev,
+                &comps.normalv,
+                light_intensity,
+                occlusion,
+            )
+        });
+        // emission counts once per hit, however many lights there are
+        let surface = surface + m.emissive;
+        if channel == RenderChannel::Surface {
+            return surface;
+        }
+
+        // secondary rays are traced once per hit, not once per light.
+        // opaque, non-reflective surfaces skip the recursive calls
+        let (reflected, refracted) = if m.reflective == 0.0 && m.transparency == 0.0 {
+            (Color::black(), Color::black())
+        } else {
+            (
+                self.reflected_color(comps, remaining),
+                self.refracted_color(comps, remaining),
+            )
+        };
+
+        // transparent surfaces split light between reflection and
+        // refraction by the Fresnel reflectance
+        let (kr, kt) = if m.transparency > 0.0 {
+            (comps.reflectance, 1.0 - comps.reflectance)
+        } else {
+            (1.0, 1.0)
+        };
+
+        match channel {
+            RenderChannel::Full => surface + reflected * kr + refracted * kt,
+            RenderChannel::Surface => surface,
+            RenderChannel::Reflected => reflected * kr,
+            RenderChannel::Refracted => refracted * kt,
+        }
     }
 
-    pub fn color_at(&self, ray: &Ray, remaining: u8) -> Color {
+    pub fn color_at(&self, ray: &Ray) -> Color {
+        self.color_at_depth(ray, self.depth())
+    }
+
+    pub fn color_at_channel(&self, ray: &Ray, channel: RenderChannel) -> Color {
         let xs = self.intersect(ray);
+        if let Some(is) = xs.hit() {
+            let comps = prepare_computations_eps(is, ray, &xs, self.surface_offset);
+            self.shade_hit_channel(&comps, self.depth(), channel)
+        } else {
+            self.background_at(ray)
+        }
+    }
+
+    // color_at for a primary ray, intersecting into buf instead of a new list
+    pub fn color_at_with<'w>(&'w self, ray: &Ray, buf: &mut Intersections<'w>) -> Color {
+        self.intersect_into(ray, buf);
+        self.shade_intersections(ray, buf, self.depth())
+    }
+
+    pub(crate) fn color_at_depth(&self, ray: &Ray, remaining: Depth) -> Color {
+        let xs = self.intersect(ray);
+        self.shade_intersections(ray, &xs, remaining)
+    }
+
+    fn shade_intersections(&self, ray: &Ray, xs: &Intersections, remaining: Depth) -> Color {
         // find hit from the intersections
         if let Some(is) = xs.hit() {
-            let comps = prepare_computations(
-                is, ray,
+            let comps = prepare_computations_eps(
+                is,
+                ray,
                 // optimization: just pass ref to xs
-                &xs,
-                //&Intersections::from_intersections(vec![is.clone()]),
+                xs,
+                self.surface_offset,
             );
             self.shade_hit(&comps, remaining)
         } else {
-            Color::black()
+            self.background_at(ray)
         }
     }
 
-    pub fn is_shadowed(&self, light_pos: &Point, p: &Point) -> bool {
+    /**
+     * What the ray hits first, for picking: (Object::get_id, hit point,
+     * normal facing back along the ray). Shapes inside groups report their
+     * own id.
+     */
+    pub fn cast(&self, ray: &Ray) -> Option<(String, Point, Vector)> {
+        let xs = self.intersect(ray);
+        xs.hit().map(|is| {
+            let comps = prepare_computations(is, ray, &xs);
+            (comps.object.get_id(), comps.point, comps.normalv)
+        })
+    }
+
+    // where shadow rays for this hit start, see shadow_bias
+    pub fn shadow_origin(&self, comps: &Computations) -> Point {
+        if self.shadow_bias > 0.0 {
+            let p = comps.point;
+            let scale = p.x.abs().max(p.y.abs()).max(p.z.abs()).max(1.0);
+            p + comps.normalv * (self.shadow_bias * scale)
+        } else {
+            comps.over_point
+        }
+    }
+
+    // shadow test against objects where they are at shutter time
+    pub fn is_shadowed(&self, light_pos: &Point, p: &Point, time: F3D) -> bool {
         let v = light_pos - p;
-        let distance = v.magnitude();
-        let direction = v.normalize();
-        let r = Ray::new(*p, direction);
-        let xs = self.intersect(&r);
+        let ray = Ray::new_normalized(*p, v).with_time(time);
+        self.is_occluded(&ray, v.magnitude())
+    }
+
+    /**
+     * Whether a shadow casting object sits on the ray less than distance
+     * (world units) from its origin, at the ray's time. The direction
+     * doesn't have to be normalized, hits are compared in units of its length.
+     */
+    pub fn is_occluded(&self, ray: &Ray, distance: F3D) -> bool {
+        let max_t = distance / ray.direction.magnitude();
+        // clear line of sight if the segment misses every object's bounds
+        if !self
+            .objects
+            .iter()
+            .any(|o| o.bounds.intersects_within(ray, max_t))
+        {
+            self.stats.record_shadow_shortcut();
+            return false;
+        }
+        let xs = self.intersect(ray);
 
         if let Some(is) = xs.hit() {
-            return is.object.has_shadow && is.t < distance;
+            return is.object.has_shadow && is.t < max_t;
         }
         false
     }
 
-    pub fn reflected_color(&self, comps: &Computations, remaining: u8) -> Color {
-        // if at max recursive depth
-        if remaining == 0 {
-            Color::black()
+    /**
+     * Fraction (0 -> 1) of random points inside the light's softness sphere
+     * that are hidden from p, approximating a penumbra
+     */
+    pub fn is_shadowed_soft(
+        &self,
+        light: &PointLight,
+        p: &Point,
+        samples: usize,
+        time: F3D,
+    ) -> F3D {
+        if samples == 0 || light.softness <= 0.0 {
+            return if self.is_shadowed(&light.position, p, time) {
+                1.0
+            } else {
+                0.0
+            };
+        }
+        let mut rng = rand::thread_rng();
+        let mut blocked = 0;
+
+        for _ in 0..samples {
+            let offset = random_in_unit_sphere(&mut rng);
+            if self.is_shadowed(&(light.position + offset * light.softness), p, time) {
+                blocked += 1;
+            }
+        }
+        blocked as F3D / samples as F3D
+    }
+
+    /**
+     * Fraction of random hemisphere rays around the surface normal that escape
+     * without hitting anything within occlusion_radius (1.0 = fully unoccluded)
+     */
+    pub fn ambient_occlusion(&self, comps: &Computations, samples: usize) -> F3D {
+        self.occlusion_at(&comps.over_point, &comps.normalv, samples)
+    }
+
+    // ambient_occlusion for any (already offset) surface point and normal
+    fn occlusion_at(&self, over_point: &Point, normalv: &Vector, samples: usize) -> F3D {
+        if samples == 0 {
+            return 1.0;
+        }
+        let mut rng = rand::thread_rng();
+        let mut unoccluded = 0;
+
+        for _ in 0..samples {
+            // a random direction, flipped into the hemisphere facing the normal
+            let mut direction = random_in_unit_sphere(&mut rng).normalize();
+            if direction.dot(normalv) < 0.0 {
+                direction = -direction;
+            }
+            let r = Ray::new(*over_point, direction);
+            let xs = self.intersect(&r);
+
+            match xs.hit() {
+                Some(is) if is.object.has_shadow && is.t < self.occlusion_radius => {}
+                _ => unoccluded += 1,
+            }
+        }
+        unoccluded as F3D / samples as F3D
+    }
+
+    /**
+     * Precompute ambient occlusion for static scenes: each top level object
+     * gets the average occlusion of `samples` random points on its surface
+     * (found by aiming rays at it from its bounding sphere), each tested with
+     * occlusion_samples rays. Lighting then scales the ambient term by it.
+     * Unbounded objects like planes keep 1.0.
+     */
+    pub fn bake_ao(&mut self, samples: usize) {
+        let factors: Vec<F3D> = self
+            .objects
+            .iter()
+            .map(|o| self.object_occlusion(o, samples))
+            .collect();
+        for (o, factor) in self.objects.iter_mut().zip(factors) {
+            o.set_ao_factor(factor);
+        }
+    }
+
+    fn object_occlusion(&self, object: &Object, samples: usize) -> F3D {
+        let (center, radius) = object.bounding_sphere();
+        if samples == 0 || !radius.is_finite() {
+            return 1.0;
+        }
+        let mut rng = rand::thread_rng();
+        let (mut total, mut hits) = (0.0, 0);
+
+        for _ in 0..samples {
+            let dir = random_in_unit_sphere(&mut rng).normalize();
+            let r = Ray::new(center + dir * (radius + 1.0), -dir);
+            let xs = object.intersect(&r);
+            if let Some(is) = xs.hit() {
+                let p = r.position(is.t);
+                let mut normalv = is.object.normal_at(p, Some(is));
+                if normalv.dot(&r.direction) > 0.0 {
+                    normalv = -normalv;
+                }
+                let over_point = p + normalv * self.surface_offset;
+                total += self.occlusion_at(&over_point, &normalv, self.occlusion_samples);
+                hits += 1;
+            }
+        }
+        if hits == 0 {
+            1.0
         } else {
-            let m = comps.object.get_material();
-            if m.reflective == 0.0 {
+            total / hits as F3D
+        }
+    }
+
+    /**
+     * Debug helper: returns a see-through cube for every object's bounding box
+     * (recursing into group children) that can be added to a scratch world.
+     * Unbounded shapes like planes are skipped.
+     */
+    pub fn render_bounds(&self) -> Vec<Object> {
+        let mut boxes = vec![];
+
+        for o in self.objects.iter() {
+            add_bounds_boxes(o, &mut boxes);
+        }
+        boxes
+    }
+
+    /**
+     * Color seen along a secondary ray with `left` of its kind's `max`
+     * bounces remaining, traced on with the `next` budget. With
+     * fade_reflections the traced color is blended with the background by
+     * left / max, so it reaches the background (not black) when the bounces
+     * run out.
+     */
+    fn secondary_color(&self, ray: &Ray, left: u8, max: u8, next: Depth) -> Color {
+        if !self.fade_reflections {
+            if left == 0 {
                 Color::black()
             } else {
-                let reflection_ray = Ray::new(comps.over_point, comps.reflectv);
-                let c = self.color_at(&reflection_ray, remaining - 1);
-                c * m.reflective
+                self.color_at_depth(ray, next)
             }
+        } else if left == 0 || max == 0 {
+            self.background_at(ray)
+        } else {
+            let f = (left as F3D / max as F3D).min(1.0);
+            self.color_at_depth(ray, next) * f + self.background_at(ray) * (1.0 - f)
         }
     }
 
-    pub fn refracted_color(&self, comps: &Computations, remaining: u8) -> Color {
-        if remaining == 0 || comps.object.get_material().transparency == 0.0 {
+    // secondary_color spending one reflection bounce
+    fn reflection_color(&self, ray: &Ray, remaining: Depth) -> Color {
+        let next = Depth {
+            reflection: remaining.reflection.saturating_sub(1),
+            ..remaining
+        };
+        self.secondary_color(ray, remaining.reflection, self.max_depth, next)
+    }
+
+    pub fn reflected_color(&self, comps: &Computations, remaining: impl Into<Depth>) -> Color {
+        self.stats.record_secondary_shade();
+        let remaining = remaining.into();
+        let m = comps.object.get_material();
+        if m.reflective == 0.0 {
             Color::black()
+        } else if m.reflection_roughness <= 0.0 {
+            self.reflection_color(&Ray::reflected(comps), remaining) * m.reflective
         } else {
-            // use snell's law
-            // glm::refract_vec() should work here but does not
-            let eta = comps.n1 / comps.n2;
-            let ni = comps.eyev.dot(&comps.normalv);
-            let sin2_t = eta.powi(2) * (1.0 - ni.powi(2));
+            self.glossy_color(comps, m.reflection_roughness, remaining) * m.reflective
+        }
+    }
 
-            if sin2_t > 1.0 {
-                Color::black()
-            } else {
-                // spawn a refracted ray
-                let k = (1.0 - sin2_t).sqrt();
-                let direction = (comps.normalv * (eta * ni - k)) - (comps.eyev * eta);
-                let refract_ray = Ray::new(comps.under_point, direction);
+    /**
+     * One reflection ray tilted by a random offset within a sphere of radius
+     * roughness around the mirror direction. Each call picks a new offset,
+     * so the blur comes from averaging the camera's samples per pixel.
+     */
+    fn glossy_color(&self, comps: &Computations, roughness: F3D, remaining: Depth) -> Color {
+        let mut rng = rand::thread_rng();
+        let mirror = Ray::reflected(comps);
+        let offset = random_in_unit_sphere(&mut rng) * roughness;
+        let mut ray = mirror;
+        ray.direction = (mirror.direction + offset).normalize();
+        // keep samples from going through the surface
+        if ray.direction.dot(&comps.normalv) <= 0.0 {
+            ray.direction = mirror.direction;
+        }
+        self.reflection_color(&ray, remaining)
+    }
 
-                let c = self.color_at(&refract_ray, remaining - 1);
-                c * comps.object.get_material().transparency
+    pub fn refracted_color(&self, comps: &Computations, remaining: impl Into<Depth>) -> Color {
+        self.stats.record_secondary_shade();
+        let remaining = remaining.into();
+        let m = comps.object.get_material();
+        if m.transparency == 0.0 {
+            Color::black()
+        } else {
+            // use snell's law, no light gets through on total internal reflection
+            match Ray::refracted(comps) {
+                Some(refract_ray) => {
+                    let next = Depth {
+                        refraction: remaining.refraction.saturating_sub(1),
+                        ..remaining
+                    };
+                    self.secondary_color(
+                        &refract_ray,
+                        remaining.refraction,
+                        self.max_refraction_depth,
+                        next,
+                    ) * m.transparency
+                }
+                None => Color::black(),
             }
         }
     }
 }
 
+// rejection sample a nonzero vector in the unit ball, uniform by volume
+fn random_in_unit_sphere(rng: &mut impl Rng) -> Vector {
+    loop {
+        let v = vector(
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+        );
+        let m = v.magnitude_squared();
+        if m > 0.0 && m <= 1.0 {
+            return v;
+        }
+    }
+}
+
+fn add_bounds_boxes(object: &Object, boxes: &mut Vec<Object>) {
+    let b = object.bounds();
+    let size = b.max - b.min;
+
+    if size.x.is_finite() && size.y.is_finite() && size.z.is_finite() {
+        // unit cube spans -1 -> 1, flat shapes still need a non-zero scale
+        let half = size / 2.0;
+        let center = b.min + half;
+        let mut bbox = cube();
+        bbox.has_shadow = false;
+        bbox.set_material(Material {
+            diffuse: 0.1,
+            specular: 0.0,
+            transparency: 0.9,
+            ..Material::default()
+        });
+        bbox.set_transform(
+            &(make_translation(center.x, center.y, center.z)
+                * make_scaling(
+                    half.x.max(EPSILON),
+                    half.y.max(EPSILON),
+                    half.z.max(EPSILON),
+                )),
+        );
+        boxes.push(bbox);
+    }
+    if let Shape::Group(g) = object.shape() {
+        for child in g.children() {
+            add_bounds_boxes(child, boxes);
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl World {
+    pub fn to_json(&self) -> Result<String, SceneError> {
+        if self.background_pattern.is_some() {
+            return Err(SceneError::Unsupported(String::from("background pattern")));
+        }
+        if self.environment.is_some() {
+            return Err(SceneError::Unsupported(String::from("environment map")));
+        }
+        let desc = WorldDesc {
+            lights: self.lights.clone(),
+            objects: self
+                .objects
+                .iter()
+                .map(ObjectDesc::from_object)
+                .collect::<Result<_, _>>()?,
+            use_ambient_occlusion: self.use_ambient_occlusion,
+            occlusion_samples: self.occlusion_samples,
+            occlusion_radius: self.occlusion_radius,
+            max_depth: self.max_depth,
+            max_refraction_depth: Some(self.max_refraction_depth),
+            fade_reflections: self.fade_reflections,
+            surface_offset: self.surface_offset,
+            shadow_bias: self.shadow_bias,
+            background: self.background,
+        };
+        Ok(serde_json::to_string(&desc)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<World, SceneError> {
+        let desc: WorldDesc = serde_json::from_str(json)?;
+        let mut world = World::new(desc.lights);
+        for o in &desc.objects {
+            world.add_shape(o.to_object()?);
+        }
+        world.use_ambient_occlusion = desc.use_ambient_occlusion;
+        world.occlusion_samples = desc.occlusion_samples;
+        world.occlusion_radius = desc.occlusion_radius;
+        world.max_depth = desc.max_depth;
+        world.max_refraction_depth = desc.max_refraction_depth.unwrap_or(desc.max_depth);
+        world.fade_reflections = desc.fade_reflections;
+        world.surface_offset = desc.surface_offset;
+        world.shadow_bias = desc.shadow_bias;
+        world.background = desc.background;
+        Ok(world)
+    }
+}
+
+impl Extend<Object> for World {
+    fn extend<I: IntoIterator<Item = Object>>(&mut self, iter: I) {
+        self.add_objects(iter);
+    }
+}
+
+// a world with no lights yet
+impl FromIterator<Object> for World {
+    fn from_iter<I: IntoIterator<Item = Object>>(iter: I) -> Self {
+        let mut world = World::new(vec![]);
+        world.add_objects(iter);
+        world
+    }
+}
+
 impl Default for World {
     fn default() -> Self {
         let light = point_light(point(-10.0, 10.0, -10.0), Color::white());
@@ -175,6 +777,7 @@ impl Default for World {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::canvas::Canvas;
     use crate::color::Color;
     use crate::computations::prepare_computations;
     use crate::lights::point_light;
@@ -184,7 +787,6 @@ mod tests {
     use crate::pattern;
     use crate::shapes::plane::plane;
     use crate::shapes::sphere::*;
-    use crate::transformation::*;
     use std::f64::consts::SQRT_2;
 
     #[test]
@@ -219,6 +821,156 @@ mod tests {
         assert_eq!(xs[3].t, 6.0);
     }
 
+    #[test]
+    fn intersecting_into_a_dirty_buffer() {
+        let world = World::default();
+        let mut buf = Intersections::new();
+        // leftovers from an earlier ray
+        world.intersect_into(&Ray::new(point(0.0, 0.5, -5.0), vector_z()), &mut buf);
+        assert!(!buf.is_empty());
+
+        let ray = Ray::new(point(0.0, 0.0, -5.0), vector_z());
+        world.intersect_into(&ray, &mut buf);
+        let xs = world.intersect(&ray);
+        let ts = |xs: &Intersections| xs.iter().map(|i| i.t).collect::<Vec<_>>();
+        assert_eq!(ts(&buf), ts(&xs));
+        assert_eq!(ts(&buf), vec![4.0, 4.5, 5.5, 6.0]);
+        assert_eq!(world.color_at_with(&ray, &mut buf), world.color_at(&ray));
+    }
+
+    #[test]
+    fn surface_offset_trades_acne_for_gaps() {
+        let mut world = World::new(vec![point_light(point(1e5, 1e4, 1e5), Color::white())]);
+        // far from the origin, rounding error in hit points is large
+        world
+            .add_shape(sphere().with_transformation(
+                make_translation(1e5, 0.0, 1e5) * make_scaling(2.0, 2.0, 2.0),
+            ));
+        world.add_shape(plane().with_transformation(make_translation(0.0, -2.0, 0.0)));
+        let light = world.lights()[0].position();
+        let acne = |eps: F3D| {
+            (0..200)
+                .filter(|i| {
+                    let x = 1e5 - 1.0 + *i as F3D * 0.01;
+                    let ray = Ray::new(point(x, 10.0, 1e5 + 0.3), vector(0.0, -1.0, 0.0));
+                    let xs = world.intersect(&ray);
+                    let comps = prepare_computations_eps(xs.hit().unwrap(), &ray, &xs, eps);
+                    world.is_shadowed(&light, &comps.over_point, 0.0)
+                })
+                .count()
+        };
+        assert!(acne(0.0) > 0);
+        assert_eq!(acne(EPSILON), 0);
+        assert_eq!(acne(0.01), 0);
+
+        // the larger offset still keeps the contact shadow under the sphere
+        let ray = Ray::new(point(1e5 + 0.5, -1.0, 1e5), vector(0.0, -1.0, 0.0));
+        let xs = world.intersect(&ray);
+        let comps = prepare_computations_eps(xs.hit().unwrap(), &ray, &xs, 0.01);
+        assert!(world.is_shadowed(&light, &comps.over_point, 0.0));
+        assert_eq!(World::new(vec![]).surface_offset, EPSILON);
+    }
+
+    #[test]
+    fn shadow_bias_scales_with_the_scene() {
+        // a fixed EPSILON offset is below the rounding step this far out
+        let far = 1e13;
+        let mut world = World::new(vec![point_light(
+            point(far, far + 1e4, far),
+            Color::white(),
+        )]);
+        world
+            .add_shape(sphere().with_transformation(
+                make_translation(far, far, far) * make_scaling(2.0, 2.0, 2.0),
+            ));
+        world.add_shape(plane().with_transformation(make_translation(0.0, far - 2.0, 0.0)));
+        let light = world.lights()[0].position();
+        let shadowed = |world: &World, origin: Point| {
+            let ray = Ray::new(origin, vector(0.0, -1.0, 0.0));
+            let xs = world.intersect(&ray);
+            let comps = prepare_computations(xs.hit().unwrap(), &ray, &xs);
+            world.is_shadowed(&light, &world.shadow_origin(&comps), 0.0)
+        };
+        let acne = |world: &World| {
+            (0..200)
+                .filter(|i| shadowed(world, point(far - 1.0 + *i as F3D * 0.01, far + 10.0, far)))
+                .count()
+        };
+        // on the plane, under the sphere 1 unit from where they touch
+        let contact = point(far + 1.0, far - 1.9, far);
+
+        assert_eq!(world.shadow_bias, 0.0);
+        assert!(acne(&world) > 0);
+
+        world.shadow_bias = 1e-14;
+        assert_eq!(acne(&world), 0);
+        assert!(shadowed(&world, contact));
+
+        // too much and the shadow comes away from the sphere
+        world.shadow_bias = 1e-12;
+        assert!(!shadowed(&world, contact));
+    }
+
+    #[test]
+    fn shadow_bias_reattaches_thin_shadows() {
+        // a coin thinner than EPSILON lying on the floor far from the origin
+        let far = 1e6;
+        let mut world = World::new(vec![point_light(
+            point(far - 10.0, 10.0, far),
+            Color::white(),
+        )]);
+        world.add_shape(
+            cube().with_transformation(
+                make_translation(far, 2e-5, far) * make_scaling(0.5, 2e-5, 0.5),
+            ),
+        );
+        world.add_shape(plane());
+        let light = world.lights()[0].position();
+        // on the floor just past the coin's edge, on the side away from the light
+        let shadowed = |world: &World| {
+            let ray = Ray::new(point(far + 0.5 + 2e-5, 1.0, far), vector(0.0, -1.0, 0.0));
+            let xs = world.intersect(&ray);
+            let comps = prepare_computations(xs.hit().unwrap(), &ray, &xs);
+            world.is_shadowed(&light, &world.shadow_origin(&comps), 0.0)
+        };
+
+        // the default offset starts the shadow ray above the coin
+        assert!(!shadowed(&world));
+
+        world.shadow_bias = 1e-12;
+        assert!(shadowed(&world));
+    }
+
+    #[test]
+    fn casting_a_ray_reports_what_it_hit() {
+        let world = World::default();
+        let (id, p, normal) = world
+            .cast(&Ray::new(point(0.0, 0.0, -5.0), vector_z()))
+            .unwrap();
+        assert_eq!(id, "sphere_s1");
+        assert_eq!(p, point(0.0, 0.0, -1.0));
+        assert_eq!(normal, vector(0.0, 0.0, -1.0));
+        assert!(world
+            .cast(&Ray::new(point(0.0, 5.0, -5.0), vector_z()))
+            .is_none());
+    }
+
+    #[test]
+    fn pushing_a_second_light_brightens() {
+        let mut world = World::default();
+        let ray = Ray::new(point(0.0, 0.0, -5.0), vector_z());
+        let one = world.color_at(&ray);
+
+        world.push_light(point_light(point(0.0, 0.0, -10.0), Color::white()));
+        assert_eq!(world.lights().len(), 2);
+        let two = world.color_at(&ray);
+        assert!(two.red() > one.red() && two.green() > one.green());
+
+        world.clear_lights();
+        assert!(world.lights().is_empty());
+        assert_eq!(world.color_at(&ray), Color::black());
+    }
+
     #[test]
     fn shading_an_intersection() {
         let world = World::default();
@@ -254,7 +1006,7 @@ mod tests {
     fn color_when_ray_misses() {
         let world = World::default();
         let ray = Ray::new(point(0.0, 0.0, -5.0), vector_y());
-        let c = world.color_at(&ray, MAX_RAY_DEPTH);
+        let c = world.color_at(&ray);
         assert_eq!(c, Color::black());
     }
 
@@ -262,61 +1014,81 @@ mod tests {
     fn color_when_ray_hits() {
         let world = World::default();
         let ray = Ray::new(point(0.0, 0.0, -5.0), vector_z());
-        let c = world.color_at(&ray, MAX_RAY_DEPTH);
+        let c = world.color_at(&ray);
         assert_eq_eps!(c.tuple(), Color::new(0.38066, 0.47583, 0.2855).tuple());
     }
 
     #[test]
     fn color_with_intersection_behind_ray() {
         let mut world = World::default();
-
-        // all this to change the material ambient value :(
-        let outer = world.get_shape(0);
-        let om = outer.get_material().clone();
-        let om2 = Material { ambient: 1.0, ..om };
-        let mut o2 = outer.clone();
-        o2.set_material(om2);
-        world.set_shape(o2, 0);
-
-        let inner = world.get_shape(1);
-        let im = inner.get_material().clone();
-        let im2 = Material { ambient: 1.0, ..im };
-        let mut i2 = inner.clone();
-        i2.set_material(im2);
-        world.set_shape(i2, 1);
+        world.map_shapes(|o| o.material.ambient = 1.0);
 
         let ray = Ray::new(point(0.0, 0.0, 0.75), vector(0.0, 0.0, -1.0));
-        let c = world.color_at(&ray, MAX_RAY_DEPTH);
+        let c = world.color_at(&ray);
         let i3 = world.get_shape(1);
         assert_eq!(c.tuple(), i3.get_material().color.tuple());
     }
 
+    #[test]
+    fn default_with_ambient_sets_every_object() {
+        let world = World::default_with_ambient(1.0);
+        assert_eq!(world.get_shape(0).get_material().ambient, 1.0);
+        assert_eq!(world.get_shape(1).get_material().ambient, 1.0);
+        // looking out from inside the inner sphere only sees its own surface
+        let ray = Ray::new(point_zero(), vector_x());
+        assert_eq!(
+            world.color_at(&ray),
+            world.get_shape(1).get_material().color
+        );
+    }
+
     #[test]
     fn no_shadow_when_nothing_is_collinear_with_point_and_light() {
         let world = World::default();
         let p = point(0.0, 10.0, 0.0);
-        assert!(!world.is_shadowed(&world.lights[0].position(), &p));
+        assert!(!world.is_shadowed(&world.lights[0].position(), &p, 0.0));
     }
 
     #[test]
     fn shadow_when_object_between_point_and_light() {
         let world = World::default();
         let p = point(10.0, -10.0, 10.0);
-        assert!(world.is_shadowed(&world.lights[0].position(), &p));
+        assert!(world.is_shadowed(&world.lights[0].position(), &p, 0.0));
     }
 
     #[test]
     fn no_shadow_when_object_behind_light() {
         let world = World::default();
         let p = point(-20.0, 20.0, -20.0);
-        assert!(!world.is_shadowed(&world.lights[0].position(), &p));
+        assert!(!world.is_shadowed(&world.lights[0].position(), &p, 0.0));
+    }
+
+    #[test]
+    fn occlusion_does_not_depend_on_ray_direction_length() {
+        let world = World::default();
+        let light = world.lights[0].position();
+        for (p, shadowed) in [
+            (point(10.0, -10.0, 10.0), true),
+            (point(-20.0, 20.0, -20.0), false),
+            (point(0.0, 10.0, 0.0), false),
+        ] {
+            let v = light - p;
+            let distance = v.magnitude();
+            assert_eq!(world.is_shadowed(&light, &p, 0.0), shadowed);
+            assert_eq!(world.is_occluded(&Ray::new(p, v), distance), shadowed);
+            assert_eq!(world.is_occluded(&Ray::new(p, v * 0.1), distance), shadowed);
+            assert_eq!(
+                world.is_occluded(&Ray::new_normalized(p, v), distance),
+                shadowed
+            );
+        }
     }
 
     #[test]
     fn no_shadow_when_object_behind_point() {
         let world = World::default();
         let p = point(-2.0, 2.0, -2.0);
-        assert!(!world.is_shadowed(&world.lights[0].position(), &p));
+        assert!(!world.is_shadowed(&world.lights[0].position(), &p, 0.0));
     }
 
     #[test]
@@ -377,6 +1149,65 @@ mod tests {
         assert_eq_eps!(color.tuple(), Color::new(0.19032, 0.2379, 0.14274).tuple());
     }
 
+    #[test]
+    fn rough_reflections_blur_an_edge() {
+        // mirror floor reflecting a black ball on a white background, with the
+        // reflected ray grazing the ball's silhouette
+        let mut world = World::new(vec![point_light(point(0.0, 10.0, -10.0), Color::white())]);
+        world.background = Color::white();
+        let mut ball = sphere().with_material(Material::matte(Color::black()));
+        ball.set_transform(&make_translation(1.0, 2.0, 2.0));
+        world.add_shape(ball);
+
+        let reflected_samples = |roughness: F3D| {
+            let floor = plane().with_material(Material {
+                reflective: 1.0,
+                ..Material::default().with_reflection_roughness(roughness)
+            });
+            let r = Ray::new(point(0.0, 1.0, -1.0), vector(0.0, -1.0, 1.0).normalize());
+            let i = Intersection::new(&floor, SQRT_2);
+            let comps =
+                prepare_computations(&i, &r, &Intersections::from_intersections(vec![i.clone()]));
+            (0..50)
+                .map(|_| world.reflected_color(&comps, MAX_RAY_DEPTH).luminance())
+                .collect::<Vec<F3D>>()
+        };
+        let variance = |xs: &[F3D]| {
+            let mean = xs.iter().sum::<F3D>() / xs.len() as F3D;
+            xs.iter().map(|x| (x - mean).powi(2)).sum::<F3D>() / xs.len() as F3D
+        };
+
+        let sharp = reflected_samples(0.0);
+        assert!(sharp.iter().all(|l| *l == sharp[0]));
+        let rough = reflected_samples(0.3);
+        assert!(variance(&rough) > variance(&sharp));
+        // averaged over calls, like camera samples, the edge is in between
+        assert!(rough.iter().all(|l| (0.0..=1.0).contains(l)));
+        let mean = rough.iter().sum::<F3D>() / rough.len() as F3D;
+        assert!(mean > 0.0 && mean < 1.0, "{}", mean);
+    }
+
+    #[test]
+    fn rough_mirrors_trace_one_ray_per_bounce() {
+        let mut world = World::new(vec![
+            point_light(point(-5.0, 0.0, 0.0), Color::white()),
+            point_light(point(5.0, 0.0, 0.0), Color::white()),
+        ])
+        .with_stats();
+        for y in [-1.0, 1.0] {
+            let mut mirror = plane().with_material(Material {
+                reflective: 1.0,
+                ..Material::default().with_reflection_roughness(0.2)
+            });
+            mirror.set_transform(&make_translation(0.0, y, 0.0));
+            world.add_shape(mirror);
+        }
+        world.color_at(&Ray::new(point_zero(), vector(0.0, 1.0, 0.1).normalize()));
+        // a reflected and a refracted shade per hit, for the first hit and
+        // each of the max_depth bounces
+        assert!(world.stats().secondary_shades <= 2 * (MAX_RAY_DEPTH as usize + 1));
+    }
+
     #[test]
     fn shade_hit_for_reflective_material() {
         let mut world = World::default();
@@ -415,11 +1246,413 @@ mod tests {
         world.add_shape(lower);
         world.add_shape(upper);
         let r = Ray::new(point_zero(), vector_y());
-        let color = world.color_at(&r, MAX_RAY_DEPTH);
+        let color = world.color_at(&r);
         // we should get here
         assert!(color.tuple().x >= 0.0);
     }
 
+    #[test]
+    fn stats_count_camera_and_shadow_rays() {
+        let world = World::default().with_stats();
+        let probe = World::default();
+        let mut c = crate::camera::Camera::new(5, 5, glm::half_pi());
+        c.transform = crate::transformation::view_transform(
+            &point(0.0, 0.0, -5.0),
+            &point_zero(),
+            &vector_y(),
+        );
+        let hits = (0..5)
+            .flat_map(|y| (0..5).map(move |x| (x, y)))
+            .filter(|&(x, y)| probe.intersect(&c.ray_for_pixel(x, y)).hit().is_some())
+            .count();
+        assert!(hits > 0);
+
+        c.render(&world);
+        let stats = world.stats();
+        // one camera ray per pixel plus one shadow ray per hit, unless the
+        // shadow ray missed every bounding box and was never cast
+        assert_eq!(stats.rays_cast + stats.shadow_shortcuts, 5 * 5 + hits);
+        assert_eq!(stats.intersection_tests, stats.rays_cast * 2);
+        assert_eq!(stats.bounding_box_skips, 0);
+    }
+
+    #[test]
+    fn opaque_hits_skip_secondary_shading() {
+        let world = World::default().with_stats();
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector_z());
+        assert_eq_eps!(
+            world.color_at(&r).tuple(),
+            Color::new(0.38066, 0.47583, 0.2855).tuple()
+        );
+        assert_eq!(world.stats().secondary_shades, 0);
+
+        let mut shiny = World::default().with_stats();
+        shiny.map_shapes(|o| o.material.reflective = 0.5);
+        shiny.color_at(&r);
+        assert!(shiny.stats().secondary_shades > 0);
+    }
+
+    #[test]
+    fn clear_shadow_rays_skip_intersection() {
+        let mut world = World::new(vec![]).with_stats();
+        world.add_shape(plane());
+        world.add_shape(
+            crate::shapes::sphere::sphere().with_transformation(make_translation(0.0, 2.0, 0.0)),
+        );
+        let light = point(0.0, 10.0, 0.0);
+
+        assert!(!world.is_shadowed(&light, &point(5.0, 0.001, 0.0), 0.0));
+        let clear = world.stats();
+        assert_eq!(clear.shadow_shortcuts, 1);
+        assert_eq!(clear.rays_cast, 0);
+
+        assert!(world.is_shadowed(&light, &point(0.0, 0.001, 0.0), 0.0));
+        let blocked = world.stats();
+        assert_eq!(blocked.shadow_shortcuts, 1);
+        assert_eq!(blocked.rays_cast, 1);
+        assert!(blocked.intersection_tests > 0);
+    }
+
+    #[test]
+    fn random_points_lie_in_the_unit_ball() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..1000 {
+            let m = random_in_unit_sphere(&mut rng).magnitude();
+            assert!(m > 0.0 && m <= 1.0);
+        }
+    }
+
+    #[test]
+    fn large_csg_casts_shadows() {
+        use crate::shapes::csg::CsgOp;
+        let slab = crate::shapes::cube::cube().with_transformation(make_scaling(3.0, 0.5, 3.0));
+        let csg = Object::new_csg(CsgOp::Union, &slab, &sphere())
+            .with_transformation(make_translation(0.0, 5.0, 0.0));
+        let mut world = World::new(vec![]);
+        world.add_shape(csg);
+        // straight down past the unit cube, but through the slab
+        assert!(world.is_shadowed(&point(2.5, 10.0, 0.0), &point(2.5, 0.0, 0.0), 0.0));
+        assert!(!world.is_shadowed(&point(4.0, 10.0, 0.0), &point(4.0, 0.0, 0.0), 0.0));
+    }
+
+    #[test]
+    fn stats_count_skipped_groups() {
+        let mut world = World::new(vec![]).with_stats();
+        let mut s = sphere();
+        s.set_transform(&make_translation(10.0, 0.0, 0.0));
+        world.add_shape(Object::new_group(vec![s]));
+        world.intersect(&Ray::new(point(0.0, 0.0, -5.0), vector_z()));
+        assert_eq!(
+            world.stats(),
+            RenderStatsSnapshot {
+                rays_cast: 1,
+                intersection_tests: 0,
+                bounding_box_skips: 1,
+                shadow_shortcuts: 0,
+                secondary_shades: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn vertex_colored_triangle_shades_with_blended_color() {
+        let mut world = World::new(vec![point_light(point(0.0, 0.0, -10.0), Color::white())]);
+        let tri = crate::shapes::smooth_triangle::smooth_triangle(
+            point_y(),
+            point(-1.0, 0.0, 0.0),
+            point_x(),
+            vector_z() * -1.0,
+            vector_z() * -1.0,
+            vector_z() * -1.0,
+        )
+        .with_vertex_colors(
+            Color::new(1.0, 0.0, 0.0),
+            Color::new(0.0, 1.0, 0.0),
+            Color::new(0.0, 0.0, 1.0),
+        )
+        .with_material(Material {
+            ambient: 1.0,
+            diffuse: 0.0,
+            specular: 0.0,
+            ..Material::default()
+        });
+        world.add_shape(tri);
+        // centroid of the triangle
+        let r = Ray::new(point(0.0, 1.0 / 3.0, -5.0), vector_z());
+        let c = world.color_at(&r);
+        assert_eq_eps!(c.tuple(), Color::new(1.0, 1.0, 1.0).tuple() / 3.0);
+    }
+
+    #[test]
+    fn finding_and_removing_shapes() {
+        let mut world = World::new(vec![]);
+        for id in ["a", "b", "c"] {
+            world.add_shape(sphere_with_id(Some(id.to_string())));
+        }
+        assert_eq!(world.shape_count(), 3);
+        assert_eq!(world.find_shape("sphere_b").unwrap().id, "b");
+        assert!(world.find_shape("sphere_d").is_none());
+
+        let removed = world.remove_shape(1);
+        assert_eq!(removed.get_id(), "sphere_b");
+        assert_eq!(world.shape_count(), 2);
+        assert!(world.find_shape("sphere_b").is_none());
+        assert_eq!(world.get_shape(0).get_id(), "sphere_a");
+        assert_eq!(world.get_shape(1).get_id(), "sphere_c");
+    }
+
+    #[test]
+    fn adding_objects_in_bulk() {
+        let mut world = World::new(vec![]);
+        world.extend(vec![sphere(), sphere(), sphere()]);
+        assert_eq!(world.shape_count(), 3);
+        world.add_objects([cube()]);
+        assert_eq!(world.shape_count(), 4);
+
+        let collected: World = (0..3).map(|_| sphere()).collect();
+        assert_eq!(collected.shape_count(), 3);
+        assert!(collected.lights().is_empty());
+    }
+
+    #[test]
+    fn soft_shadows_have_a_penumbra() {
+        let mut world = World::new(vec![]);
+        world.add_shape(sphere());
+        let light = match Light::point_soft(point(0.0, 0.0, -10.0), Color::white(), 1.0, 64) {
+            Light::Point(p) => p,
+            _ => unreachable!(),
+        };
+        // right behind the occluder
+        assert_eq!(
+            world.is_shadowed_soft(&light, &point(0.0, 0.0, 10.0), 64, 0.0),
+            1.0
+        );
+        // on the line grazing the sphere from the light's center
+        let edge = world.is_shadowed_soft(&light, &point(2.0101, 0.0, 10.0), 64, 0.0);
+        assert!(edge > 0.0 && edge < 1.0, "edge occlusion {}", edge);
+        // well outside the shadow
+        assert_eq!(
+            world.is_shadowed_soft(&light, &point(10.0, 0.0, 10.0), 64, 0.0),
+            0.0
+        );
+    }
+
+    #[test]
+    fn placeholder_objects_are_skipped() {
+        let mut world = World::default();
+        let nshapes = world.shape_count();
+        world.add_shape(Object::new_dummy());
+        let miss = Ray::new(point(0.0, 0.0, -5.0), vector_y());
+        assert_eq!(world.color_at(&miss), Color::black());
+        let hit = Ray::new(point(0.0, 0.0, -5.0), vector_z());
+        assert_eq!(world.intersect(&hit).len(), 4);
+        assert!(world.get_shape(nshapes).intersect(&hit).is_empty());
+    }
+
+    #[test]
+    fn summary_counts_mesh_triangles_and_bounds() {
+        let obj = "v -1 1 0\nv -1 0 0\nv 1 0 0\nv 1 1 0\nf 1 2 3\nf 1 3 4\n";
+        let (models, _) = tobj::load_obj_buf(
+            &mut std::io::BufReader::new(obj.as_bytes()),
+            &tobj::GPU_LOAD_OPTIONS,
+            |_| Ok(Default::default()),
+        )
+        .unwrap();
+        let mesh = crate::obj_file::ObjData::new(models).to_group();
+        let mut world = World::default();
+        world.add_shape(mesh.transform(&make_translation(0.0, 0.0, 5.0)));
+
+        let summary = world.summary();
+        assert_eq!(summary.objects, 3);
+        assert_eq!(summary.primitives, 4);
+        assert_eq!(summary.triangles, 2);
+        assert_eq!(summary.lights, 1);
+        assert_eq!(summary.bounds.min, point(-1.0, -1.0, -1.0));
+        assert_eq!(summary.bounds.max, point(1.0, 1.0, 5.0));
+    }
+
+    #[test]
+    fn missing_ray_returns_background() {
+        let blue = Color::new(0.0, 0.0, 1.0);
+        let world = World::default().with_background(blue);
+        let miss = Ray::new(point(0.0, 0.0, -5.0), vector_y());
+        assert_eq!(world.color_at(&miss), blue);
+        assert_eq!(World::default().color_at(&miss), Color::black());
+    }
+
+    #[test]
+    fn background_pattern_is_sampled_by_direction() {
+        let mut world = World::new(vec![]);
+        world.background_pattern = Some(pattern::TPattern::Gradient(
+            pattern::gradient::gradient_pattern(Color::black(), Color::white()),
+        ));
+        let a = world.color_at(&Ray::new(point_zero(), vector(1.0, 1.0, 0.0)));
+        let b = world.color_at(&Ray::new(point_zero(), vector_z()));
+        assert_ne!(a, b);
+        assert_eq!(b, Color::black());
+    }
+
+    #[test]
+    fn moving_objects_cast_shadows_where_they_are() {
+        let mut world = World::new(vec![point_light(point(-10.0, 0.0, 0.0), Color::white())]);
+        world.add_shape(sphere().with_motion(make_translation(1.0, 0.0, 0.0)));
+        let light = world.lights()[0].clone();
+
+        // at time 1 the near side of the sphere is where its center started
+        let ray = Ray::new(point(-5.0, 0.0, 0.0), vector_x()).with_time(1.0);
+        let xs = world.intersect(&ray);
+        let comps = prepare_computations(xs.hit().unwrap(), &ray, &xs);
+        assert!(comps.point.x.abs() < EPSILON);
+
+        assert!(!world.is_shadowed(&light.position(), &comps.over_point, comps.time));
+        assert_eq!(
+            light.intensity_at(&world, &comps.over_point, comps.time),
+            1.0
+        );
+        // the time 0 sphere would have covered it
+        assert!(world.is_shadowed(&light.position(), &comps.over_point, 0.0));
+    }
+
+    #[test]
+    fn emission_is_added_once_per_hit() {
+        let glow = |lights| {
+            let mut world = World::new(lights);
+            let mut s = sphere();
+            s.set_material(
+                Material {
+                    ambient: 0.0,
+                    diffuse: 0.0,
+                    specular: 0.0,
+                    ..Material::default()
+                }
+                .with_emissive(Color::new(0.2, 0.3, 0.4)),
+            );
+            world.add_shape(s);
+            world.color_at(&Ray::new(point(0.0, 0.0, -5.0), vector_z()))
+        };
+        let two_lights = glow(vec![
+            point_light(point(-10.0, 10.0, -10.0), Color::white()),
+            point_light(point(10.0, 10.0, -10.0), Color::white()),
+        ]);
+        assert_eq_eps!(two_lights.tuple(), Color::new(0.2, 0.3, 0.4).tuple());
+        // and still glows in the dark
+        let no_lights = glow(vec![]);
+        assert_eq_eps!(no_lights.tuple(), Color::new(0.2, 0.3, 0.4).tuple());
+    }
+
+    #[test]
+    fn environment_map_is_sampled_by_direction() {
+        // 4x2 equirectangular sky: white on top, blue below
+        let mut sky = Canvas::new(4, 2, Some(Color::white()));
+        for x in 0..4 {
+            sky.write_pixel(x, 1, Color::new(0.0, 0.0, 1.0));
+        }
+        let mut world = World::new(vec![]);
+        world.background_pattern = Some(pattern::TPattern::Gradient(
+            pattern::gradient::gradient_pattern(Color::black(), Color::black()),
+        ));
+        world.environment = Some(ImagePattern::new(sky));
+
+        let up = world.color_at(&Ray::new(point_zero(), vector_y()));
+        let down = world.color_at(&Ray::new(point_zero(), vector(0.0, -1.0, 0.0)));
+        assert_eq!(up, Color::white());
+        assert_eq!(down, Color::new(0.0, 0.0, 1.0));
+
+        // and mirrors reflect it
+        let mut mirror = plane();
+        mirror.material.reflective = 1.0;
+        mirror.set_transform(&make_translation(0.0, -1.0, 0.0));
+        world.add_shape(mirror);
+        world.push_light(point_light(point(0.0, 10.0, 0.0), Color::black()));
+        let reflected = world.color_at(&Ray::new(point_zero(), vector(0.0, -1.0, 0.0)));
+        assert_eq!(reflected, Color::white());
+    }
+
+    #[test]
+    fn higher_max_depth_adds_mirror_bounces() {
+        let mirrors = |depth| {
+            let mut world =
+                World::new(vec![point_light(point_zero(), Color::white())]).with_max_depth(depth);
+            let mut lower = plane();
+            lower.material.reflective = 0.5;
+            lower.set_transform(&make_translation(0.0, -1.0, 0.0));
+            let mut upper = plane();
+            upper.material.reflective = 0.5;
+            upper.set_transform(&make_translation(0.0, 1.0, 0.0));
+            world.add_shape(lower);
+            world.add_shape(upper);
+            world
+        };
+        let r = Ray::new(point_zero(), vector_y());
+        let shallow = mirrors(1).color_at(&r);
+        let deep = mirrors(5).color_at(&r);
+        assert_eq!(mirrors(5).max_depth, 5);
+        assert!(deep.red() > shallow.red());
+        assert_eq!(World::new(vec![]).max_depth, MAX_RAY_DEPTH);
+    }
+
+    #[test]
+    fn refraction_budget_lets_rays_through_nested_glass() {
+        let nested = |refraction_depth| {
+            let mut world =
+                World::new(vec![point_light(point(-10.0, 10.0, -10.0), Color::white())])
+                    .with_max_depth(1)
+                    .with_max_refraction_depth(refraction_depth)
+                    .with_background(Color::white());
+            let mut outer = sphere().with_transformation(make_scaling(2.0, 2.0, 2.0));
+            outer.set_material(Material::glass());
+            let mut inner = sphere();
+            inner.set_material(Material::glass());
+            world.add_shape(outer);
+            world.add_shape(inner);
+            world
+        };
+        // through the middle: 4 refractions before reaching the background
+        let r = Ray::new(point(0.0, 0.0, -5.0), vector_z());
+        let starved = nested(3).color_at(&r);
+        let enough = nested(4).color_at(&r);
+        assert!(starved.luminance() < 0.1, "{}", starved);
+        assert!(enough.luminance() > 0.5, "{}", enough);
+        assert_eq!(World::new(vec![]).max_refraction_depth, MAX_RAY_DEPTH);
+    }
+
+    #[test]
+    fn faded_reflections_have_no_black_band() {
+        let hall = |fade| {
+            let mut world = World::new(vec![point_light(point_zero(), Color::white())])
+                .with_background(Color::new(0.5, 0.5, 0.5));
+            world.fade_reflections = fade;
+            // perfect black mirrors, everything seen comes from bounces
+            let mirror = Material {
+                color: Color::black(),
+                ambient: 0.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                reflective: 1.0,
+                ..Material::default()
+            };
+            for y in [-1.0, 1.0] {
+                world.add_shape(
+                    plane()
+                        .with_material(mirror.clone())
+                        .with_transformation(make_translation(0.0, y, 0.0)),
+                );
+            }
+            world
+        };
+        let row = |world: World| -> Vec<Color> {
+            (0..8)
+                .map(|i| {
+                    let d = vector(i as F3D * 0.5, 1.0, 0.0).normalize();
+                    world.color_at(&Ray::new(point_zero(), d))
+                })
+                .collect()
+        };
+        assert!(row(hall(false)).iter().all(|&c| c == Color::black()));
+        assert!(row(hall(true)).iter().all(|c| c.red() > 0.0));
+        assert!(!World::new(vec![]).fade_reflections);
+    }
+
     #[test]
     fn reflective_color_at_max_recursive_depth() {
         let mut world = World::default();
@@ -555,7 +1788,98 @@ mod tests {
         )]);
         let comps = prepare_computations(&xs[0], &ray, &xs);
         let color = world.shade_hit(&comps, MAX_RAY_DEPTH);
-        assert_eq_eps!(color.tuple(), Color::new(0.93642, 0.68642, 0.68642).tuple());
+        // refracted light is weighted by 1 - schlick reflectance
+        assert_eq_eps!(color.tuple(), Color::new(0.92591, 0.68642, 0.68642).tuple());
+    }
+
+    #[test]
+    fn render_channels_add_up_to_full_color() {
+        let mut world = World::default();
+        let mut floor = plane();
+        floor.set_transform(&make_translation(0.0, -1.0, 0.0));
+        floor.set_material(Material {
+            reflective: 0.5,
+            transparency: 0.5,
+            refractive_index: 1.5,
+            ..Material::default()
+        });
+        world.add_shape(floor);
+        let mut ball = sphere();
+        ball.set_transform(&make_translation(0.0, -3.5, -0.5));
+        ball.set_material(Material {
+            color: Color::new(1.0, 0.0, 0.0),
+            ambient: 0.5,
+            ..Material::default()
+        });
+        world.add_shape(ball);
+
+        let ray = Ray::new(
+            point(0.0, 0.0, -3.0),
+            vector(0.0, -SQRT_2_DIV_2, SQRT_2_DIV_2),
+        );
+        let full = world.color_at_channel(&ray, RenderChannel::Full);
+        let surface = world.color_at_channel(&ray, RenderChannel::Surface);
+        let reflected = world.color_at_channel(&ray, RenderChannel::Reflected);
+        let refracted = world.color_at_channel(&ray, RenderChannel::Refracted);
+        assert_eq_eps!(full.tuple(), world.color_at(&ray).tuple());
+        assert_eq_eps!(full.tuple(), (surface + reflected + refracted).tuple());
+        assert!(refracted.red() > 0.0);
+        assert_ne!(surface, full);
+    }
+
+    #[test]
+    fn shade_hit_with_transparent_material_applies_fresnel_without_reflectivity() {
+        let mut world = World::default();
+        let nshapes = world.objects.len();
+
+        let mut floor = plane();
+        floor.set_transform(&make_translation(0.0, -1.0, 0.0));
+        floor.set_material(Material {
+            transparency: 0.5,
+            refractive_index: 1.5,
+            ..Material::default()
+        });
+        world.add_shape(floor);
+
+        let mut ball = sphere();
+        ball.set_transform(&make_translation(0.0, -3.5, -0.5));
+        ball.set_material(Material {
+            color: Color::new(1.0, 0.0, 0.0),
+            ambient: 0.5,
+            ..Material::default()
+        });
+        world.add_shape(ball);
+
+        let ray = Ray::new(
+            point(0.0, 0.0, -3.0),
+            vector(0.0, -SQRT_2_DIV_2, SQRT_2_DIV_2),
+        );
+        let xs = Intersections::from_intersections(vec![Intersection::new(
+            world.get_shape(nshapes),
+            SQRT_2,
+        )]);
+        let comps = prepare_computations(&xs[0], &ray, &xs);
+        let light = &world.lights[0];
+        let surface = comps.object.get_material().lighting(
+            comps.object,
+            light,
+            &comps.over_point,
+            &comps.eyev,
+            &comps.normalv,
+            light.intensity_at(&world, &comps.over_point, 0.0),
+        );
+        let refracted = world.refracted_color(&comps, MAX_RAY_DEPTH);
+        let reflectance = schlick(&comps);
+
+        let color = world.shade_hit(&comps, MAX_RAY_DEPTH);
+        // previously the refracted color was added at full strength
+        let unweighted = surface + refracted;
+        assert!(reflectance > 0.0);
+        assert!(color.red() < unweighted.red());
+        assert_eq_eps!(
+            color.tuple(),
+            (surface + refracted * (1.0 - reflectance)).tuple()
+        );
     }
 
     #[test]
@@ -594,4 +1918,77 @@ mod tests {
         let color = world.shade_hit(&comps, MAX_RAY_DEPTH);
         assert_eq_eps!(color.tuple(), Color::new(0.93391, 0.69643, 0.69243).tuple());
     }
+
+    #[test]
+    fn baked_ao_is_low_for_enclosed_objects() {
+        let mut world = World::new(vec![point_light(point(0.0, 10.0, 0.0), Color::white())]);
+        world.occlusion_radius = 10.0;
+        world.add_shape(sphere());
+        world.add_shape(sphere().transform(&make_scaling(3.0, 3.0, 3.0)));
+        world.bake_ao(20);
+        assert!(world.get_shape(0).ao_factor < 0.2);
+
+        let mut lonely = World::new(vec![point_light(point(0.0, 10.0, 0.0), Color::white())]);
+        lonely.occlusion_radius = 10.0;
+        lonely.add_shape(sphere());
+        lonely.bake_ao(20);
+        assert!(lonely.get_shape(0).ao_factor > 0.95);
+
+        // ambient is scaled by the baked factor
+        let inner = world.get_shape(0);
+        let m = inner.get_material();
+        let light = point_light(point(0.0, 0.0, -10.0), Color::white());
+        let (p, v) = (point(0.0, 0.0, -1.0), vector(0.0, 0.0, -1.0));
+        let shadowed = m.lighting(inner, &light, &p, &v, &v, 0.0);
+        assert_eq!(shadowed, m.color * m.ambient * inner.ao_factor);
+    }
+
+    #[test]
+    fn ambient_occlusion_is_lower_near_contact_point() {
+        let mut world = World::new(vec![point_light(point(0.0, 10.0, 0.0), Color::white())]);
+        world.occlusion_radius = 2.0;
+        let floor = plane();
+        world.add_shape(floor.clone());
+        let mut ball = sphere();
+        ball.set_transform(&make_translation(0.0, 1.0, 0.0));
+        world.add_shape(ball);
+
+        let occlusion_at = |x: F3D| {
+            let ray = Ray::new(point(x, 1.0, 0.0), vector(0.0, -1.0, 0.0));
+            let i = Intersection::new(&floor, 1.0);
+            let comps = prepare_computations(
+                &i,
+                &ray,
+                &Intersections::from_intersections(vec![i.clone()]),
+            );
+            world.ambient_occlusion(&comps, 200)
+        };
+        let near = occlusion_at(1.2);
+        let far = occlusion_at(10.0);
+        assert!(near < far, "near {} >= far {}", near, far);
+        assert_eq!(far, 1.0);
+    }
+
+    #[test]
+    fn ambient_occlusion_disabled_by_default() {
+        let world = World::default();
+        assert!(!world.use_ambient_occlusion);
+    }
+
+    #[test]
+    fn render_bounds_returns_box_per_shape_and_group_child() {
+        let mut world = World::default();
+        let mut s = sphere();
+        s.set_transform(&make_translation(3.0, 0.0, 0.0));
+        let group = Object::new_group(vec![s, cube()]);
+        world.add_shape(group);
+        world.add_shape(plane());
+
+        let boxes = world.render_bounds();
+        // 2 spheres + group + its 2 children, plane is unbounded
+        assert_eq!(boxes.len(), 5);
+        assert_eq!(boxes[0].bounds(), world.get_shape(0).bounds());
+        assert_eq_eps!(boxes[3].bounds().min, point(2.0, -1.0, -1.0));
+        assert_eq_eps!(boxes[3].bounds().max, point(4.0, 1.0, 1.0));
+    }
 }